@@ -4,7 +4,14 @@ use std::io::Write;
 use std::path::Path;
 
 fn main() {
-    model::download_model_files();
+    // The app can download the model at runtime, so builds that don't bundle
+    // it can skip the multi-GB fetch
+    println!("cargo:rerun-if-env-changed=SKIP_MODEL_DOWNLOAD");
+    if std::env::var_os("SKIP_MODEL_DOWNLOAD").is_some() {
+        fs::create_dir_all(model::MODEL_OUTPUT_DIR).expect("Failed to create model directory");
+    } else {
+        model::download_model_files();
+    }
     tauri_build::build()
 }
 
@@ -18,17 +25,32 @@ mod model {
     // const QWEN3_URL: &str =
     //     "https://huggingface.co/Qwen/Qwen3-8B-GGUF/resolve/main/Qwen3-8B-Q4_K_M.gguf?download=true";
     const QWEN3_URL : &str = "https://huggingface.co/Qwen/Qwen3-1.7B-GGUF/resolve/main/Qwen3-1.7B-Q8_0.gguf?download=true";
-
-    struct ModelFile<'a> {
-        filename: &'a str,
-        url: &'a str,
+    // The rust-bert port of M2M100, only needed with the m2m100 feature
+    const M2M100_URL: &str = "https://huggingface.co/facebook/m2m100_418M/resolve/main";
+    const M2M100_FILES: [&str; 4] = [
+        "rust_model.ot",
+        "config.json",
+        "vocab.json",
+        "sentencepiece.bpe.model",
+    ];
+
+    struct ModelFile {
+        // Relative to MODEL_OUTPUT_DIR
+        filename: String,
+        url: String,
     }
 
     pub fn download_model_files() {
-        let files = vec![ModelFile {
-            filename: "Qwen3-1.7B-Q8_0.gguf",
-            url: QWEN3_URL,
+        let mut files = vec![ModelFile {
+            filename: "Qwen3-1.7B-Q8_0.gguf".to_string(),
+            url: QWEN3_URL.to_string(),
         }];
+        if std::env::var_os("CARGO_FEATURE_M2M100").is_some() {
+            files.extend(M2M100_FILES.iter().map(|file| ModelFile {
+                filename: format!("m2m100/{}", file),
+                url: format!("{}/{}?download=true", M2M100_URL, file),
+            }));
+        }
 
         // 1. Create directory if it doesn't exist
         let output_dir = Path::new(MODEL_OUTPUT_DIR);
@@ -43,15 +65,18 @@ mod model {
 
         // 2. Loop through files
         for file in files {
-            let dest_path = output_dir.join(file.filename);
+            let dest_path = output_dir.join(&file.filename);
 
             if dest_path.exists() {
                 // Determine style to look like Cargo's "    Finished ..."
                 print_cargo_style("Skipping", &format!("{} (already exists)", file.filename));
                 continue;
             }
+            if let Some(parent) = dest_path.parent() {
+                fs::create_dir_all(parent).expect("Failed to create model directory");
+            }
 
-            download_file(&client, file.url, &dest_path, file.filename);
+            download_file(&client, &file.url, &dest_path, &file.filename);
         }
     }
 
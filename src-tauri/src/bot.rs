@@ -1,24 +1,214 @@
-use std::sync::Arc;
+use std::collections::{HashMap, VecDeque};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 
 use eyre::WrapErr as _;
-use tokio::sync::Mutex;
+use lingua::Language;
+use lru::LruCache;
+use tokio::sync::{oneshot, Mutex};
 use twitch_api::{
     eventsub::{self, Event, Message, Payload},
+    types::MsgId,
     HelixClient,
 };
-use twitch_oauth2::TwitchToken as _;
+use twitch_oauth2::{Scope, TwitchToken as _};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tauri::{Emitter, Manager};
 
-use crate::{model, websocket, TranslationModelState};
+use crate::{
+    emotes, model, refresh_login, romanize, settings, toxicity, websocket, JoinedChannelState,
+    TranslationModelState,
+};
+
+// Upper bound on how many distinct chatters we remember languages for.
+const TRACKED_CHATTERS: usize = 1000;
+// How many of our replies we can still retract when the original is deleted.
+const TRACKED_REPLIES: usize = 500;
+// Twitch rejects chat messages longer than this.
+pub const MAX_CHAT_MESSAGE_CHARS: usize = 500;
+// A reply that shows the original keeps at least this much of it, cutting
+// the translation instead when both don't fit.
+const MIN_ORIGINAL_CHARS: usize = 50;
+// Placeholders a `reply_template` can use.
+const REPLY_PLACEHOLDERS: [&str; 6] = [
+    "user",
+    "user_original",
+    "user_romanized",
+    "translation",
+    "lang",
+    "original",
+];
+// How long a reply waits for the one before it before going out anyway.
+const MAX_REPLY_WAIT: std::time::Duration = std::time::Duration::from_secs(10);
 
 // Define the payload structure we send to the frontend
 #[derive(Clone, Serialize, Debug)]
 pub struct ChatLogPayload {
+    pub message_id: String,
     pub user: String,
     pub message: String,
     pub timestamp: String,
+    /// `timestamp` in the local timezone, formatted per the settings.
+    pub local_time: String,
+    pub sentiment: Option<model::Sentiment>,
+    pub toxicity: Option<u8>,
+}
+
+/// Sent when a moderator clears the whole chat.
+#[derive(Clone, Serialize, Debug)]
+pub struct ChatClearedPayload {
+    pub broadcaster: String,
+}
+
+/// Sent when a user is banned or timed out and their messages are removed.
+#[derive(Clone, Serialize, Debug)]
+pub struct UserMessagesClearedPayload {
+    pub broadcaster: String,
+    pub user: String,
+    pub user_login: String,
+    pub user_id: String,
+}
+
+/// Sent when a reply is dropped to stay under the chat rate limit.
+#[derive(Clone, Serialize, Debug)]
+pub struct ReplyDroppedPayload {
+    pub message_id: String,
+    pub reply: String,
+}
+
+/// Sent when a message is deleted, so its log entry and translation can go too.
+#[derive(Clone, Serialize, Debug)]
+pub struct TranslationRetractedPayload {
+    pub message_id: String,
+    /// Our translated reply to the deleted message, if we posted one.
+    pub reply_message_id: Option<String>,
+}
+
+/// Sent when a channel's bot stopped because its Twitch login expired.
+#[derive(Clone, Serialize, Debug)]
+pub struct AuthExpiredPayload {
+    pub channel: String,
+    pub error: String,
+}
+
+/// Sent before a crashed bot is restarted.
+#[derive(Clone, Serialize, Debug)]
+pub struct BotReconnectingPayload {
+    pub channel: String,
+    pub attempt: u32,
+    pub delay_ms: u64,
+    pub error: String,
+}
+
+/// Sent once a restarted bot is connected to chat again.
+#[derive(Clone, Serialize, Debug)]
+pub struct BotReconnectedPayload {
+    pub channel: String,
+}
+
+// Restarts wait this long, doubling with each failed attempt up to the cap
+const RECONNECT_BASE_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Error context marking a bot that stopped because the token could no
+/// longer be refreshed or validated. Only logging in again fixes that.
+#[derive(Debug)]
+pub struct AuthExpired;
+
+impl std::fmt::Display for AuthExpired {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Twitch login expired")
+    }
+}
+
+/// What became of a chat message sent for translation.
+#[derive(Clone, Copy, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TranslationOutcome {
+    Translated,
+    /// Already in the target language.
+    AlreadyTarget,
+    /// The model had nothing to add (links, unclear text, ...).
+    Ignored,
+    Failed,
+}
+
+/// Why a chat message was left untranslated.
+#[derive(Clone, Copy, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Universal slang like "GG", or already in the target language,
+    /// English unless configured otherwise.
+    DetectedEnglish,
+    /// The model had nothing to add, or its output failed the quality gate.
+    ModelIgnored,
+    /// Too short, or its language couldn't be told confidently enough.
+    BelowThreshold,
+}
+
+/// Sent next to the `translation-event` of a message that wasn't
+/// translated, so the app can count why.
+#[derive(Clone, Serialize, Debug)]
+pub struct TranslationSkippedPayload {
+    pub channel: String,
+    pub message_id: String,
+    pub language: Option<String>,
+    pub reason: SkipReason,
+}
+
+/// A translation (or the reason there isn't one), for showing next to the
+/// original message in the app.
+#[derive(Clone, Serialize, Debug)]
+pub struct TranslationEventPayload {
+    pub message_id: String,
+    pub user: String,
+    pub original: String,
+    pub translation: Option<String>,
+    pub language: Option<String>,
+    pub status: TranslationOutcome,
+    /// In `Preview` mode, the reply the bot would have posted.
+    pub reply: Option<String>,
+}
+
+/// Whether translations are posted to chat or only shown in the app.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranslationMode {
+    /// Reply to the original message in chat.
+    Reply,
+    /// Translate for the app only.
+    Silent,
+    /// Like `Silent`, but also show the reply that would have been posted.
+    Preview,
+}
+
+/// Our replies keyed by the id of the message they translate.
+pub struct ReplyHistory {
+    replies: LruCache<MsgId, MsgId>,
+}
+
+impl Default for ReplyHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReplyHistory {
+    pub fn new() -> Self {
+        Self {
+            replies: LruCache::new(NonZeroUsize::new(TRACKED_REPLIES).unwrap()),
+        }
+    }
+
+    pub fn record(&mut self, original: MsgId, reply: MsgId) {
+        self.replies.put(original, reply);
+    }
+
+    pub fn take(&mut self, original: &MsgId) -> Option<MsgId> {
+        self.replies.pop(original)
+    }
 }
 
 pub struct Bot {
@@ -26,9 +216,532 @@ pub struct Bot {
     pub client: HelixClient<'static, reqwest::Client>,
     pub token: Arc<Mutex<twitch_oauth2::UserToken>>,
     pub broadcaster: twitch_api::types::UserId,
+    pub broadcaster_login: String,
+    pub language_history: Arc<std::sync::Mutex<LanguageHistory>>,
+    pub third_party_emotes: Arc<emotes::EmoteSet>,
+    /// Whether the token was granted `user:write:chat`.
+    pub can_reply: bool,
+    pub reply_history: Arc<std::sync::Mutex<ReplyHistory>>,
+    pub reply_order: Arc<std::sync::Mutex<ReplyOrder>>,
+    pub debouncer: Arc<std::sync::Mutex<MessageDebouncer>>,
+    pub reply_limiter: Arc<std::sync::Mutex<ReplyLimiter>>,
+    /// Language every message is assumed to be in, skipping detection.
+    pub force_language: Arc<std::sync::Mutex<Option<Language>>>,
+    /// Restarts since the bot was last connected.
+    pub reconnect_attempts: Arc<AtomicU32>,
+    /// What became of this channel's messages, shared with the app.
+    pub stats: Arc<TranslationStats>,
+}
+
+/// Messages a chatter sent within the debounce window, waiting to be
+/// translated together as one.
+#[derive(Default)]
+pub struct MessageDebouncer {
+    pending: HashMap<twitch_api::types::UserId, (u64, Vec<String>)>,
+    next_seq: u64,
+}
+
+impl MessageDebouncer {
+    /// Queues `text` and returns its sequence number for `take_if_latest`.
+    pub fn push(&mut self, user: &twitch_api::types::UserId, text: String) -> u64 {
+        self.next_seq += 1;
+        let entry = self.pending.entry(user.clone()).or_default();
+        entry.0 = self.next_seq;
+        entry.1.push(text);
+        self.next_seq
+    }
+
+    /// Returns everything the user queued if `seq` is still their newest
+    /// message, or `None` if a later message will flush it instead.
+    pub fn take_if_latest(&mut self, user: &twitch_api::types::UserId, seq: u64) -> Option<String> {
+        if self.pending.get(user)?.0 != seq {
+            return None;
+        }
+        self.pending.remove(user).map(|(_, texts)| texts.join(" "))
+    }
+}
+
+/// Hands out turns in the order messages arrive, so replies are posted in
+/// that order even though translations finish whenever they finish.
+#[derive(Default)]
+pub struct ReplyOrder {
+    last: Option<oneshot::Receiver<()>>,
+}
+
+impl ReplyOrder {
+    pub fn take_turn(&mut self) -> ReplyTurn {
+        let (done, next) = oneshot::channel();
+        ReplyTurn {
+            previous: self.last.replace(next),
+            _done: done,
+        }
+    }
+}
+
+/// A place in the reply queue. Dropping it, whether or not a reply was sent,
+/// lets the next message go.
+pub struct ReplyTurn {
+    previous: Option<oneshot::Receiver<()>>,
+    _done: oneshot::Sender<()>,
+}
+
+impl ReplyTurn {
+    /// Waits until the previous message has replied or given up, but no
+    /// longer than `MAX_REPLY_WAIT` so one slow translation can't stall chat.
+    pub async fn wait(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            if tokio::time::timeout(MAX_REPLY_WAIT, previous)
+                .await
+                .is_err()
+            {
+                tracing::warn!("Previous translation is taking too long, replying out of order");
+            }
+        }
+    }
+}
+
+/// Token bucket for outgoing replies. Shared by every channel, since Twitch
+/// rate-limits the account rather than each chat.
+pub struct ReplyLimiter {
+    available: f64,
+    refilled_at: std::time::Instant,
+}
+
+impl Default for ReplyLimiter {
+    fn default() -> Self {
+        Self {
+            // Starts full; clamped to the capacity on first use
+            available: f64::INFINITY,
+            refilled_at: std::time::Instant::now(),
+        }
+    }
+}
+
+impl ReplyLimiter {
+    /// Takes one of `capacity` slots that refill over `window`, or returns
+    /// how long until the next one frees up. A zero `window` never limits.
+    pub fn try_acquire(
+        &mut self,
+        capacity: u32,
+        window: std::time::Duration,
+    ) -> Result<(), std::time::Duration> {
+        self.try_acquire_at(std::time::Instant::now(), capacity, window)
+    }
+
+    fn try_acquire_at(
+        &mut self,
+        now: std::time::Instant,
+        capacity: u32,
+        window: std::time::Duration,
+    ) -> Result<(), std::time::Duration> {
+        // Slots would refill instantly, and 0 / 0 would make the count NaN
+        if window.is_zero() {
+            return Ok(());
+        }
+
+        let capacity = capacity.max(1) as f64;
+        let refill_secs = window.as_secs_f64() / capacity;
+
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.refilled_at = now;
+        self.available = (self.available + elapsed / refill_secs).min(capacity);
+
+        if self.available >= 1.0 {
+            self.available -= 1.0;
+            Ok(())
+        } else {
+            Err(std::time::Duration::from_secs_f64(
+                (1.0 - self.available) * refill_secs,
+            ))
+        }
+    }
+}
+
+/// Recently detected languages per chatter, used to disambiguate short
+/// messages like "ok lol" from a regular who normally writes French.
+pub struct LanguageHistory {
+    users: LruCache<twitch_api::types::UserId, VecDeque<Language>>,
+}
+
+/// Share of a channel's messages detected as one language.
+#[derive(Clone, Serialize, Debug)]
+pub struct LanguageShare {
+    pub language: String,
+    pub count: u64,
+    pub share: f64,
+}
+
+/// What became of a channel's messages this session. Counted from every
+/// message's task, so the counters are atomics; a language only takes the
+/// write lock the first time it's seen.
+#[derive(Default)]
+pub struct TranslationStats {
+    translated: AtomicU64,
+    detected_english: AtomicU64,
+    model_ignored: AtomicU64,
+    below_threshold: AtomicU64,
+    failed: AtomicU64,
+    languages: RwLock<HashMap<String, AtomicU64>>,
+}
+
+/// A copy of a channel's `TranslationStats`, for the frontend.
+#[derive(Clone, Serialize, Debug)]
+pub struct TranslationStatsSnapshot {
+    pub translated: u64,
+    pub detected_english: u64,
+    pub model_ignored: u64,
+    pub below_threshold: u64,
+    pub failed: u64,
+    /// Messages per detected source language, whatever became of them.
+    pub languages: HashMap<String, u64>,
+}
+
+impl TranslationStats {
+    pub fn record_translated(&self) {
+        self.translated.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_skipped(&self, reason: SkipReason) {
+        let counter = match reason {
+            SkipReason::DetectedEnglish => &self.detected_english,
+            SkipReason::ModelIgnored => &self.model_ignored,
+            SkipReason::BelowThreshold => &self.below_threshold,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_failed(&self) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_language(&self, language: &str) {
+        let languages = self
+            .languages
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(count) = languages.get(language) {
+            count.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        drop(languages);
+
+        self.languages
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(language.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        for counter in [
+            &self.translated,
+            &self.detected_english,
+            &self.model_ignored,
+            &self.below_threshold,
+            &self.failed,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.languages
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clear();
+    }
+
+    pub fn snapshot(&self) -> TranslationStatsSnapshot {
+        TranslationStatsSnapshot {
+            translated: self.translated.load(Ordering::Relaxed),
+            detected_english: self.detected_english.load(Ordering::Relaxed),
+            model_ignored: self.model_ignored.load(Ordering::Relaxed),
+            below_threshold: self.below_threshold.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            languages: self
+                .languages
+                .read()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .iter()
+                .map(|(language, count)| (language.clone(), count.load(Ordering::Relaxed)))
+                .collect(),
+        }
+    }
+}
+
+/// Detected-language counts for the current session in a channel.
+#[derive(Default)]
+pub struct ChannelLanguageStats {
+    counts: HashMap<String, u64>,
+}
+
+impl ChannelLanguageStats {
+    pub fn record(&mut self, language: &str) {
+        *self.counts.entry(language.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.clear();
+    }
+
+    pub fn dominant(&self) -> Option<Language> {
+        self.counts
+            .iter()
+            .max_by_key(|(_, count)| **count)
+            .and_then(|(language, _)| language.parse().ok())
+    }
+
+    /// Languages sorted from most to least common.
+    pub fn distribution(&self) -> Vec<LanguageShare> {
+        let total: u64 = self.counts.values().sum();
+        let mut shares: Vec<LanguageShare> = self
+            .counts
+            .iter()
+            .map(|(language, count)| LanguageShare {
+                language: language.clone(),
+                count: *count,
+                share: *count as f64 / total as f64,
+            })
+            .collect();
+        shares.sort_by(|a, b| b.count.cmp(&a.count));
+        shares
+    }
+}
+
+impl Default for LanguageHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LanguageHistory {
+    pub fn new() -> Self {
+        Self {
+            users: LruCache::new(NonZeroUsize::new(TRACKED_CHATTERS).unwrap()),
+        }
+    }
+
+    pub fn record(&mut self, user: &twitch_api::types::UserId, language: Language, limit: usize) {
+        let history = self.users.get_or_insert_mut(user.clone(), VecDeque::new);
+        history.push_back(language);
+        while history.len() > limit {
+            history.pop_front();
+        }
+    }
+
+    /// The language this chatter used most often recently.
+    pub fn preferred(&mut self, user: &twitch_api::types::UserId) -> Option<Language> {
+        let mut counts: HashMap<Language, usize> = HashMap::new();
+        for language in self.users.get(user)? {
+            *counts.entry(*language).or_insert(0) += 1;
+        }
+
+        counts
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(language, _)| language)
+    }
+}
+
+/// Builds the chat reply, fitting it into Twitch's message limit. When the
+/// original is included it's shortened first, since the translation is what
+/// the rest of chat needs, but never below `MIN_ORIGINAL_CHARS`.
+fn format_reply(label: &str, name: &str, original: Option<&str>, translation: &str) -> String {
+    let prefix = if label.is_empty() {
+        format!("{}: ", name)
+    } else {
+        format!("{} {}: ", label, name)
+    };
+
+    let Some(original) = original else {
+        return truncate_chars(
+            &format!("{}{}", prefix, translation),
+            MAX_CHAT_MESSAGE_CHARS,
+        );
+    };
+
+    let separator = " → ";
+    let budget = MAX_CHAT_MESSAGE_CHARS
+        .saturating_sub(
+            prefix.chars().count() + separator.chars().count() + translation.chars().count(),
+        )
+        .max(MIN_ORIGINAL_CHARS);
+
+    truncate_chars(
+        &format!(
+            "{}{}{}{}",
+            prefix,
+            truncate_chars(original, budget),
+            separator,
+            translation
+        ),
+        MAX_CHAT_MESSAGE_CHARS,
+    )
+}
+
+/// Checks that a reply template only uses known placeholders, closes every
+/// brace and includes `{translation}`.
+pub fn validate_reply_template(template: &str) -> Result<(), String> {
+    let mut rest = template;
+    let mut has_translation = false;
+
+    while let Some(start) = rest.find(['{', '}']) {
+        if rest[start..].starts_with('}') {
+            return Err("Unmatched '}' in reply template".to_string());
+        }
+        let end = start
+            + rest[start..]
+                .find('}')
+                .ok_or("Unclosed '{' in reply template")?;
+
+        let name = &rest[start + 1..end];
+        if !REPLY_PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "Unknown placeholder {{{}}} in reply template",
+                name
+            ));
+        }
+        has_translation |= name == "translation";
+        rest = &rest[end + 1..];
+    }
+
+    if !has_translation {
+        return Err("Reply template must include {translation}".to_string());
+    }
+    Ok(())
+}
+
+/// Fills in a template that passed `validate_reply_template`, fitting the
+/// result into Twitch's message limit. Like `format_reply`, the original is
+/// shortened first, down to `MIN_ORIGINAL_CHARS` shared between its uses.
+fn render_reply(
+    template: &str,
+    user: &str,
+    user_original: &str,
+    lang: &str,
+    original: &str,
+    translation: &str,
+) -> String {
+    let user_romanized = romanize::romanize_name(user_original);
+    let fill = |original: &str| {
+        let mut reply = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            reply.push_str(&rest[..start]);
+            // One pass, so text from chat is never read as a placeholder
+            reply.push_str(match &rest[start + 1..start + len] {
+                "user" => user,
+                "user_original" => user_original,
+                "user_romanized" => &user_romanized,
+                "translation" => translation,
+                "lang" => lang,
+                "original" => original,
+                _ => &rest[start..=start + len],
+            });
+            rest = &rest[start + len + 1..];
+        }
+        reply.push_str(rest);
+        reply
+    };
+
+    let uses = template.matches("{original}").count();
+    let budget = if uses == 0 {
+        0
+    } else {
+        (MAX_CHAT_MESSAGE_CHARS.saturating_sub(fill("").chars().count()) / uses)
+            .max(MIN_ORIGINAL_CHARS / uses)
+    };
+
+    truncate_chars(
+        &fill(truncate_chars(original, budget).as_str()),
+        MAX_CHAT_MESSAGE_CHARS,
+    )
+}
+
+/// Converts an RFC 3339 timestamp from Twitch into local time using a
+/// strftime-style `pattern`, falling back to the original on a bad pattern.
+fn format_local_time(timestamp: &str, pattern: &str) -> String {
+    use std::fmt::Write as _;
+
+    let Ok(time) = chrono::DateTime::parse_from_rfc3339(timestamp) else {
+        return timestamp.to_string();
+    };
+
+    // Formatting reports invalid patterns as a fmt error rather than up front
+    let mut formatted = String::new();
+    match write!(
+        formatted,
+        "{}",
+        time.with_timezone(&chrono::Local).format(pattern)
+    ) {
+        Ok(()) => formatted,
+        Err(_) => timestamp.to_string(),
+    }
+}
+
+// Cuts `text` to at most `max` characters, marking the cut with an ellipsis.
+fn truncate_chars(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.chars().take(max.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Whether `text` is a chat command like "!lurk", possibly aimed at someone
+/// ("@streamer !so @friend").
+fn is_command(text: &str) -> bool {
+    text.split_whitespace()
+        .find(|word| !word.starts_with('@'))
+        .is_some_and(|word| word.starts_with('!'))
 }
 
 impl Bot {
+    /// Runs the bot, restarting it with exponential backoff when it crashes,
+    /// up to `reconnect_max_attempts` times in a row. An expired login stops
+    /// it straight away, since only the user can fix that.
+    pub async fn run(&self) -> Result<(), eyre::Report> {
+        loop {
+            let error = match self.start().await {
+                Ok(()) => return Ok(()),
+                Err(error) => error,
+            };
+
+            let max_attempts = settings::current(&self.app_handle).reconnect_max_attempts;
+            let attempt = self.reconnect_attempts.load(Ordering::SeqCst) + 1;
+            if error.downcast_ref::<AuthExpired>().is_some() || attempt > max_attempts {
+                return Err(error);
+            }
+            self.reconnect_attempts.store(attempt, Ordering::SeqCst);
+
+            let delay = RECONNECT_BASE_DELAY
+                .saturating_mul(1 << (attempt - 1).min(16))
+                .min(RECONNECT_MAX_DELAY);
+            tracing::warn!(
+                "Bot for {} crashed ({}), reconnecting in {:?} (attempt {}/{})",
+                self.broadcaster_login,
+                error,
+                delay,
+                attempt,
+                max_attempts
+            );
+            let _ = self.app_handle.emit(
+                "bot-reconnecting",
+                BotReconnectingPayload {
+                    channel: self.broadcaster_login.clone(),
+                    attempt,
+                    delay_ms: delay.as_millis() as u64,
+                    error: error.to_string(),
+                },
+            );
+
+            tokio::time::sleep(delay).await;
+        }
+    }
+
     pub async fn start(&self) -> Result<(), eyre::Report> {
         // To make a connection to the chat we need to use a websocket connection.
         // This is a wrapper for the websocket connection that handles the reconnects and handles all messages from eventsub.
@@ -38,6 +751,21 @@ impl Bot {
             client: self.client.clone(),
             connect_url: twitch_api::TWITCH_EVENTSUB_WEBSOCKET_URL.clone(),
             chats: vec![self.broadcaster.clone()],
+            on_connected: {
+                let app_handle = self.app_handle.clone();
+                let channel = self.broadcaster_login.clone();
+                let reconnect_attempts = self.reconnect_attempts.clone();
+                Box::new(move || {
+                    if reconnect_attempts.swap(0, Ordering::SeqCst) > 0 {
+                        let _ = app_handle.emit(
+                            "bot-reconnected",
+                            BotReconnectedPayload {
+                                channel: channel.clone(),
+                            },
+                        );
+                    }
+                })
+            },
         };
         let refresh_token = async move {
             let token = self.token.clone();
@@ -49,15 +777,17 @@ impl Bot {
                 interval.tick().await;
                 let mut token = token.lock().await;
                 if token.expires_in() < std::time::Duration::from_secs(60) {
-                    token
-                        .refresh_token(&self.client)
+                    refresh_login(&self.app_handle, &self.client, &mut token)
                         .await
-                        .wrap_err("couldn't refresh token")?;
+                        .map_err(|e| eyre::eyre!(e))
+                        .wrap_err("couldn't refresh token")
+                        .wrap_err(AuthExpired)?;
                 }
                 token
                     .validate_token(&client)
                     .await
-                    .wrap_err("couldn't validate token")?;
+                    .wrap_err("couldn't validate token")
+                    .wrap_err(AuthExpired)?;
             }
             #[allow(unreachable_code)]
             Ok(())
@@ -78,73 +808,481 @@ impl Bot {
                 subscription,
                 ..
             }) => {
-                let log = ChatLogPayload {
+                let settings = settings::current(&self.app_handle);
+
+                let text = if self.third_party_emotes.is_empty() {
+                    payload.message.text.to_string()
+                } else {
+                    emotes::strip_emotes(&payload.message.text, &self.third_party_emotes)
+                };
+
+                // A streamer logged in as the bot still gets their own messages
+                // translated outbound. Our translations are always threaded
+                // replies, so those are never mistaken for the streamer's.
+                let outbound = settings.translate_broadcaster_messages
+                    && payload.chatter_user_id == payload.broadcaster_user_id
+                    && payload.reply.is_none();
+
+                // Never translate our own replies, other chat bots' command
+                // output and timers, ignored chatters, chatters missing from
+                // the allowlist, !commands, or messages that were nothing but
+                // emotes
+                let skip_translation = (payload.chatter_user_id == subscription.condition.user_id
+                    && !outbound)
+                    || settings.ignores_chatter(payload.chatter_user_login.as_str())
+                    || (!outbound
+                        && settings.translate_only_users.as_ref().is_some_and(|users| {
+                            !users.iter().any(|login| {
+                                login.eq_ignore_ascii_case(payload.chatter_user_login.as_str())
+                            })
+                        }))
+                    || (settings.ignore_command_prefix && is_command(&text))
+                    || text.is_empty();
+
+                let mut log = ChatLogPayload {
+                    message_id: payload.message_id.to_string(),
                     user: payload.chatter_user_name.to_string(),
                     message: payload.message.text.to_string(),
                     timestamp: timestamp.to_string(),
+                    local_time: format_local_time(timestamp.as_str(), &settings.timestamp_format),
+                    sentiment: None,
+                    toxicity: settings
+                        .score_toxicity
+                        .then(|| toxicity::score_toxicity(&payload.message.text)),
                 };
-                let _ = self.app_handle.emit("chat-event", &log);
+                if log
+                    .toxicity
+                    .is_some_and(|score| score >= settings.toxicity_threshold)
+                {
+                    let _ = self.app_handle.emit("toxic-message", &log);
+                }
+                // With sentiment enabled the log entry waits for the translation
+                // task, which is where the sentiment gets computed
+                if !settings.classify_sentiment || skip_translation {
+                    let _ = self.app_handle.emit("chat-event", &log);
+                }
                 println!(
                     "[{}] {}: {}",
                     timestamp, payload.chatter_user_name, payload.message.text
                 );
 
+                if skip_translation {
+                    return Ok(());
+                }
+
                 // Clone data for the background thread
                 let app_handle = self.app_handle.clone();
                 let client = self.client.clone();
                 let token_arc = self.token.clone();
+                let language_history = self.language_history.clone();
+                let can_reply = self.can_reply;
+                let reply_history = self.reply_history.clone();
+                let debouncer = self.debouncer.clone();
+                let reply_limiter = self.reply_limiter.clone();
+                let force_language = self.force_language.lock().ok().and_then(|forced| *forced);
+                let mut turn = self
+                    .reply_order
+                    .lock()
+                    .map_err(|_| eyre::eyre!("reply order lock poisoned"))?
+                    .take_turn();
+
+                // The broadcaster and mods are served first and never shed
+                let priority = if payload
+                    .badges
+                    .iter()
+                    .any(|badge| matches!(badge.set_id.as_str(), "broadcaster" | "moderator"))
+                {
+                    model::Priority::High
+                } else {
+                    model::Priority::Normal
+                };
 
-                let text = payload.message.text.to_string();
                 let chatter_name = payload.chatter_user_name.clone();
+                let chatter_id = payload.chatter_user_id.clone();
                 let message_id = payload.message_id.clone();
                 let broadcaster_id = subscription.condition.broadcaster_user_id.clone();
                 let bot_user_id = subscription.condition.user_id.clone();
+                let channel = self.broadcaster_login.clone();
+                let stats = self.stats.clone();
 
                 tauri::async_runtime::spawn(async move {
+                    // Hold the message briefly; if the same chatter keeps
+                    // typing, the last message translates all of them at once
+                    let text = if settings.debounce_window_ms > 0 {
+                        let Ok(seq) = debouncer.lock().map(|mut d| d.push(&chatter_id, text))
+                        else {
+                            return;
+                        };
+                        tokio::time::sleep(std::time::Duration::from_millis(
+                            settings.debounce_window_ms,
+                        ))
+                        .await;
+                        let batch = debouncer
+                            .lock()
+                            .ok()
+                            .and_then(|mut d| d.take_if_latest(&chatter_id, seq));
+                        match batch {
+                            Some(batch) => batch,
+                            None => {
+                                if settings.classify_sentiment {
+                                    let _ = app_handle.emit("chat-event", &log);
+                                }
+                                return;
+                            }
+                        }
+                    } else {
+                        text
+                    };
+
+                    let user_hint = if settings.learn_user_languages {
+                        language_history
+                            .lock()
+                            .ok()
+                            .and_then(|mut history| history.preferred(&chatter_id))
+                    } else {
+                        None
+                    };
+
+                    let dominant_language = app_handle
+                        .state::<JoinedChannelState>()
+                        .language_stats
+                        .lock()
+                        .ok()
+                        .and_then(|stats| stats.dominant());
+
+                    let channel_hint = if settings.use_channel_language_prior {
+                        dominant_language
+                    } else {
+                        None
+                    };
+
+                    let language_hint = user_hint.or(channel_hint);
+
+                    let target = settings
+                        .broadcaster_target_language
+                        .as_deref()
+                        .and_then(|language| language.parse::<Language>().ok())
+                        .or(dominant_language);
+                    let direction = match target {
+                        Some(target) if outbound && target != Language::English => {
+                            model::Direction::FromEnglish(target)
+                        }
+                        _ => model::Direction::ToEnglish,
+                    };
+                    let outbound = direction != model::Direction::ToEnglish;
+
                     let result = model::perform_translation(
                         text.clone(),
                         &app_handle.state::<TranslationModelState>(),
+                        &settings,
+                        language_hint,
+                        force_language,
+                        direction,
+                        priority,
                     )
                     .await;
 
+                    if settings.classify_sentiment {
+                        log.sentiment = result.as_ref().ok().and_then(|r| r.sentiment);
+                        let _ = app_handle.emit("chat-event", &log);
+                    }
+
+                    let mut event = TranslationEventPayload {
+                        message_id: message_id.to_string(),
+                        user: chatter_name.to_string(),
+                        original: text.clone(),
+                        translation: None,
+                        language: None,
+                        status: TranslationOutcome::Failed,
+                        reply: None,
+                    };
+
+                    let skipped = |language: Option<String>, reason| {
+                        stats.record_skipped(reason);
+                        TranslationSkippedPayload {
+                            channel: channel.clone(),
+                            message_id: message_id.to_string(),
+                            language,
+                            reason,
+                        }
+                    };
+
+                    match &result {
+                        Ok(result) => {
+                            event.language = Some(result.language.clone());
+                            stats.record_language(&result.language);
+                        }
+                        Err(e) => {
+                            tracing::warn!("Translation failed: {}", e);
+                            let _ = app_handle.emit("translation-event", &event);
+                            if e == model::LOW_CONFIDENCE_ERROR {
+                                let _ = app_handle.emit(
+                                    "translation-skipped",
+                                    skipped(None, SkipReason::BelowThreshold),
+                                );
+                            } else {
+                                stats.record_failed();
+                            }
+                        }
+                    }
+
                     if let Ok(result) = result {
-                        if result.language == "English" {
-                            tracing::info!("English");
-                        } else if result.translation == text {
+                        // The streamer's outbound messages would skew the
+                        // chat's language stats towards English, as would
+                        // slang and cache hits reported without detection
+                        if !outbound && result.detected {
+                            if let Ok(mut stats) = app_handle
+                                .state::<JoinedChannelState>()
+                                .language_stats
+                                .lock()
+                            {
+                                stats.record(&result.language);
+                            }
+                        }
+
+                        if settings.learn_user_languages && !outbound && result.detected {
+                            if let (Ok(language), Ok(mut history)) =
+                                (result.language.parse::<Language>(), language_history.lock())
+                            {
+                                history.record(
+                                    &chatter_id,
+                                    language,
+                                    settings.user_language_history_size,
+                                );
+                            }
+                        }
+
+                        // Explained English jargon is posted like a translation
+                        if result.already_target && result.translation == text && !outbound {
+                            event.status = TranslationOutcome::AlreadyTarget;
+                            let _ = app_handle.emit("translation-event", &event);
+                            let _ = app_handle.emit(
+                                "translation-skipped",
+                                skipped(Some(result.language.clone()), SkipReason::DetectedEnglish),
+                            );
+                        } else if result.translation == text || result.translation.is_empty() {
+                            // Empty when the model declined ('<@>') or never
+                            // got past its reasoning
                             tracing::info!(
                                 "Ignored from {}: {}",
                                 result.language,
                                 result.translation
                             );
+                            event.status = TranslationOutcome::Ignored;
+                            let _ = app_handle.emit("translation-event", &event);
+                            let reason = if model::is_too_short(&text, &settings) {
+                                SkipReason::BelowThreshold
+                            } else {
+                                SkipReason::ModelIgnored
+                            };
+                            let _ = app_handle.emit(
+                                "translation-skipped",
+                                skipped(Some(result.language.clone()), reason),
+                            );
                         } else {
                             tracing::info!(
                                 "Translated from {}: {}",
                                 result.language,
                                 result.translation
                             );
+                            let reply_name = if settings.romanize_usernames {
+                                let romanized = romanize::romanize_name(chatter_name.as_str());
+                                if romanized == chatter_name.as_str() {
+                                    romanized
+                                } else {
+                                    format!("{} ({})", romanized, chatter_name)
+                                }
+                            } else {
+                                chatter_name.to_string()
+                            };
+
+                            // A template broken by editing the store by hand
+                            // falls back to the default layout
+                            let template = settings
+                                .reply_template
+                                .as_deref()
+                                .filter(|template| validate_reply_template(template).is_ok());
+                            let reply_text = match template {
+                                Some(template) => render_reply(
+                                    template,
+                                    &reply_name,
+                                    chatter_name.as_str(),
+                                    &result.language,
+                                    &text,
+                                    &result.translation,
+                                ),
+                                None => format_reply(
+                                    &settings.reply_label,
+                                    &reply_name,
+                                    settings.include_original.then_some(text.as_str()),
+                                    &result.translation,
+                                ),
+                            };
+
+                            event.status = TranslationOutcome::Translated;
+                            stats.record_translated();
+                            event.translation = Some(result.translation.clone());
+                            if settings.translation_mode == TranslationMode::Preview {
+                                event.reply = Some(reply_text.clone());
+                            }
+                            let _ = app_handle.emit("translation-event", &event);
+
+                            if settings.translation_mode != TranslationMode::Reply {
+                                return;
+                            }
+
+                            // Without the user:write:chat scope we can only show
+                            // translations in the app
+                            if !can_reply {
+                                tracing::info!("Read-only login, not replying: {}", reply_text);
+                                return;
+                            }
 
                             // Send Reply
+                            turn.wait().await;
+
+                            // Stay under Twitch's chat rate limit, or the bot
+                            // gets its messages dropped
+                            loop {
+                                let throttled = match reply_limiter.lock() {
+                                    Ok(mut limiter) => limiter.try_acquire(
+                                        settings.max_replies_per_window,
+                                        std::time::Duration::from_secs(settings.reply_window_secs),
+                                    ),
+                                    Err(_) => Ok(()),
+                                };
+                                match throttled {
+                                    Ok(()) => break,
+                                    Err(_) if settings.drop_throttled_replies => {
+                                        tracing::warn!(
+                                            "Reply rate limit reached, dropping: {}",
+                                            reply_text
+                                        );
+                                        let _ = app_handle.emit(
+                                            "reply-dropped",
+                                            &ReplyDroppedPayload {
+                                                message_id: message_id.to_string(),
+                                                reply: reply_text,
+                                            },
+                                        );
+                                        return;
+                                    }
+                                    Err(wait) => tokio::time::sleep(wait).await,
+                                }
+                            }
+
                             let token_guard = token_arc.lock().await;
 
-                            let reply_text =
-                                format!("(translation) {}: {}", chatter_name, result.translation);
-
-                            if let Err(e) = client
-                                .send_chat_message_reply(
-                                    &broadcaster_id,
-                                    &bot_user_id,
-                                    &message_id,
-                                    reply_text.as_str(), // ✅ FIX: Use .as_str() here
-                                    &*token_guard,
-                                )
-                                .await
-                            {
-                                tracing::error!("Failed to send Twitch reply: {}", e);
+                            let sent = if settings.reply_as_thread {
+                                client
+                                    .send_chat_message_reply(
+                                        &broadcaster_id,
+                                        &bot_user_id,
+                                        &message_id,
+                                        reply_text.as_str(), // ✅ FIX: Use .as_str() here
+                                        &*token_guard,
+                                    )
+                                    .await
+                            } else {
+                                client
+                                    .send_chat_message(
+                                        &broadcaster_id,
+                                        &bot_user_id,
+                                        reply_text.as_str(),
+                                        &*token_guard,
+                                    )
+                                    .await
+                            };
+
+                            match sent {
+                                Ok(response) => {
+                                    if let Ok(mut history) = reply_history.lock() {
+                                        history.record(message_id, response.message_id);
+                                    }
+                                }
+                                Err(e) => tracing::error!("Failed to send Twitch reply: {}", e),
                             }
                         }
                     }
                 });
             }
+            Event::ChannelChatMessageDeleteV1(Payload {
+                message: Message::Notification(payload),
+                subscription,
+                ..
+            }) => {
+                let reply_id = self
+                    .reply_history
+                    .lock()
+                    .ok()
+                    .and_then(|mut history| history.take(&payload.message_id));
+
+                let _ = self.app_handle.emit(
+                    "translation-retracted",
+                    &TranslationRetractedPayload {
+                        message_id: payload.message_id.to_string(),
+                        reply_message_id: reply_id.as_ref().map(|id| id.to_string()),
+                    },
+                );
+
+                let reply_id = match reply_id {
+                    Some(reply_id)
+                        if settings::current(&self.app_handle).delete_retracted_replies =>
+                    {
+                        reply_id
+                    }
+                    _ => return Ok(()),
+                };
+
+                // Deleting needs the bot to be a moderator with this scope
+                let token = self.token.lock().await;
+                if !token.scopes().contains(&Scope::ModeratorManageChatMessages) {
+                    tracing::warn!(
+                        "Can't delete retracted reply without the moderator:manage:chat_messages scope"
+                    );
+                    return Ok(());
+                }
+
+                if let Err(e) = self
+                    .client
+                    .delete_chat_message(
+                        &payload.broadcaster_user_id,
+                        &subscription.condition.user_id,
+                        &reply_id,
+                        &*token,
+                    )
+                    .await
+                {
+                    tracing::error!("Failed to delete retracted reply: {}", e);
+                }
+            }
+            Event::ChannelChatClearV1(Payload {
+                message: Message::Notification(payload),
+                ..
+            }) => {
+                tracing::info!("Chat cleared in {}", payload.broadcaster_user_login);
+                let _ = self.app_handle.emit(
+                    "chat-cleared",
+                    &ChatClearedPayload {
+                        broadcaster: payload.broadcaster_user_login.to_string(),
+                    },
+                );
+            }
+            Event::ChannelChatClearUserMessagesV1(Payload {
+                message: Message::Notification(payload),
+                ..
+            }) => {
+                tracing::info!("Messages from {} cleared", payload.target_user_login);
+                let _ = self.app_handle.emit(
+                    "user-messages-cleared",
+                    &UserMessagesClearedPayload {
+                        broadcaster: payload.broadcaster_user_login.to_string(),
+                        user: payload.target_user_name.to_string(),
+                        user_login: payload.target_user_login.to_string(),
+                        user_id: payload.target_user_id.to_string(),
+                    },
+                );
+            }
             Event::ChannelChatNotificationV1(Payload {
                 message: Message::Notification(payload),
                 ..
@@ -167,3 +1305,186 @@ impl Bot {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn replies_go_out_in_the_order_messages_arrived() {
+        let mut order = ReplyOrder::default();
+        let turns: Vec<_> = (0..3).map(|_| order.take_turn()).collect();
+        let (sent_tx, mut sent) = tokio::sync::mpsc::unbounded_channel();
+
+        // Later messages finish translating first
+        let replies: Vec<_> = turns
+            .into_iter()
+            .enumerate()
+            .rev()
+            .map(|(i, mut turn)| {
+                let sent_tx = sent_tx.clone();
+                tokio::spawn(async move {
+                    turn.wait().await;
+                    sent_tx.send(i).unwrap();
+                })
+            })
+            .collect();
+        for reply in replies {
+            reply.await.unwrap();
+        }
+        drop(sent_tx);
+
+        let mut order = Vec::new();
+        while let Some(i) = sent.recv().await {
+            order.push(i);
+        }
+        assert_eq!(order, [0, 1, 2]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_stuck_translation_holds_up_the_next_reply_for_a_while() {
+        let mut order = ReplyOrder::default();
+        let _stuck = order.take_turn();
+        let mut next = order.take_turn();
+
+        let started = tokio::time::Instant::now();
+        next.wait().await;
+
+        assert!(started.elapsed() >= MAX_REPLY_WAIT);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_failed_translation_lets_the_next_reply_go_at_once() {
+        let mut order = ReplyOrder::default();
+        let failed = order.take_turn();
+        let mut next = order.take_turn();
+        drop(failed);
+
+        let started = tokio::time::Instant::now();
+        next.wait().await;
+
+        assert!(started.elapsed().is_zero());
+    }
+
+    #[test]
+    fn a_full_bucket_allows_a_burst_then_refills() {
+        let mut limiter = ReplyLimiter::default();
+        let start = limiter.refilled_at;
+        let window = std::time::Duration::from_secs(30);
+
+        for _ in 0..20 {
+            assert_eq!(limiter.try_acquire_at(start, 20, window), Ok(()));
+        }
+        // One slot frees up every 1.5 seconds
+        assert_eq!(
+            limiter.try_acquire_at(start, 20, window),
+            Err(std::time::Duration::from_millis(1500))
+        );
+
+        let later = start + std::time::Duration::from_secs(3);
+        assert_eq!(limiter.try_acquire_at(later, 20, window), Ok(()));
+        assert_eq!(limiter.try_acquire_at(later, 20, window), Ok(()));
+        assert!(limiter.try_acquire_at(later, 20, window).is_err());
+    }
+
+    #[test]
+    fn an_idle_bucket_refills_no_further_than_its_capacity() {
+        let mut limiter = ReplyLimiter::default();
+        let start = limiter.refilled_at;
+        let window = std::time::Duration::from_secs(30);
+        limiter.try_acquire_at(start, 2, window).unwrap();
+
+        let much_later = start + std::time::Duration::from_secs(3600);
+        assert!(limiter.try_acquire_at(much_later, 2, window).is_ok());
+        assert!(limiter.try_acquire_at(much_later, 2, window).is_ok());
+        assert!(limiter.try_acquire_at(much_later, 2, window).is_err());
+    }
+
+    #[test]
+    fn a_zero_window_never_limits() {
+        let mut limiter = ReplyLimiter::default();
+        let start = limiter.refilled_at;
+
+        for _ in 0..100 {
+            assert_eq!(
+                limiter.try_acquire_at(start, 20, std::time::Duration::ZERO),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn quick_messages_from_one_chatter_are_merged() {
+        let mut debouncer = MessageDebouncer::default();
+        let chatter = twitch_api::types::UserId::from("1");
+
+        let first = debouncer.push(&chatter, "yo".to_string());
+        let second = debouncer.push(&chatter, "lol".to_string());
+        let last = debouncer.push(&chatter, "gg".to_string());
+
+        // Only the newest message's timer flushes, with everything queued
+        assert_eq!(debouncer.take_if_latest(&chatter, first), None);
+        assert_eq!(debouncer.take_if_latest(&chatter, second), None);
+        assert_eq!(
+            debouncer.take_if_latest(&chatter, last).as_deref(),
+            Some("yo lol gg")
+        );
+        assert_eq!(debouncer.take_if_latest(&chatter, last), None);
+    }
+
+    #[test]
+    fn other_chatters_are_not_merged_in() {
+        let mut debouncer = MessageDebouncer::default();
+        let alice = twitch_api::types::UserId::from("1");
+        let bob = twitch_api::types::UserId::from("2");
+
+        let from_alice = debouncer.push(&alice, "bonjour".to_string());
+        let from_bob = debouncer.push(&bob, "hola".to_string());
+
+        assert_eq!(
+            debouncer.take_if_latest(&alice, from_alice).as_deref(),
+            Some("bonjour")
+        );
+        assert_eq!(
+            debouncer.take_if_latest(&bob, from_bob).as_deref(),
+            Some("hola")
+        );
+    }
+
+    #[test]
+    fn reply_template_can_name_the_user_as_written_or_romanized() {
+        let template = "{user_romanized} ({user_original}): {translation}";
+        validate_reply_template(template).unwrap();
+
+        let reply = render_reply(template, "小明", "小明", "Chinese", "你好", "hello");
+
+        assert_eq!(reply, "XiaoMing (小明): hello");
+    }
+
+    #[test]
+    fn a_long_translation_still_leaves_room_for_the_original() {
+        let translation = "word ".repeat(100);
+        let original = "bonjour tout le monde";
+
+        let reply = format_reply("[FR]", "viewer", Some(original), &translation);
+        assert!(reply.starts_with("[FR] viewer: bonjour tout le monde → word"));
+        assert!(reply.chars().count() <= MAX_CHAT_MESSAGE_CHARS);
+
+        let reply = render_reply(
+            "{original} → {translation}",
+            "viewer",
+            "viewer",
+            "French",
+            original,
+            &translation,
+        );
+        assert!(reply.starts_with("bonjour tout le monde → word"));
+        assert!(reply.chars().count() <= MAX_CHAT_MESSAGE_CHARS);
+    }
+
+    #[test]
+    fn unknown_reply_placeholders_are_rejected() {
+        assert!(validate_reply_template("{username}: {translation}").is_err());
+        assert!(validate_reply_template("{user}").is_err());
+    }
+}
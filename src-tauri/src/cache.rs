@@ -0,0 +1,195 @@
+use std::num::NonZeroUsize;
+
+use lingua::Language;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+
+use crate::TranslationResponse;
+
+/// How a chat message is turned into a cache key. The key is only used for
+/// lookups; the model always receives the original text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheKeyMode {
+    /// Only identical messages share an entry.
+    Exact,
+    /// Lowercased, whitespace-collapsed and stripped of trailing
+    /// punctuation/emoji, so "yyds!!!" and "yyds" share an entry.
+    Normalized,
+}
+
+pub fn cache_key(text: &str, mode: CacheKeyMode) -> String {
+    match mode {
+        CacheKeyMode::Exact => text.to_string(),
+        CacheKeyMode::Normalized => {
+            let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            let trimmed = collapsed.trim_end_matches(|c: char| !c.is_alphanumeric());
+
+            // A message made only of punctuation/emoji would otherwise
+            // collapse to "" and collide with every other one.
+            if trimmed.is_empty() {
+                collapsed.to_lowercase()
+            } else {
+                trimmed.to_lowercase()
+            }
+        }
+    }
+}
+
+/// Everything that shapes a chat translation, so messages only share an
+/// entry when they would be translated the same way.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    /// The message after slang normalization and placeholders, as
+    /// `cache_key` reduces it.
+    pub text: String,
+    /// The language it was translated from.
+    pub language: Language,
+    pub target: Language,
+    pub system_prompt: Option<String>,
+    pub preserve_profanity: bool,
+}
+
+pub struct TranslationCache {
+    entries: LruCache<CacheKey, TranslationResponse>,
+}
+
+impl TranslationCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: LruCache::new(NonZeroUsize::new(capacity.max(1)).unwrap()),
+        }
+    }
+
+    pub fn get(&mut self, key: &CacheKey) -> Option<TranslationResponse> {
+        self.entries.get(key).cloned()
+    }
+
+    pub fn insert(&mut self, key: CacheKey, response: TranslationResponse) {
+        self.entries.put(key, response);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Changes how many entries are kept, evicting the oldest if it shrinks.
+    pub fn resize(&mut self, capacity: usize) {
+        self.entries
+            .resize(NonZeroUsize::new(capacity.max(1)).unwrap());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(text: &str, target: Language) -> CacheKey {
+        keyed(text, CacheKeyMode::Normalized, target)
+    }
+
+    fn keyed(text: &str, mode: CacheKeyMode, target: Language) -> CacheKey {
+        CacheKey {
+            text: cache_key(text, mode),
+            language: Language::Chinese,
+            target,
+            system_prompt: None,
+            preserve_profanity: false,
+        }
+    }
+
+    fn response(translation: &str) -> TranslationResponse {
+        TranslationResponse {
+            language: "Chinese".into(),
+            target_language: "English".into(),
+            translation: translation.into(),
+            sentiment: None,
+            toxicity: None,
+            normalized_only: false,
+            truncated: false,
+            confidence: None,
+            detected: true,
+            already_target: false,
+            normalized_input: None,
+            stop_reason: None,
+            debug_timings: None,
+        }
+    }
+
+    #[test]
+    fn normalized_keys_share_an_entry_between_variants() {
+        let mut cache = TranslationCache::new(8);
+        cache.insert(key("yyds!!!", Language::English), response("GOAT"));
+
+        let hit = cache.get(&key("  YYDS ", Language::English)).unwrap();
+
+        assert_eq!(hit.translation, "GOAT");
+        assert!(cache.get(&key("yyds 666", Language::English)).is_none());
+    }
+
+    #[test]
+    fn exact_keys_only_match_identical_messages() {
+        let mut cache = TranslationCache::new(8);
+        cache.insert(
+            keyed("yyds", CacheKeyMode::Exact, Language::English),
+            response("GOAT"),
+        );
+
+        assert!(cache
+            .get(&keyed("yyds", CacheKeyMode::Exact, Language::English))
+            .is_some());
+        assert!(cache
+            .get(&keyed("yyds!", CacheKeyMode::Exact, Language::English))
+            .is_none());
+    }
+
+    #[test]
+    fn punctuation_only_messages_keep_their_own_entries() {
+        assert_eq!(cache_key("???", CacheKeyMode::Normalized), "???");
+        assert_ne!(
+            cache_key("???", CacheKeyMode::Normalized),
+            cache_key("!!!", CacheKeyMode::Normalized)
+        );
+    }
+
+    #[test]
+    fn the_least_recently_used_entry_is_evicted() {
+        let mut cache = TranslationCache::new(2);
+        cache.insert(key("一", Language::English), response("one"));
+        cache.insert(key("二", Language::English), response("two"));
+        cache.get(&key("一", Language::English));
+        cache.insert(key("三", Language::English), response("three"));
+
+        assert!(cache.get(&key("一", Language::English)).is_some());
+        assert!(cache.get(&key("二", Language::English)).is_none());
+
+        cache.resize(1);
+        assert!(cache.get(&key("三", Language::English)).is_none());
+    }
+
+    #[test]
+    fn entries_are_kept_apart_by_how_they_were_translated() {
+        let mut cache = TranslationCache::new(8);
+        cache.insert(key("你好", Language::English), response("hello"));
+
+        assert!(cache.get(&key("你好", Language::French)).is_none());
+        assert!(cache
+            .get(&CacheKey {
+                language: Language::Japanese,
+                ..key("你好", Language::English)
+            })
+            .is_none());
+        assert!(cache
+            .get(&CacheKey {
+                system_prompt: Some("Translate into {lang}.".into()),
+                ..key("你好", Language::English)
+            })
+            .is_none());
+        assert!(cache
+            .get(&CacheKey {
+                preserve_profanity: true,
+                ..key("你好", Language::English)
+            })
+            .is_none());
+    }
+}
@@ -0,0 +1,75 @@
+use tauri_plugin_store::StoreExt;
+
+use crate::STORE_PATH;
+
+// Entries in the OS credential store are grouped under this service name
+const KEYRING_SERVICE: &str = "star-system-bot";
+
+fn entry(key: &str) -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, key)
+}
+
+/// Reads the secret saved under `key`, from the OS credential store or,
+/// where that isn't available, from the JSON store. A secret left in the
+/// JSON store by an older version is moved to the credential store.
+pub fn load(app: &tauri::AppHandle, key: &str) -> Result<Option<String>, String> {
+    let keyring_entry = entry(key);
+    if let Ok(entry) = &keyring_entry {
+        match entry.get_password() {
+            Ok(secret) => return Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => {}
+            Err(err) => tracing::warn!("Failed to read {} from the keyring: {}", key, err),
+        }
+    }
+
+    let store = app.store(STORE_PATH).map_err(|err| err.to_string())?;
+    let Some(serde_json::Value::String(secret)) = store.get(key) else {
+        return Ok(None);
+    };
+
+    if let Ok(entry) = keyring_entry {
+        if entry.set_password(&secret).is_ok() {
+            store.delete(key);
+            store.save().map_err(|err| err.to_string())?;
+        }
+    }
+
+    Ok(Some(secret))
+}
+
+/// Saves `secret` under `key` in the OS credential store, falling back to
+/// the JSON store when there is none (e.g. Linux without a Secret Service).
+pub fn save(app: &tauri::AppHandle, key: &str, secret: &str) -> Result<(), String> {
+    let store = app.store(STORE_PATH).map_err(|err| err.to_string())?;
+
+    match entry(key).and_then(|entry| entry.set_password(secret)) {
+        Ok(()) => {
+            store.delete(key);
+        }
+        Err(err) => {
+            tracing::warn!(
+                "Keyring unavailable ({}), storing {} in {} instead",
+                err,
+                key,
+                STORE_PATH
+            );
+            store.set(key, secret);
+        }
+    }
+
+    store.save().map_err(|err| err.to_string())
+}
+
+/// Removes the secret under `key` from both places it could be.
+pub fn delete(app: &tauri::AppHandle, key: &str) -> Result<(), String> {
+    if let Ok(entry) = entry(key) {
+        match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(err) => tracing::warn!("Failed to remove {} from the keyring: {}", key, err),
+        }
+    }
+
+    let store = app.store(STORE_PATH).map_err(|err| err.to_string())?;
+    store.delete(key);
+    store.save().map_err(|err| err.to_string())
+}
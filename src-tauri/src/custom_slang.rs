@@ -0,0 +1,194 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+use crate::model;
+use crate::slang;
+use crate::slang_es;
+use crate::slang_fr;
+use crate::slang_jp;
+use crate::slang_ko;
+use crate::slang_vi;
+use crate::slang_zh;
+
+const CUSTOM_SLANG_FILE: &str = "custom_slang.json";
+
+/// Which dictionary a custom entry extends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlangLanguage {
+    Zh,
+    Fr,
+    Jp,
+    Ko,
+    Es,
+    Vi,
+    /// Applied to every language, after its own entries.
+    Universal,
+}
+
+/// A user-defined slang term, e.g. a channel emote or community in-joke.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlangEntry {
+    pub lang: SlangLanguage,
+    pub from: String,
+    pub to: String,
+}
+
+// The entries as last loaded or saved, so edits don't re-read the file
+static ENTRIES: Lazy<Mutex<Vec<SlangEntry>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+// Universal entries for languages without a dictionary of their own
+static UNIVERSAL_FLATTENER: Lazy<RwLock<Option<(AhoCorasick, Vec<String>)>>> =
+    Lazy::new(|| RwLock::new(None));
+
+/// Loads `<app data>/custom_slang.json` into the slang dictionaries. A
+/// missing file just means there are no custom entries.
+pub fn load(app: &tauri::AppHandle) -> Result<(), String> {
+    let path = custom_slang_path(app)?;
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let contents = fs::read_to_string(&path).map_err(|err| err.to_string())?;
+    let entries: Vec<SlangEntry> = serde_json::from_str(&contents)
+        .map_err(|err| format!("{} is malformed: {}", CUSTOM_SLANG_FILE, err))?;
+
+    apply(&entries)?;
+    *ENTRIES.lock().map_err(|_| "Poisoned lock")? = entries;
+
+    Ok(())
+}
+
+pub fn entries() -> Result<Vec<SlangEntry>, String> {
+    Ok(ENTRIES.lock().map_err(|_| "Poisoned lock")?.clone())
+}
+
+/// Adds `entry`, replacing any entry for the same term in the same
+/// dictionary, and saves the file.
+pub fn add_entry(app: &tauri::AppHandle, entry: SlangEntry) -> Result<(), String> {
+    if entry.from.trim().is_empty() {
+        return Err("Slang term can't be empty".into());
+    }
+
+    let mut entries = ENTRIES.lock().map_err(|_| "Poisoned lock")?;
+    let mut updated = entries.clone();
+    updated.retain(|existing| !(existing.lang == entry.lang && existing.from == entry.from));
+    updated.push(entry);
+
+    save(app, &updated)?;
+    apply(&updated)?;
+    *entries = updated;
+
+    Ok(())
+}
+
+/// Removes the entry for `from` in `lang`. Returns whether there was one.
+pub fn remove_entry(
+    app: &tauri::AppHandle,
+    lang: SlangLanguage,
+    from: &str,
+) -> Result<bool, String> {
+    let mut entries = ENTRIES.lock().map_err(|_| "Poisoned lock")?;
+    let mut updated = entries.clone();
+    updated.retain(|existing| !(existing.lang == lang && existing.from == from));
+    if updated.len() == entries.len() {
+        return Ok(false);
+    }
+
+    save(app, &updated)?;
+    apply(&updated)?;
+    *entries = updated;
+
+    Ok(true)
+}
+
+/// Replaces universal custom slang in text whose language has no built-in
+/// dictionary. Like French slang, only whole words are replaced.
+pub fn normalize_universal_slang(text: &str) -> String {
+    let flattener = UNIVERSAL_FLATTENER
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    match &*flattener {
+        Some((ac, replacements)) => slang::replace_whole_words(ac, replacements, text),
+        None => text.to_string(),
+    }
+}
+
+// Rebuilds every automaton. A language's own entries come before the
+// universal ones so they win when both define the same term.
+fn apply(entries: &[SlangEntry]) -> Result<(), String> {
+    let pairs = |lang: SlangLanguage| -> Vec<(String, String)> {
+        entries
+            .iter()
+            .filter(|entry| entry.lang == lang)
+            .chain(
+                entries
+                    .iter()
+                    .filter(|entry| entry.lang == SlangLanguage::Universal),
+            )
+            .map(|entry| (entry.from.clone(), entry.to.clone()))
+            .collect()
+    };
+
+    slang_zh::set_custom_slang(&pairs(SlangLanguage::Zh));
+    slang_jp::set_custom_slang(&pairs(SlangLanguage::Jp));
+    slang_fr::set_custom_slang(&pairs(SlangLanguage::Fr));
+    slang_ko::set_custom_slang(&pairs(SlangLanguage::Ko));
+    slang_es::set_custom_slang(&pairs(SlangLanguage::Es));
+    slang_vi::set_custom_slang(&pairs(SlangLanguage::Vi));
+
+    let (patterns, replacements): (Vec<String>, Vec<String>) = entries
+        .iter()
+        .filter(|entry| entry.lang == SlangLanguage::Universal)
+        .map(|entry| (entry.from.clone(), entry.to.clone()))
+        .unzip();
+    let universal = if patterns.is_empty() {
+        None
+    } else {
+        let ac = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .ascii_case_insensitive(true)
+            .build(&patterns)
+            .map_err(|err| err.to_string())?;
+        Some((ac, replacements))
+    };
+    *UNIVERSAL_FLATTENER
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = universal;
+
+    // Universal terms also pass through untranslated when they're all a
+    // message says
+    model::set_custom_universal_slang(
+        entries
+            .iter()
+            .filter(|entry| entry.lang == SlangLanguage::Universal)
+            .map(|entry| entry.from.clone()),
+    );
+
+    Ok(())
+}
+
+fn save(app: &tauri::AppHandle, entries: &[SlangEntry]) -> Result<(), String> {
+    let path = custom_slang_path(app)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+
+    let contents = serde_json::to_string_pretty(entries).map_err(|err| err.to_string())?;
+    fs::write(&path, contents).map_err(|err| err.to_string())
+}
+
+fn custom_slang_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(app
+        .path()
+        .app_data_dir()
+        .map_err(|err| err.to_string())?
+        .join(CUSTOM_SLANG_FILE))
+}
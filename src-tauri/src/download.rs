@@ -0,0 +1,208 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+// Downloaded models live under the app data dir, next to nothing else.
+const MODEL_DIR: &str = "model";
+// Progress events are throttled to one per this many bytes.
+const PROGRESS_STEP: u64 = 1 << 20;
+
+/// A model the app knows how to fetch.
+pub struct KnownModel {
+    pub name: &'static str,
+    pub filename: &'static str,
+    pub url: &'static str,
+}
+
+pub const KNOWN_MODELS: &[KnownModel] = &[KnownModel {
+    name: "qwen3-1.7b-q8",
+    filename: "Qwen3-1.7B-Q8_0.gguf",
+    url: "https://huggingface.co/Qwen/Qwen3-1.7B-GGUF/resolve/main/Qwen3-1.7B-Q8_0.gguf?download=true",
+}];
+
+/// The model used when nothing else is configured.
+pub const DEFAULT_MODEL: &KnownModel = &KNOWN_MODELS[0];
+
+/// Cancellation flag of the download in progress, if any.
+#[derive(Default)]
+pub struct DownloadState {
+    cancel: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct DownloadProgressPayload {
+    pub name: String,
+    pub downloaded: u64,
+    pub total: Option<u64>,
+}
+
+pub fn downloaded_model_path(app: &tauri::AppHandle, filename: &str) -> Result<PathBuf, String> {
+    Ok(app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join(MODEL_DIR)
+        .join(filename))
+}
+
+/// Fetches a known model into the app data dir, emitting
+/// `model-download-progress` as it goes. Returns the path of the model,
+/// skipping the download if it's already there.
+pub async fn download_model(app: &tauri::AppHandle, name: &str) -> Result<PathBuf, String> {
+    let model = KNOWN_MODELS
+        .iter()
+        .find(|model| model.name == name)
+        .ok_or_else(|| format!("Unknown model '{}'", name))?;
+
+    let dest = downloaded_model_path(app, model.filename)?;
+    if dest.is_file() {
+        return Ok(dest);
+    }
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let state = app.state::<DownloadState>();
+    let cancel = Arc::new(AtomicBool::new(false));
+    {
+        let mut current = state.cancel.lock().map_err(|_| "Poisoned lock")?;
+        if current.is_some() {
+            return Err("A model download is already running".into());
+        }
+        *current = Some(cancel.clone());
+    }
+
+    let result = fetch(app, model, &dest, &cancel).await;
+
+    *state.cancel.lock().map_err(|_| "Poisoned lock")? = None;
+
+    result.map(|()| dest)
+}
+
+/// Stops the running download, if any. Returns whether there was one.
+pub fn cancel_download(app: &tauri::AppHandle) -> Result<bool, String> {
+    let state = app.state::<DownloadState>();
+    let current = state.cancel.lock().map_err(|_| "Poisoned lock")?;
+
+    Ok(match current.as_ref() {
+        Some(cancel) => {
+            cancel.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    })
+}
+
+// A download in progress. Whatever stops it early, an error, a cancel or the
+// download itself being dropped, the half-written file goes with it.
+struct PartialFile {
+    path: PathBuf,
+    complete: bool,
+}
+
+impl PartialFile {
+    fn new(dest: &Path) -> Self {
+        Self {
+            path: dest.with_extension("gguf.part"),
+            complete: false,
+        }
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if !self.complete {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+// Streams into a `.part` file and only renames it into place once complete,
+// so an interrupted download is never mistaken for a model.
+async fn fetch(
+    app: &tauri::AppHandle,
+    model: &KnownModel,
+    dest: &PathBuf,
+    cancel: &AtomicBool,
+) -> Result<(), String> {
+    let mut partial = PartialFile::new(dest);
+
+    let mut response = reqwest::get(model.url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to download {}: {}", model.name, e))?;
+    let total = response.content_length();
+
+    let mut file = std::fs::File::create(&partial.path).map_err(|e| e.to_string())?;
+    let mut downloaded = 0;
+    let mut reported = 0;
+
+    tracing::info!("Downloading {} to {:?}", model.name, dest);
+
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        if cancel.load(Ordering::SeqCst) {
+            return Err("Download cancelled".into());
+        }
+
+        file.write_all(&chunk).map_err(|e| e.to_string())?;
+        downloaded += chunk.len() as u64;
+
+        if downloaded - reported >= PROGRESS_STEP || Some(downloaded) == total {
+            reported = downloaded;
+            let _ = app.emit(
+                "model-download-progress",
+                &DownloadProgressPayload {
+                    name: model.name.to_string(),
+                    downloaded,
+                    total,
+                },
+            );
+        }
+    }
+
+    file.flush().map_err(|e| e.to_string())?;
+    drop(file);
+    std::fs::rename(&partial.path, dest).map_err(|e| e.to_string())?;
+    partial.complete = true;
+
+    tracing::info!("Downloaded {}", model.name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dest(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{}-{}.gguf", name, std::process::id()))
+    }
+
+    #[test]
+    fn an_unfinished_download_leaves_no_part_file() {
+        let partial = PartialFile::new(&dest("unfinished"));
+        std::fs::write(&partial.path, b"half a model").unwrap();
+        let path = partial.path.clone();
+
+        drop(partial);
+
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn a_finished_download_is_left_alone() {
+        let dest = dest("finished");
+        let mut partial = PartialFile::new(&dest);
+        std::fs::write(&partial.path, b"a model").unwrap();
+        std::fs::rename(&partial.path, &dest).unwrap();
+        partial.complete = true;
+
+        drop(partial);
+
+        assert!(dest.exists());
+        std::fs::remove_file(dest).unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::HashSet;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+const BTTV_GLOBAL_URL: &str = "https://api.betterttv.net/3/cached/emotes/global";
+const BTTV_CHANNEL_URL: &str = "https://api.betterttv.net/3/cached/users/twitch";
+const FFZ_CHANNEL_URL: &str = "https://api.betterttv.net/3/cached/frankerfacez/users/twitch";
+const SEVENTV_CHANNEL_URL: &str = "https://7tv.io/v3/users/twitch";
+
+/// Names of third-party emotes (BTTV, FFZ, 7TV) usable in a channel. These
+/// show up as plain words in the message text rather than emote fragments.
+pub type EmoteSet = HashSet<String>;
+
+#[derive(Deserialize)]
+struct BttvEmote {
+    code: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BttvChannel {
+    channel_emotes: Vec<BttvEmote>,
+    shared_emotes: Vec<BttvEmote>,
+}
+
+#[derive(Deserialize)]
+struct SevenTvUser {
+    emote_set: Option<SevenTvEmoteSet>,
+}
+
+#[derive(Deserialize)]
+struct SevenTvEmoteSet {
+    emotes: Vec<SevenTvEmote>,
+}
+
+#[derive(Deserialize)]
+struct SevenTvEmote {
+    name: String,
+}
+
+/// Fetches every third-party emote available in the channel. Providers that
+/// fail (or where the channel has no account) are logged and skipped.
+pub async fn fetch_channel_emotes(broadcaster_id: &str) -> EmoteSet {
+    let client = reqwest::Client::new();
+    let mut emotes = EmoteSet::new();
+
+    match fetch_json::<Vec<BttvEmote>>(&client, BTTV_GLOBAL_URL).await {
+        Ok(global) => emotes.extend(global.into_iter().map(|e| e.code)),
+        Err(e) => tracing::warn!("Failed to fetch BTTV global emotes: {}", e),
+    }
+
+    let url = format!("{}/{}", BTTV_CHANNEL_URL, broadcaster_id);
+    match fetch_json::<BttvChannel>(&client, &url).await {
+        Ok(channel) => emotes.extend(
+            channel
+                .channel_emotes
+                .into_iter()
+                .chain(channel.shared_emotes)
+                .map(|e| e.code),
+        ),
+        Err(e) => tracing::warn!("Failed to fetch BTTV channel emotes: {}", e),
+    }
+
+    let url = format!("{}/{}", FFZ_CHANNEL_URL, broadcaster_id);
+    match fetch_json::<Vec<BttvEmote>>(&client, &url).await {
+        Ok(channel) => emotes.extend(channel.into_iter().map(|e| e.code)),
+        Err(e) => tracing::warn!("Failed to fetch FFZ channel emotes: {}", e),
+    }
+
+    let url = format!("{}/{}", SEVENTV_CHANNEL_URL, broadcaster_id);
+    match fetch_json::<SevenTvUser>(&client, &url).await {
+        Ok(user) => emotes.extend(
+            user.emote_set
+                .into_iter()
+                .flat_map(|set| set.emotes)
+                .map(|e| e.name),
+        ),
+        Err(e) => tracing::warn!("Failed to fetch 7TV channel emotes: {}", e),
+    }
+
+    tracing::info!("Loaded {} third-party emotes", emotes.len());
+
+    emotes
+}
+
+/// Drops emote words from `text` so they don't get translated into gibberish.
+pub fn strip_emotes(text: &str, emotes: &EmoteSet) -> String {
+    if !text.split_whitespace().any(|word| emotes.contains(word)) {
+        return text.to_string();
+    }
+
+    text.split_whitespace()
+        .filter(|word| !emotes.contains(*word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+async fn fetch_json<T: DeserializeOwned>(client: &reqwest::Client, url: &str) -> Result<T, String> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let body = response.text().await.map_err(|e| e.to_string())?;
+
+    serde_json::from_str(&body).map_err(|e| e.to_string())
+}
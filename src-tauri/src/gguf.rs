@@ -0,0 +1,201 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::Path;
+
+// Reads just enough of a GGUF header to size up a model before llama.cpp
+// loads it. See https://github.com/ggml-org/ggml/blob/master/docs/gguf.md
+
+const MAGIC: &[u8; 4] = b"GGUF";
+// Metadata keys are short; anything longer means the file isn't GGUF
+const MAX_KEY_BYTES: u64 = 64 * 1024;
+
+/// What the header of a GGUF file says about the model.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GgufInfo {
+    pub tensor_count: u64,
+    /// Transformer blocks (`<arch>.block_count`), if the file names them.
+    pub block_count: Option<u32>,
+}
+
+/// Reads the header of the GGUF file at `path`. Fails if it isn't GGUF or
+/// ends before the header does.
+pub fn read_info(path: &Path) -> io::Result<GgufInfo> {
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a GGUF file"));
+    }
+    // Version 1 used 32-bit counts and is long gone
+    let version = read_u32(&mut reader)?;
+    if version < 2 {
+        return Err(invalid("unsupported GGUF version"));
+    }
+
+    let tensor_count = read_u64(&mut reader)?;
+    let kv_count = read_u64(&mut reader)?;
+
+    let mut block_count = None;
+    for _ in 0..kv_count {
+        let key = read_string(&mut reader)?;
+        let value_type = read_u32(&mut reader)?;
+        if key.ends_with(".block_count") {
+            block_count = Some(match value_type {
+                4 | 5 => read_u32(&mut reader)?,
+                10 | 11 => u32::try_from(read_u64(&mut reader)?)
+                    .map_err(|_| invalid("block count out of range"))?,
+                _ => return Err(invalid("block count isn't an integer")),
+            });
+        } else {
+            skip_value(&mut reader, value_type, file_len)?;
+        }
+    }
+
+    Ok(GgufInfo {
+        tensor_count,
+        block_count,
+    })
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let len = read_u64(reader)?;
+    if len > MAX_KEY_BYTES {
+        return Err(invalid("metadata key too long"));
+    }
+    let mut bytes = vec![0; len as usize];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| invalid("metadata key isn't UTF-8"))
+}
+
+// Skips over a metadata value. The tokenizer's vocabulary is an array of
+// thousands of strings, so nothing is read into memory.
+fn skip_value(reader: &mut BufReader<File>, value_type: u32, file_len: u64) -> io::Result<()> {
+    let size = match value_type {
+        // u8, i8, bool
+        0 | 1 | 7 => 1,
+        // u16, i16
+        2 | 3 => 2,
+        // u32, i32, f32
+        4..=6 => 4,
+        // u64, i64, f64
+        10..=12 => 8,
+        // string
+        8 => read_u64(reader)?,
+        // array
+        9 => {
+            let element_type = read_u32(reader)?;
+            let len = read_u64(reader)?;
+            for _ in 0..len {
+                skip_value(reader, element_type, file_len)?;
+            }
+            return Ok(());
+        }
+        _ => return Err(invalid("unknown metadata type")),
+    };
+
+    // Seeking past the end of a file succeeds, so a cut-off header is
+    // caught here
+    let end = reader.stream_position()?.saturating_add(size);
+    if end > file_len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    reader.seek_relative(size as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(bytes: &mut Vec<u8>, text: &str) {
+        bytes.extend((text.len() as u64).to_le_bytes());
+        bytes.extend(text.as_bytes());
+    }
+
+    // A header like llama.cpp writes: an architecture, a vocabulary and the
+    // block count last
+    fn header(block_count: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(MAGIC);
+        bytes.extend(3u32.to_le_bytes());
+        bytes.extend(310u64.to_le_bytes());
+        bytes.extend(3u64.to_le_bytes());
+
+        string(&mut bytes, "general.architecture");
+        bytes.extend(8u32.to_le_bytes());
+        string(&mut bytes, "qwen3");
+
+        string(&mut bytes, "tokenizer.ggml.tokens");
+        bytes.extend(9u32.to_le_bytes());
+        bytes.extend(8u32.to_le_bytes());
+        bytes.extend(2u64.to_le_bytes());
+        string(&mut bytes, "hello");
+        string(&mut bytes, "world");
+
+        string(&mut bytes, "qwen3.block_count");
+        bytes.extend(4u32.to_le_bytes());
+        bytes.extend(block_count.to_le_bytes());
+        bytes
+    }
+
+    fn write(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}.gguf", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn the_block_count_is_read_from_the_header() {
+        let path = write("header", &header(28));
+
+        let info = read_info(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            info.unwrap(),
+            GgufInfo {
+                tensor_count: 310,
+                block_count: Some(28),
+            }
+        );
+    }
+
+    #[test]
+    fn a_cut_off_header_is_an_error() {
+        let bytes = header(28);
+        let path = write("cut-off", &bytes[..bytes.len() - 30]);
+
+        let info = read_info(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(info.is_err());
+    }
+
+    #[test]
+    fn other_files_are_not_gguf() {
+        let path = write("html", b"<!DOCTYPE html><html>Rate limited</html>");
+
+        let info = read_info(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(info.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::fs;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use tauri::Manager;
+
+use crate::protect::ProtectedText;
+
+const GLOSSARY_DIR: &str = "glossary";
+
+/// A curated per-game vocabulary, mapping source terms to the English
+/// rendering the community prefers (e.g. "大招" -> "ult").
+pub struct Glossary {
+    pub name: String,
+    automaton: AhoCorasick,
+    renderings: Vec<String>,
+}
+
+impl Glossary {
+    /// Swaps glossary terms in `text` for placeholders that restore to their
+    /// preferred renderings.
+    pub fn protect(&self, text: &mut ProtectedText) {
+        text.protect_matches(&self.automaton, &self.renderings);
+    }
+}
+
+/// Loads `<app data>/glossary/<name>.json`, a flat JSON object of
+/// `"source term": "English rendering"` pairs.
+pub fn load_glossary(app: &tauri::AppHandle, name: &str) -> Result<Glossary, String> {
+    // The name becomes part of a path, so keep it to a plain file stem
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!("Invalid glossary name '{}'", name));
+    }
+
+    let path = app
+        .path()
+        .app_data_dir()
+        .map_err(|err| err.to_string())?
+        .join(GLOSSARY_DIR)
+        .join(format!("{}.json", name));
+
+    if !path.exists() {
+        return Err(format!(
+            "Unknown glossary '{}' (expected it at {})",
+            name,
+            path.display()
+        ));
+    }
+
+    let contents = fs::read_to_string(&path).map_err(|err| err.to_string())?;
+    let terms: HashMap<String, String> = serde_json::from_str(&contents)
+        .map_err(|err| format!("Glossary '{}' is malformed: {}", name, err))?;
+
+    let (patterns, renderings): (Vec<String>, Vec<String>) = terms.into_iter().unzip();
+
+    // LeftmostLongest so "ult" doesn't shadow "ultimate"
+    let automaton = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostLongest)
+        .ascii_case_insensitive(true)
+        .build(&patterns)
+        .map_err(|err| err.to_string())?;
+
+    Ok(Glossary {
+        name: name.to_string(),
+        automaton,
+        renderings,
+    })
+}
@@ -0,0 +1,92 @@
+use std::future::Future;
+use std::time::Duration;
+
+use twitch_api::helix::{ClientRequestError, HelixRequestGetError};
+use twitch_oauth2::tokens::errors::ValidationError;
+
+// A call is tried this many times in total, waiting RETRY_BASE_DELAY before
+// the second attempt and doubling the wait after that
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+const LOGIN_EXPIRED: &str = "Your Twitch login has expired or was revoked. Please log in again.";
+
+/// Why a call to Twitch failed, and whether trying again could help.
+pub enum HelixError {
+    /// The request never got an answer, or Twitch had a server error.
+    Transient(String),
+    /// Twitch's answer won't change, e.g. a rejected token.
+    Fatal(String),
+}
+
+/// Runs `call` until it succeeds, fails with a fatal error, or has failed
+/// transiently `MAX_ATTEMPTS` times. `what` describes the call for messages,
+/// e.g. "look up the channel".
+pub async fn with_retries<T, F, Fut>(what: &str, mut call: F) -> Result<T, String>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, HelixError>>,
+{
+    let mut attempt = 1;
+    loop {
+        match call().await {
+            Ok(value) => return Ok(value),
+            Err(HelixError::Fatal(message)) => return Err(message),
+            Err(HelixError::Transient(message)) if attempt >= MAX_ATTEMPTS => {
+                return Err(format!(
+                    "Couldn't reach Twitch to {} after {} attempts: {}",
+                    what, attempt, message
+                ));
+            }
+            Err(HelixError::Transient(message)) => {
+                let delay = RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+                tracing::warn!("Failed to {} ({}), retrying in {:?}", what, message, delay);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Sorts a failed token validation. Only a request that never got an
+/// answer is worth repeating; a rejected token stays rejected.
+pub fn validation_error<RE>(error: ValidationError<RE>) -> HelixError
+where
+    RE: std::error::Error + Send + Sync + 'static,
+{
+    match error {
+        ValidationError::Request(err) => HelixError::Transient(err.to_string()),
+        ValidationError::NotAuthorized | ValidationError::InvalidToken(_) => {
+            HelixError::Fatal(LOGIN_EXPIRED.to_string())
+        }
+        other => HelixError::Fatal(other.to_string()),
+    }
+}
+
+/// Sorts a failed Helix request. Network errors, rate limits and 5xx
+/// responses are transient; any other status is Twitch's final answer.
+pub fn request_error<RE>(error: ClientRequestError<RE>) -> HelixError
+where
+    RE: std::error::Error + Send + Sync + 'static,
+{
+    match error {
+        ClientRequestError::RequestError(err) => HelixError::Transient(err.to_string()),
+        ClientRequestError::HelixRequestGetError(HelixRequestGetError::Error {
+            status,
+            message,
+            ..
+        }) => {
+            if status.is_server_error() || status.as_u16() == 429 {
+                HelixError::Transient(format!("{} {}", status, message))
+            } else if status.as_u16() == 401 {
+                HelixError::Fatal(LOGIN_EXPIRED.to_string())
+            } else {
+                HelixError::Fatal(format!(
+                    "Twitch rejected the request: {} {}",
+                    status, message
+                ))
+            }
+        }
+        other => HelixError::Fatal(other.to_string()),
+    }
+}
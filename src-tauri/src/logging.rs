@@ -0,0 +1,75 @@
+use tauri::Manager;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::prelude::*;
+
+use crate::settings::Settings;
+
+/// Target of the per-translation spans that make up the translation log.
+pub const TRANSLATION_TARGET: &str = "translation";
+
+// Files are named translations.log.YYYY-MM-DD
+const TRANSLATION_LOG_PREFIX: &str = "translations.log";
+
+/// Keeps the translation log's writer thread alive. Dropping it flushes
+/// whatever is still buffered.
+pub struct TranslationLogGuard {
+    _guard: Option<WorkerGuard>,
+}
+
+/// Logs to stderr and, with `translation_log` on, writes every translation
+/// as a JSON line to a daily file in the app log dir, keeping
+/// `translation_log_retention_days` of them. Settings only apply at startup.
+pub fn init(app: &tauri::AppHandle, settings: &Settings) -> TranslationLogGuard {
+    let stderr = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_filter(LevelFilter::INFO);
+
+    let (file_layer, guard, error) = if settings.translation_log {
+        match translation_log_appender(app, settings) {
+            Ok(appender) => {
+                let (writer, guard) = tracing_appender::non_blocking(appender);
+                // A translation's fields are filled in as it runs, so its
+                // line is written when the span closes
+                let layer = tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_writer(writer)
+                    .with_span_events(FmtSpan::CLOSE)
+                    .with_current_span(true)
+                    .with_span_list(false)
+                    .with_filter(Targets::new().with_target(TRANSLATION_TARGET, LevelFilter::INFO));
+                (Some(layer), Some(guard), None)
+            }
+            Err(err) => (None, None, Some(err)),
+        }
+    } else {
+        (None, None, None)
+    };
+
+    tracing_subscriber::registry()
+        .with(stderr)
+        .with(file_layer)
+        .init();
+
+    if let Some(err) = error {
+        tracing::warn!("Failed to open the translation log: {}", err);
+    }
+
+    TranslationLogGuard { _guard: guard }
+}
+
+fn translation_log_appender(
+    app: &tauri::AppHandle,
+    settings: &Settings,
+) -> Result<RollingFileAppender, String> {
+    let dir = app.path().app_log_dir().map_err(|err| err.to_string())?;
+
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(TRANSLATION_LOG_PREFIX)
+        .max_log_files(settings.translation_log_retention_days.max(1))
+        .build(dir)
+        .map_err(|err| err.to_string())
+}
@@ -0,0 +1,126 @@
+use lingua::Language;
+
+// The rust-bert port of facebook/m2m100_418M, bundled as
+// model/m2m100/{rust_model.ot, config.json, vocab.json, sentencepiece.bpe.model}.
+// build.rs fetches them when the m2m100 feature is enabled.
+#[cfg(feature = "m2m100")]
+const M2M100_DIR: &str = "model/m2m100";
+
+// ---------------------------------------------------------------------------
+// Real engine (Active only when --features m2m100 is used)
+// ---------------------------------------------------------------------------
+#[cfg(feature = "m2m100")]
+pub struct M2m100Engine {
+    model: std::sync::Mutex<rust_bert::pipelines::translation::TranslationModel>,
+}
+
+#[cfg(feature = "m2m100")]
+impl M2m100Engine {
+    pub fn load(app_handle: &tauri::AppHandle) -> anyhow::Result<Self> {
+        use anyhow::Context;
+        use rust_bert::m2m_100::{M2M100SourceLanguages, M2M100TargetLanguages};
+        use rust_bert::pipelines::common::{ModelResource, ModelType};
+        use rust_bert::pipelines::translation::{TranslationConfig, TranslationModel};
+        use rust_bert::resources::LocalResource;
+        use tauri::path::BaseDirectory;
+        use tauri::Manager;
+
+        let dir = app_handle
+            .path()
+            .resolve(M2M100_DIR, BaseDirectory::Resource)
+            .context("Failed to resolve path to M2M100")?;
+
+        for file in [
+            "rust_model.ot",
+            "config.json",
+            "vocab.json",
+            "sentencepiece.bpe.model",
+        ] {
+            if !dir.join(file).is_file() {
+                anyhow::bail!("M2M100 file {:?} is missing", dir.join(file));
+            }
+        }
+
+        let resource = |file: &str| LocalResource {
+            local_path: dir.join(file),
+        };
+
+        let config = TranslationConfig::new(
+            ModelType::M2M100,
+            ModelResource::Torch(Box::new(resource("rust_model.ot"))),
+            resource("config.json"),
+            resource("vocab.json"),
+            Some(resource("sentencepiece.bpe.model")),
+            M2M100SourceLanguages::M2M100_418M,
+            M2M100TargetLanguages::M2M100_418M,
+            tch::Device::cuda_if_available(),
+        );
+
+        let model = TranslationModel::new(config).context("Failed to load M2M100")?;
+
+        Ok(Self {
+            model: std::sync::Mutex::new(model),
+        })
+    }
+
+    /// Translates `text`, or fails if either language isn't one M2M100 knows.
+    pub fn translate(
+        &self,
+        text: &str,
+        source: Language,
+        target: Language,
+    ) -> anyhow::Result<String> {
+        let (Some(source), Some(target)) = (to_m2m100(source), to_m2m100(target)) else {
+            anyhow::bail!("M2M100 can't translate {} to {}", source, target);
+        };
+
+        let model = self
+            .model
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Poisoned lock"))?;
+        let output = model.translate(&[text], source, target)?;
+
+        Ok(output.into_iter().next().unwrap_or_default())
+    }
+}
+
+#[cfg(feature = "m2m100")]
+fn to_m2m100(language: Language) -> Option<rust_bert::pipelines::translation::Language> {
+    use rust_bert::pipelines::translation::Language as M2m100Language;
+
+    Some(match language {
+        Language::English => M2m100Language::English,
+        Language::French => M2m100Language::French,
+        Language::Japanese => M2m100Language::Japanese,
+        Language::Chinese => M2m100Language::ChineseMandarin,
+        Language::Korean => M2m100Language::Korean,
+        Language::Spanish => M2m100Language::Spanish,
+        Language::German => M2m100Language::German,
+        Language::Portuguese => M2m100Language::Portuguese,
+        Language::Russian => M2m100Language::Russian,
+        Language::Vietnamese => M2m100Language::Vietnamese,
+        _ => return None,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Stub (Active by default): the app builds without libtorch
+// ---------------------------------------------------------------------------
+#[cfg(not(feature = "m2m100"))]
+pub struct M2m100Engine;
+
+#[cfg(not(feature = "m2m100"))]
+impl M2m100Engine {
+    pub fn load(_app_handle: &tauri::AppHandle) -> anyhow::Result<Self> {
+        anyhow::bail!("Built without M2M100 support (enable the m2m100 feature)")
+    }
+
+    pub fn translate(
+        &self,
+        _text: &str,
+        _source: Language,
+        _target: Language,
+    ) -> anyhow::Result<String> {
+        anyhow::bail!("Built without M2M100 support")
+    }
+}
@@ -1,58 +1,217 @@
-use lingua::LanguageDetector;
+use lingua::{Language, LanguageDetector};
 use llama_cpp_2::{llama_backend::LlamaBackend, model::LlamaModel};
 use reqwest::header::InvalidHeaderValue;
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
-use tauri::Manager;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tauri::{Emitter, Manager};
 use tauri_plugin_store::StoreExt;
 use tokio::sync::Semaphore;
 use twitch_api::client::ClientDefault;
 use twitch_api::{client::ReqwestClientDefaultError, HelixClient};
-use twitch_oauth2::{AccessToken, DeviceUserTokenBuilder, Scope, TwitchToken as _, UserToken};
+use twitch_oauth2::{
+    AccessToken, ClientId, ClientSecret, DeviceUserTokenBuilder, RefreshToken, Scope,
+    TwitchToken as _, UserToken,
+};
 
 mod bot;
+mod cache;
+mod credentials;
+mod custom_slang;
+mod download;
+mod emotes;
+mod gguf;
+mod glossary;
+mod helix;
+mod logging;
+mod m2m100;
 mod model;
+mod protect;
+mod quality;
+mod remote;
+mod romanize;
+mod settings;
+mod slang;
+mod slang_en;
+mod slang_es;
 mod slang_fr;
 mod slang_jp;
+mod slang_ko;
+mod slang_vi;
 mod slang_zh;
+mod toxicity;
 mod websocket;
 
 const STORE_PATH: &str = "configs.json";
 const CLIENT_ID_KEY: &str = "client_id";
 const CLIENT_SECRET_KEY: &str = "client_secret";
-const CONTEXT_THREADS: usize = 20;
+const REFRESH_TOKEN_KEY: &str = "refresh_token";
+const APP_SECRET_KEY: &str = "app_client_secret";
+const JOINED_CHANNELS_KEY: &str = "joined_channels";
+const BACKEND_UNAVAILABLE: &str = "llama.cpp failed to initialize; restart the app to load a model";
 
 #[allow(unused)]
 struct RefiningModelState {
+    // Declared first so the contexts drop before the model they borrow from
+    // when a model is swapped out
+    context_pool: Mutex<Vec<model::ThreadSafeContext>>,
+    // Decodes batched translations together, with how many messages it
+    // holds. Built the first time a batch runs.
+    batch_context: Mutex<Option<(model::ThreadSafeContext, usize)>>,
+    // One permit per context in use, so a permit always finds a free context
+    semaphore: Arc<Semaphore>,
     backend: Arc<LlamaBackend>,
     model: Arc<LlamaModel>,
-    context_pool: Mutex<Vec<model::ThreadSafeContext>>,
+    model_info: model::ModelInfo,
+}
+
+impl RefiningModelState {
+    fn new(
+        backend: Arc<LlamaBackend>,
+        model: Arc<LlamaModel>,
+        model_info: model::ModelInfo,
+        pool_size: usize,
+        concurrency_limit: usize,
+    ) -> anyhow::Result<Self> {
+        let mut contexts = Vec::new();
+        for _ in 0..pool_size {
+            contexts.push(model::initialize_llama_context(&backend, &model)?);
+        }
+
+        // The first decode on a context is slow, so it's done here rather
+        // than on the first chat message. A failure only costs that speedup.
+        let started = std::time::Instant::now();
+        for ctx in &mut contexts {
+            if let Err(err) = model::warm_up(&model, ctx) {
+                tracing::warn!("Failed to warm up a llama context: {:#}", err);
+            }
+        }
+        tracing::info!(
+            "Warmed up {} contexts in {:?}",
+            contexts.len(),
+            started.elapsed()
+        );
+
+        Ok(Self {
+            context_pool: Mutex::new(contexts),
+            batch_context: Mutex::new(None),
+            semaphore: Arc::new(Semaphore::new(concurrency_limit.min(pool_size))),
+            backend,
+            model,
+            model_info,
+        })
+    }
 }
 
 struct TranslationModelState {
-    detector: LanguageDetector,
-    llm_state: Arc<RefiningModelState>,
-    semaphore: Arc<Semaphore>,
+    // Rebuilt when the enabled languages change
+    detector: RwLock<LanguageDetector>,
+    // `None` if llama.cpp failed to initialize, in which case no model can
+    // be loaded until the app restarts
+    backend: Option<Arc<LlamaBackend>>,
+    // `None` until a model has been found or downloaded
+    llm_state: Mutex<Option<Arc<RefiningModelState>>>,
+    // Why the last attempt to load a model failed, for the frontend
+    load_error: Mutex<Option<String>>,
+    // Number of llama contexts, and so the most translations that can run
+    // at once
+    pool_size: AtomicUsize,
+    concurrency_limit: Mutex<usize>,
+    // Translations waiting for a context, and ones holding one
+    queued: AtomicUsize,
+    in_flight: AtomicUsize,
+    // Cancellation handles for translations waiting under `ShedOldest`
+    shed_queue: model::ShedQueue,
+    // High-priority translations waiting, and a wakeup for the normal ones
+    // that let them go first
+    high_priority_waiting: AtomicUsize,
+    high_priority_served: tokio::sync::Notify,
+    // When the queue started backing up, and whether that tipped us into
+    // fast mode
+    overloaded_since: Mutex<Option<std::time::Instant>>,
+    fast_mode: AtomicBool,
+    decode_failures: AtomicUsize,
+    app_handle: tauri::AppHandle,
+    // Loaded the first time the M2M100 engine is used; `None` if unavailable
+    m2m100: tokio::sync::OnceCell<Option<Arc<m2m100::M2m100Engine>>>,
+    // M2M100 translates one message at a time
+    m2m100_semaphore: Arc<Semaphore>,
+    cache: Mutex<cache::TranslationCache>,
+    glossary: Mutex<Option<glossary::Glossary>>,
+    // Translations collected for the next batch, and a wakeup for when
+    // there are enough of them to stop waiting
+    batch_queue: Mutex<model::BatchQueue>,
+    batch_full: tokio::sync::Notify,
+    // Per-language caps layered under `semaphore`, with the cap each was
+    // made for. Created the first time a capped language is translated.
+    language_semaphores: Mutex<HashMap<Language, (usize, Arc<Semaphore>)>>,
 }
 
 struct TwitchBotState {
     client_id: Mutex<Option<String>>,
+    // The user access token, despite the name
     client_secret: Mutex<Option<String>>,
+    refresh_token: Mutex<Option<String>>,
+    // The Twitch application's secret, only needed to refresh tokens of
+    // confidential clients
+    app_secret: Mutex<Option<String>>,
+    // Held while a bot refreshes the login, so two bots can't spend the
+    // same refresh token
+    refreshing: tokio::sync::Mutex<()>,
 }
 
 struct AuthorizationFlow {
     client_id: Mutex<Option<String>>,
+    client_secret: Mutex<Option<String>>,
     builder: Mutex<Option<DeviceUserTokenBuilder>>,
 }
 
 struct JoinedChannelState {
-    join_handle: Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    // One bot task per joined channel, keyed by broadcaster login
+    join_handles: Mutex<HashMap<String, tauri::async_runtime::JoinHandle<()>>>,
+    language_stats: Mutex<bot::ChannelLanguageStats>,
+    emote_cache: Mutex<HashMap<String, Arc<emotes::EmoteSet>>>,
+    // Twitch's reply rate limit is per account, so every channel shares it
+    reply_limiter: Arc<Mutex<bot::ReplyLimiter>>,
+    // Each channel's language override, shared with its bot so it can be
+    // changed without rejoining
+    forced_languages: Mutex<HashMap<String, Arc<Mutex<Option<Language>>>>>,
+    // Each channel's translation counts, shared with its bot
+    translation_stats: Mutex<HashMap<String, Arc<bot::TranslationStats>>>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 struct TranslationResponse {
     language: String,
+    target_language: String,
     translation: String,
+    sentiment: Option<model::Sentiment>,
+    toxicity: Option<u8>,
+    /// The translation is only the slang-normalized text; the LLM was skipped.
+    normalized_only: bool,
+    /// The message was too long for the model and only its start was
+    /// translated.
+    truncated: bool,
+    /// How sure language detection was of `language`, from 0 to 1.
+    confidence: Option<f64>,
+    /// Whether lingua detected `language` in this message just now, rather
+    /// than it coming from a fast path or a forced language.
+    /// Only these count towards a channel's language stats.
+    #[serde(skip)]
+    detected: bool,
+    /// Left as written because it needed no translation: universal slang,
+    /// or already in `target_language`.
+    #[serde(skip)]
+    already_target: bool,
+    /// What the model was given after slang normalization, only filled in
+    /// when asked for with `debug`.
+    normalized_input: Option<String>,
+    /// Why the model stopped generating, only filled in when asked for with
+    /// `debug` and when the model ran.
+    stop_reason: Option<model::StopReason>,
+    /// Time spent in each stage, only filled in when asked for with `debug`.
+    debug_timings: Option<model::StageTimings>,
 }
 
 fn main() {
@@ -61,45 +220,138 @@ fn main() {
         .plugin(tauri_plugin_store::Builder::default().build())
         .invoke_handler(tauri::generate_handler![
             translate,
+            translate_stream,
+            translate_batch,
             get_token,
             wait_for_token,
+            logout,
             check_auth_status,
+            get_auth_status,
             join_channel,
+            set_channel_language,
             leave_channel,
-            is_in_channel
+            is_in_channel,
+            list_channels,
+            channel_language_stats,
+            get_translation_stats,
+            reset_stats,
+            get_settings,
+            update_settings,
+            clear_translation_cache,
+            set_active_glossary,
+            list_slang_entries,
+            normalize_slang,
+            self_test,
+            detect_language,
+            add_slang_entry,
+            remove_slang_entry,
+            set_max_concurrent_translations,
+            set_translation_mode,
+            set_reply_template,
+            set_system_prompt,
+            set_inference_limits,
+            translation_status,
+            load_model_from_path,
+            is_model_loaded,
+            get_status,
+            set_enabled_languages,
+            model_load_error,
+            reload_model,
+            download_model,
+            cancel_model_download
         ])
         .setup(move |app| {
             color_eyre::install()?;
-            tracing_subscriber::fmt::fmt()
-                .with_writer(std::io::stderr)
-                .init();
 
             let app_handle = app.handle();
 
-            let llama_backend = Arc::new(
-                model::initialize_llama_backend().expect("Failed to load llamacpp backend!"),
-            );
+            // Settings come first since they decide where logs go
+            let (loaded_settings, settings_error) = match settings::load(app_handle) {
+                Ok(settings) => (settings, None),
+                Err(err) => (settings::Settings::default(), Some(err)),
+            };
+            app.manage(logging::init(app_handle, &loaded_settings));
+            if let Some(err) = settings_error {
+                tracing::warn!("Failed to load settings, using defaults: {}", err);
+            }
+            let active_glossary = loaded_settings.active_glossary.as_ref().and_then(|name| {
+                glossary::load_glossary(app_handle, name)
+                    .map_err(|err| tracing::warn!("Failed to load glossary: {}", err))
+                    .ok()
+            });
 
-            let llm = Arc::new(
-                model::initialize_llm_from_app_handle(&app_handle, &llama_backend)
-                    .expect("failed to load qwen3 model!"),
-            );
+            let pool_size = loaded_settings.context_pool_size.max(1);
+            let detection_languages = loaded_settings.detection_languages();
+            let translation_cache_size = loaded_settings.translation_cache_size;
+            if let Err(err) = custom_slang::load(app_handle) {
+                tracing::warn!("Failed to load custom slang: {}", err);
+            }
+
+            let concurrency_limit = loaded_settings
+                .max_concurrent_translations
+                .clamp(1, pool_size);
+
+            app.manage(settings::SettingsState {
+                settings: Mutex::new(loaded_settings),
+            });
 
-            let mut contexts = Vec::new();
-            for _ in 0..5 {
-                let ctx = model::initialize_llama_context(&llama_backend, &llm)
-                    .expect("Failed to create context");
-                contexts.push(ctx);
+            // Without a model the app still starts, so Twitch features keep
+            // working and the user can fix the file or download one
+            let llama_backend = model::initialize_llama_backend()
+                .map(Arc::new)
+                .map_err(|err| tracing::error!("Failed to load llama.cpp backend: {:#}", err))
+                .ok();
+
+            let (llm_state, load_error) = match &llama_backend {
+                Some(backend) => {
+                    match load_configured_model(app_handle, backend, pool_size, concurrency_limit) {
+                        Ok(llm_state) => (Some(Arc::new(llm_state)), None),
+                        Err(err) => {
+                            tracing::warn!("No translation model loaded: {:#}", err);
+                            (None, Some(model_load_error_message(&err)))
+                        }
+                    }
+                }
+                None => (None, Some(BACKEND_UNAVAILABLE.to_string())),
+            };
+
+            // Offer a download on first launch. A download can't help if
+            // llama.cpp itself failed, so that case only records the error.
+            // The window may not be listening yet, so the frontend also
+            // checks `get_status` when it loads.
+            if let (Some(_), Some(error)) = (&llama_backend, &load_error) {
+                let _ = app_handle.emit(
+                    "model-missing",
+                    ModelMissingPayload {
+                        error: error.clone(),
+                        download: download::DEFAULT_MODEL.name,
+                    },
+                );
             }
 
             app.manage(TranslationModelState {
-                detector: model::initialize_lingua(),
-                llm_state: Arc::new(RefiningModelState {
-                    backend: llama_backend,
-                    model: llm,
-                    context_pool: Mutex::new(contexts),
-                }),
-                semaphore: Arc::new(Semaphore::new(CONTEXT_THREADS)),
+                detector: RwLock::new(model::initialize_lingua(&detection_languages)),
+                backend: llama_backend,
+                llm_state: Mutex::new(llm_state),
+                load_error: Mutex::new(load_error),
+                pool_size: AtomicUsize::new(pool_size),
+                concurrency_limit: Mutex::new(concurrency_limit),
+                queued: AtomicUsize::new(0),
+                in_flight: AtomicUsize::new(0),
+                shed_queue: model::ShedQueue::default(),
+                high_priority_waiting: AtomicUsize::new(0),
+                high_priority_served: tokio::sync::Notify::new(),
+                overloaded_since: Mutex::new(None),
+                fast_mode: AtomicBool::new(false),
+                decode_failures: AtomicUsize::new(0),
+                app_handle: app_handle.clone(),
+                m2m100: tokio::sync::OnceCell::new(),
+                m2m100_semaphore: Arc::new(Semaphore::new(1)),
+                cache: Mutex::new(cache::TranslationCache::new(translation_cache_size)),
+                glossary: Mutex::new(active_glossary),
+                batch_queue: Mutex::new(model::BatchQueue::default()),
+                batch_full: tokio::sync::Notify::new(),
+                language_semaphores: Mutex::new(HashMap::new()),
             });
 
             let store = app.store(STORE_PATH)?;
@@ -108,6 +360,9 @@ fn main() {
             let twitch_bot_state = TwitchBotState {
                 client_id: Mutex::new(None),
                 client_secret: Mutex::new(None),
+                refresh_token: Mutex::new(None),
+                app_secret: Mutex::new(None),
+                refreshing: tokio::sync::Mutex::new(()),
             };
 
             // Load from Store if exists
@@ -118,38 +373,856 @@ fn main() {
                 }
             }
 
-            let client_secret = store.get(CLIENT_SECRET_KEY);
-            if let Some(value) = client_secret {
-                if let serde_json::Value::String(value) = value {
-                    *twitch_bot_state.client_secret.lock().unwrap() = Some(value.clone());
+            // The token itself lives in the OS credential store
+            match credentials::load(app_handle, CLIENT_SECRET_KEY) {
+                Ok(client_secret) => {
+                    *twitch_bot_state.client_secret.lock().unwrap() = client_secret
                 }
+                Err(err) => tracing::warn!("Failed to load the Twitch login: {}", err),
+            }
+            match credentials::load(app_handle, REFRESH_TOKEN_KEY) {
+                Ok(refresh_token) => {
+                    *twitch_bot_state.refresh_token.lock().unwrap() = refresh_token
+                }
+                Err(err) => tracing::warn!("Failed to load the Twitch refresh token: {}", err),
+            }
+            match credentials::load(app_handle, APP_SECRET_KEY) {
+                Ok(app_secret) => *twitch_bot_state.app_secret.lock().unwrap() = app_secret,
+                Err(err) => tracing::warn!("Failed to load the Twitch client secret: {}", err),
             }
 
+            let logged_in = twitch_bot_state.client_secret.lock().unwrap().is_some();
             app.manage(twitch_bot_state);
+            app.manage(download::DownloadState::default());
             app.manage(AuthorizationFlow {
                 client_id: Mutex::new(None),
+                client_secret: Mutex::new(None),
                 builder: Mutex::new(None),
             });
             app.manage(JoinedChannelState {
-                join_handle: Mutex::new(None),
+                join_handles: Mutex::new(HashMap::new()),
+                language_stats: Mutex::new(bot::ChannelLanguageStats::default()),
+                emote_cache: Mutex::new(HashMap::new()),
+                reply_limiter: Arc::new(Mutex::new(bot::ReplyLimiter::default())),
+                forced_languages: Mutex::new(HashMap::new()),
+                translation_stats: Mutex::new(HashMap::new()),
             });
 
+            let saved_channels: Vec<String> = store
+                .get(JOINED_CHANNELS_KEY)
+                .and_then(|value| serde_json::from_value(value).ok())
+                .unwrap_or_default();
+            if logged_in && settings::current(app_handle).auto_rejoin && !saved_channels.is_empty()
+            {
+                tauri::async_runtime::spawn(rejoin_saved_channels(
+                    app_handle.clone(),
+                    saved_channels,
+                ));
+            }
+
             Ok(())
         })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
-#[tauri::command]
-async fn translate(
-    text: String,
-    state: tauri::State<'_, TranslationModelState>,
-) -> Result<TranslationResponse, String> {
-    model::perform_translation(text, &state).await
+/// Translates `text`. With `debug`, the response includes how long each
+/// stage took.
+#[tauri::command]
+async fn translate(
+    text: String,
+    debug: Option<bool>,
+    state: tauri::State<'_, TranslationModelState>,
+    settings_state: tauri::State<'_, settings::SettingsState>,
+) -> Result<TranslationResponse, String> {
+    let settings = settings_state
+        .settings
+        .lock()
+        .map_err(|_| "Poisoned lock")?
+        .clone();
+    let mut response = model::perform_translation(
+        text,
+        &state,
+        &settings,
+        None,
+        None,
+        model::Direction::ToEnglish,
+        model::Priority::Normal,
+    )
+    .await?;
+
+    if !debug.unwrap_or(false) {
+        response.normalized_input = None;
+        response.stop_reason = None;
+        response.debug_timings = None;
+    }
+    Ok(response)
+}
+
+/// Translates every text in `texts` concurrently, as far as the concurrency
+/// limit allows. Results come back in input order, each with its own error.
+#[tauri::command]
+async fn translate_batch(
+    texts: Vec<String>,
+    state: tauri::State<'_, TranslationModelState>,
+    settings_state: tauri::State<'_, settings::SettingsState>,
+) -> Result<Vec<Result<TranslationResponse, String>>, String> {
+    let settings = settings_state
+        .settings
+        .lock()
+        .map_err(|_| "Poisoned lock")?
+        .clone();
+
+    let translations = texts.into_iter().map(|text| {
+        model::perform_translation(
+            text,
+            &state,
+            &settings,
+            None,
+            None,
+            model::Direction::ToEnglish,
+            model::Priority::Normal,
+        )
+    });
+
+    Ok(futures::future::join_all(translations)
+        .await
+        .into_iter()
+        .map(|result| {
+            result.map(|mut response| {
+                response.normalized_input = None;
+                response.stop_reason = None;
+                response.debug_timings = None;
+                response
+            })
+        })
+        .collect())
+}
+
+#[derive(Clone, Serialize, Debug)]
+struct TranslationTokenPayload {
+    request_id: String,
+    token: String,
+}
+
+#[derive(Clone, Serialize, Debug)]
+struct TranslationDonePayload {
+    request_id: String,
+    result: Result<TranslationResponse, String>,
+}
+
+/// Same as `translate`, but streams the translation as `translation-token`
+/// events and finishes with `translation-done`, both tagged with `request_id`.
+/// With `target_language`, English `text` is translated into it instead, as
+/// for a reply to chat.
+#[tauri::command]
+async fn translate_stream(
+    request_id: String,
+    text: String,
+    target_language: Option<String>,
+    debug: Option<bool>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, TranslationModelState>,
+    settings_state: tauri::State<'_, settings::SettingsState>,
+) -> Result<(), String> {
+    let settings = settings_state
+        .settings
+        .lock()
+        .map_err(|_| "Poisoned lock")?
+        .clone();
+    let direction = match target_language.as_deref() {
+        Some(name) => model::Direction::FromEnglish(parse_language(name)?),
+        None => model::Direction::ToEnglish,
+    };
+
+    let token_app = app.clone();
+    let token_request_id = request_id.clone();
+    let on_token = Box::new(move |token: &str| {
+        let _ = token_app.emit(
+            "translation-token",
+            &TranslationTokenPayload {
+                request_id: token_request_id.clone(),
+                token: token.to_string(),
+            },
+        );
+    });
+
+    let result = model::stream_translation(
+        text,
+        &state,
+        &settings,
+        None,
+        None,
+        direction,
+        model::Priority::Normal,
+        on_token,
+    )
+    .await
+    .map(|mut response| {
+        if !debug.unwrap_or(false) {
+            response.normalized_input = None;
+            response.stop_reason = None;
+            response.debug_timings = None;
+        }
+        response
+    });
+
+    app.emit(
+        "translation-done",
+        &TranslationDonePayload { request_id, result },
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_settings(
+    state: tauri::State<'_, settings::SettingsState>,
+) -> Result<settings::Settings, String> {
+    Ok(state
+        .settings
+        .lock()
+        .map_err(|_| "Poisoned lock")?
+        .redacted())
+}
+
+/// Saves `new_settings` and applies them. The context pool can't be resized
+/// here; a different `context_pool_size` is an error, use
+/// `set_inference_limits` instead.
+#[tauri::command]
+async fn update_settings(
+    app: tauri::AppHandle,
+    mut new_settings: settings::Settings,
+    state: tauri::State<'_, TranslationModelState>,
+) -> Result<settings::Settings, String> {
+    if let Some(template) = &new_settings.reply_template {
+        bot::validate_reply_template(template)?;
+    }
+    // A misspelled language would quietly translate into English
+    parse_language(&new_settings.target_language)?;
+    if let Some(language) = &new_settings.broadcaster_target_language {
+        parse_language(language)?;
+    }
+    let current = settings::current(&app);
+    // Resizing the pool means rebuilding it, which is `set_inference_limits`' job
+    if new_settings.context_pool_size != current.context_pool_size {
+        return Err(format!(
+            "The context pool holds {} contexts; resize it with set_inference_limits",
+            current.context_pool_size
+        ));
+    }
+    // A new glossary is loaded up front, so a missing one changes nothing
+    let glossary = if new_settings.active_glossary != current.active_glossary {
+        match &new_settings.active_glossary {
+            Some(name) => Some(Some(glossary::load_glossary(&app, name)?)),
+            None => Some(None),
+        }
+    } else {
+        None
+    };
+    if let Some(warning) = new_settings
+        .system_prompt
+        .as_deref()
+        .and_then(model::system_prompt_warning)
+    {
+        tracing::warn!("{}", warning);
+    }
+
+    settings::store_api_key(&app, &mut new_settings)?;
+
+    // The enabled languages are `set_enabled_languages`' to change
+    new_settings.enabled_languages = current.enabled_languages.clone();
+    // The detector also knows the target language, so a new target needs
+    // a new detector
+    let detection_languages = new_settings.detection_languages();
+    if detection_languages != current.detection_languages() {
+        let detector = tauri::async_runtime::spawn_blocking(move || {
+            model::initialize_lingua(&detection_languages)
+        })
+        .await
+        .map_err(|e| format!("Task Join Error: {}", e))?;
+        *state.detector.write().map_err(|_| "Poisoned lock")? = detector;
+    }
+    new_settings.max_concurrent_translations =
+        model::set_concurrency_limit(&state, new_settings.max_concurrent_translations)?;
+    let glossary_changed = glossary.is_some();
+    if let Some(glossary) = glossary {
+        if let Some(glossary) = &glossary {
+            tracing::info!("Using glossary {}", glossary.name);
+        }
+        *state.glossary.lock().map_err(|_| "Poisoned lock")? = glossary;
+    }
+    {
+        let mut cache = state.cache.lock().map_err(|_| "Poisoned lock")?;
+        cache.resize(new_settings.translation_cache_size);
+        // Cached translations were produced with the old instructions or
+        // glossary
+        if new_settings.system_prompt != current.system_prompt
+            || new_settings.preserve_profanity != current.preserve_profanity
+            || glossary_changed
+        {
+            cache.clear();
+        }
+    }
+    settings::update(&app, |settings| *settings = new_settings).map(|settings| settings.redacted())
+}
+
+/// Forgets every cached translation, e.g. after editing a glossary on disk.
+#[tauri::command]
+async fn clear_translation_cache(
+    state: tauri::State<'_, TranslationModelState>,
+) -> Result<(), String> {
+    state.cache.lock().map_err(|_| "Poisoned lock")?.clear();
+    Ok(())
+}
+
+/// Switches between replying in chat and only translating for the app.
+#[tauri::command]
+async fn set_translation_mode(
+    app: tauri::AppHandle,
+    mode: bot::TranslationMode,
+) -> Result<(), String> {
+    settings::update(&app, |settings| settings.translation_mode = mode)?;
+    Ok(())
+}
+
+/// Sets the layout of chat replies, or goes back to the default with `None`.
+/// Templates with unknown placeholders or stray braces are rejected.
+#[tauri::command]
+async fn set_reply_template(app: tauri::AppHandle, template: Option<String>) -> Result<(), String> {
+    if let Some(template) = &template {
+        bot::validate_reply_template(template)?;
+    }
+    settings::update(&app, |settings| settings.reply_template = template)?;
+    Ok(())
+}
+
+/// Sets the instructions chat is translated with, `{lang}` standing for
+/// the target language, or goes back to the built-in ones with `None`.
+/// Returns a warning if the prompt would make the model translate things it
+/// should skip.
+#[tauri::command]
+async fn set_system_prompt(
+    app: tauri::AppHandle,
+    prompt: Option<String>,
+    state: tauri::State<'_, TranslationModelState>,
+) -> Result<Option<String>, String> {
+    let prompt = prompt.filter(|prompt| !prompt.trim().is_empty());
+    let warning = prompt.as_deref().and_then(model::system_prompt_warning);
+    if let Some(warning) = &warning {
+        tracing::warn!("{}", warning);
+    }
+
+    settings::update(&app, |settings| settings.system_prompt = prompt)?;
+    // Cached translations were produced with the old prompt
+    state.cache.lock().map_err(|_| "Poisoned lock")?.clear();
+
+    Ok(warning)
+}
+
+/// Changes how many translations run at once. The limit can never exceed the
+/// number of llama contexts, so larger values are clamped; the applied limit
+/// is returned.
+#[tauri::command]
+async fn set_max_concurrent_translations(
+    app: tauri::AppHandle,
+    limit: usize,
+    state: tauri::State<'_, TranslationModelState>,
+) -> Result<usize, String> {
+    let limit = model::set_concurrency_limit(&state, limit)?;
+    settings::update(&app, |settings| {
+        settings.max_concurrent_translations = limit
+    })?;
+
+    Ok(limit)
+}
+
+#[derive(Clone, Serialize, Debug)]
+struct InferenceLimits {
+    context_pool_size: usize,
+    max_concurrent_translations: usize,
+}
+
+/// Rebuilds the context pool with `context_pool_size` contexts and allows
+/// `max_concurrent_translations` of them to be used at once (clamped to the
+/// pool). Translations already running finish on the old pool. Returns the
+/// limits that were applied.
+#[tauri::command]
+async fn set_inference_limits(
+    app: tauri::AppHandle,
+    context_pool_size: usize,
+    max_concurrent_translations: usize,
+    state: tauri::State<'_, TranslationModelState>,
+) -> Result<InferenceLimits, String> {
+    let pool_size = context_pool_size.max(1);
+    let concurrency_limit = max_concurrent_translations.clamp(1, pool_size);
+
+    let current = state.llm_state.lock().map_err(|_| "Poisoned lock")?.clone();
+    let rebuilt = match current {
+        Some(current) => {
+            let backend = current.backend.clone();
+            let llm = current.model.clone();
+            let model_info = current.model_info.clone();
+            let rebuilt = tauri::async_runtime::spawn_blocking(move || {
+                RefiningModelState::new(backend, llm, model_info, pool_size, concurrency_limit)
+            })
+            .await
+            .map_err(|e| format!("Task Join Error: {}", e))?
+            .map_err(|e| format!("{:#}", e))?;
+            Some(Arc::new(rebuilt))
+        }
+        None => None,
+    };
+
+    {
+        // The limit lock is held while swapping so a concurrent resize can't
+        // be applied to the pool being replaced
+        let mut limit = state
+            .concurrency_limit
+            .lock()
+            .map_err(|_| "Poisoned lock")?;
+        state.pool_size.store(pool_size, Ordering::SeqCst);
+        *limit = concurrency_limit;
+        if rebuilt.is_some() {
+            *state.llm_state.lock().map_err(|_| "Poisoned lock")? = rebuilt;
+        }
+    }
+
+    settings::update(&app, |settings| {
+        settings.context_pool_size = pool_size;
+        settings.max_concurrent_translations = concurrency_limit;
+    })?;
+
+    tracing::info!(
+        "Running {} contexts, {} translations at a time",
+        pool_size,
+        concurrency_limit
+    );
+
+    Ok(InferenceLimits {
+        context_pool_size: pool_size,
+        max_concurrent_translations: concurrency_limit,
+    })
+}
+
+/// Detects only `languages` (by name, e.g. "French") plus the target
+/// language from now on, and saves the choice. Fewer languages load fewer
+/// models and leave lingua fewer wrong answers to pick. Returns the
+/// languages the detector was built with.
+#[tauri::command]
+async fn set_enabled_languages(
+    app: tauri::AppHandle,
+    languages: Vec<String>,
+    state: tauri::State<'_, TranslationModelState>,
+) -> Result<Vec<String>, String> {
+    for name in &languages {
+        parse_language(name)?;
+    }
+
+    let mut candidate = settings::current(&app);
+    candidate.enabled_languages = languages.clone();
+    let detection_languages = candidate.detection_languages();
+    if detection_languages.len() < 2 {
+        return Err("Enable at least one language besides the target language".to_string());
+    }
+
+    // Preloading the language models takes a moment
+    let applied = detection_languages.clone();
+    let detector = tauri::async_runtime::spawn_blocking(move || {
+        model::initialize_lingua(&detection_languages)
+    })
+    .await
+    .map_err(|e| format!("Task Join Error: {}", e))?;
+
+    *state.detector.write().map_err(|_| "Poisoned lock")? = detector;
+    settings::update(&app, |settings| settings.enabled_languages = languages)?;
+
+    Ok(applied
+        .into_iter()
+        .map(|language| language.to_string())
+        .collect())
+}
+
+#[tauri::command]
+async fn translation_status(
+    state: tauri::State<'_, TranslationModelState>,
+) -> Result<model::TranslationStatus, String> {
+    model::translation_status(&state)
+}
+
+/// Swaps in a GGUF from anywhere on disk, with a fresh context pool.
+/// Translations already running finish on the old model.
+#[tauri::command]
+async fn load_model_from_path(
+    path: String,
+    state: tauri::State<'_, TranslationModelState>,
+) -> Result<(), String> {
+    let path = std::path::PathBuf::from(path);
+    if !path.is_file() {
+        return Err(format!("No model file at {}", path.display()));
+    }
+    if path.extension().and_then(|ext| ext.to_str()) != Some("gguf") {
+        return Err(format!("{} is not a .gguf file", path.display()));
+    }
+
+    swap_model(&state, path).await
+}
+
+#[derive(Clone, Serialize, Debug)]
+struct ModelMissingPayload {
+    error: String,
+    /// The `download_model` name to offer.
+    download: &'static str,
+}
+
+#[derive(Clone, Serialize, Debug)]
+struct AppStatus {
+    model_loaded: bool,
+    /// The loaded model's file and GPU offload, if one is loaded.
+    model: Option<model::ModelInfo>,
+    /// Why the model isn't loaded, if loading it failed.
+    load_error: Option<String>,
+    /// Idle llama contexts. `None` if the pool was being used at the time.
+    available_contexts: Option<usize>,
+    /// Translations that could start right now without waiting.
+    available_permits: usize,
+    /// Whether any channel's bot task is running.
+    bot_running: bool,
+    /// Whether llama.cpp was built with GPU support.
+    gpu_offload_supported: bool,
+    /// The GPU llama.cpp found at runtime, e.g. "Vulkan (AMD Radeon
+    /// RX 6700 XT)". The model may still run on the CPU; see `model`.
+    gpu_device: Option<String>,
+}
+
+/// A snapshot of the model and bots for a status panel. Never waits on a
+/// context, so it answers even while every one is translating.
+#[tauri::command]
+async fn get_status(
+    state: tauri::State<'_, TranslationModelState>,
+    bot_state: tauri::State<'_, JoinedChannelState>,
+) -> Result<AppStatus, String> {
+    let llm_state = state.llm_state.lock().map_err(|_| "Poisoned lock")?.clone();
+    let load_error = state
+        .load_error
+        .lock()
+        .map_err(|_| "Poisoned lock")?
+        .clone();
+
+    let bot_running = {
+        let mut handles = bot_state
+            .join_handles
+            .lock()
+            .map_err(|_| "Failed to lock mutex")?;
+        prune_finished(&mut handles);
+        !handles.is_empty()
+    };
+
+    Ok(AppStatus {
+        model_loaded: llm_state.is_some(),
+        model: llm_state.as_ref().map(|llm| llm.model_info.clone()),
+        load_error,
+        available_contexts: llm_state
+            .as_ref()
+            .and_then(|llm| llm.context_pool.try_lock().ok().map(|pool| pool.len())),
+        available_permits: llm_state
+            .as_ref()
+            .map_or(0, |llm| llm.semaphore.available_permits()),
+        bot_running,
+        gpu_offload_supported: state
+            .backend
+            .as_ref()
+            .is_some_and(|backend| backend.supports_gpu_offload()),
+        gpu_device: state.backend.as_ref().and_then(|_| model::gpu_device()),
+    })
+}
+
+#[tauri::command]
+async fn is_model_loaded(state: tauri::State<'_, TranslationModelState>) -> Result<bool, String> {
+    Ok(state
+        .llm_state
+        .lock()
+        .map_err(|_| "Poisoned lock")?
+        .is_some())
+}
+
+/// Downloads one of `download::KNOWN_MODELS` into the app data dir and
+/// loads it. Progress arrives as `model-download-progress` events.
+#[tauri::command]
+async fn download_model(
+    app: tauri::AppHandle,
+    name: String,
+    state: tauri::State<'_, TranslationModelState>,
+) -> Result<(), String> {
+    let path = download::download_model(&app, &name).await?;
+    swap_model(&state, path).await
+}
+
+#[tauri::command]
+async fn cancel_model_download(app: tauri::AppHandle) -> Result<bool, String> {
+    download::cancel_download(&app)
+}
+
+/// Why no model is loaded, if the last attempt to load one failed.
+#[tauri::command]
+async fn model_load_error(
+    state: tauri::State<'_, TranslationModelState>,
+) -> Result<Option<String>, String> {
+    Ok(state
+        .load_error
+        .lock()
+        .map_err(|_| "Poisoned lock")?
+        .clone())
+}
+
+/// Tries loading the configured model again, the same way startup does,
+/// e.g. after the user has fixed or replaced the file.
+#[tauri::command]
+async fn reload_model(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, TranslationModelState>,
+) -> Result<(), String> {
+    let backend = state.backend.clone().ok_or(BACKEND_UNAVAILABLE)?;
+    let pool_size = state.pool_size.load(Ordering::SeqCst);
+    let concurrency_limit = *state
+        .concurrency_limit
+        .lock()
+        .map_err(|_| "Poisoned lock")?;
+
+    let loaded = tauri::async_runtime::spawn_blocking(move || {
+        load_configured_model(&app, &backend, pool_size, concurrency_limit)
+    })
+    .await
+    .map_err(|e| format!("Task Join Error: {}", e))?;
+
+    let llm_state = match loaded {
+        Ok(llm_state) => llm_state,
+        Err(err) => {
+            let message = model_load_error_message(&err);
+            *state.load_error.lock().map_err(|_| "Poisoned lock")? = Some(message.clone());
+            return Err(message);
+        }
+    };
+
+    *state.llm_state.lock().map_err(|_| "Poisoned lock")? = Some(Arc::new(llm_state));
+    *state.load_error.lock().map_err(|_| "Poisoned lock")? = None;
+    // The file may have changed since the cached translations were made
+    state.cache.lock().map_err(|_| "Poisoned lock")?.clear();
+
+    Ok(())
+}
+
+// Prefers the bundled model, then one downloaded at runtime
+fn load_configured_model(
+    app_handle: &tauri::AppHandle,
+    backend: &Arc<LlamaBackend>,
+    pool_size: usize,
+    concurrency_limit: usize,
+) -> anyhow::Result<RefiningModelState> {
+    let (llm, model_info) =
+        model::initialize_llm_from_app_handle(app_handle, backend).or_else(|err| {
+            let path =
+                download::downloaded_model_path(app_handle, &model::model_filename(app_handle))
+                    .map_err(anyhow::Error::msg)?;
+            if !path.is_file() {
+                return Err(err);
+            }
+            let gpu_layers = settings::current(app_handle).gpu_layers;
+            model::load_model_from_file(backend, &path, gpu_layers)
+        })?;
+
+    RefiningModelState::new(
+        backend.clone(),
+        Arc::new(llm),
+        model_info,
+        pool_size,
+        concurrency_limit,
+    )
+}
+
+fn model_load_error_message(err: &anyhow::Error) -> String {
+    format!("Couldn't load the translation model: {:#}", err)
+}
+
+async fn swap_model(state: &TranslationModelState, path: std::path::PathBuf) -> Result<(), String> {
+    let backend = state.backend.clone().ok_or(BACKEND_UNAVAILABLE)?;
+    let pool_size = state.pool_size.load(Ordering::SeqCst);
+    let concurrency_limit = *state
+        .concurrency_limit
+        .lock()
+        .map_err(|_| "Poisoned lock")?;
+
+    let gpu_layers = settings::current(&state.app_handle).gpu_layers;
+
+    let llm_state = tauri::async_runtime::spawn_blocking(move || {
+        let (llm, model_info) = model::load_model_from_file(&backend, &path, gpu_layers)?;
+        tracing::info!("Loaded model from {}", path.display());
+        RefiningModelState::new(
+            backend,
+            Arc::new(llm),
+            model_info,
+            pool_size,
+            concurrency_limit,
+        )
+    })
+    .await
+    .map_err(|e| format!("Task Join Error: {}", e))?
+    .map_err(|e| format!("{:#}", e))?;
+
+    *state.llm_state.lock().map_err(|_| "Poisoned lock")? = Some(Arc::new(llm_state));
+    *state.load_error.lock().map_err(|_| "Poisoned lock")? = None;
+    // Cached translations came from the old model
+    state.cache.lock().map_err(|_| "Poisoned lock")?.clear();
+
+    Ok(())
+}
+
+/// Shows what the slang dictionaries make of `text` without translating
+/// it. `lang` skips detection, e.g. to test a custom entry for a language
+/// the text isn't detected as.
+#[tauri::command]
+async fn normalize_slang(
+    text: String,
+    lang: Option<String>,
+    state: tauri::State<'_, TranslationModelState>,
+    settings_state: tauri::State<'_, settings::SettingsState>,
+) -> Result<model::SlangPreview, String> {
+    let forced_language = lang.as_deref().map(parse_language).transpose()?;
+    let settings = settings_state
+        .settings
+        .lock()
+        .map_err(|_| "Poisoned lock")?
+        .clone();
+
+    model::preview_normalization(&state, &settings, &text, forced_language)
+}
+
+/// Which language the bot takes `text` to be, and how sure it is, without
+/// running the model.
+#[tauri::command]
+async fn detect_language(
+    text: String,
+    state: tauri::State<'_, TranslationModelState>,
+    settings_state: tauri::State<'_, settings::SettingsState>,
+) -> Result<model::DetectionResult, String> {
+    let settings = settings_state
+        .settings
+        .lock()
+        .map_err(|_| "Poisoned lock")?
+        .clone();
+
+    Ok(model::detect_message_language(&state, &settings, &text))
+}
+
+/// Runs sample messages through the whole pipeline with the current
+/// settings, so the model, prompt and dictionaries can be checked before
+/// going live.
+#[tauri::command]
+async fn self_test(
+    state: tauri::State<'_, TranslationModelState>,
+    settings_state: tauri::State<'_, settings::SettingsState>,
+) -> Result<Vec<model::SelfTestResult>, String> {
+    let settings = settings_state
+        .settings
+        .lock()
+        .map_err(|_| "Poisoned lock")?
+        .clone();
+
+    Ok(model::self_test(&state, &settings).await)
+}
+
+#[tauri::command]
+async fn list_slang_entries() -> Result<Vec<custom_slang::SlangEntry>, String> {
+    custom_slang::entries()
+}
+
+/// Adds a term to the custom slang dictionary (`custom_slang.json`), taking
+/// effect on the next message.
+#[tauri::command]
+async fn add_slang_entry(
+    app: tauri::AppHandle,
+    entry: custom_slang::SlangEntry,
+    state: tauri::State<'_, TranslationModelState>,
+) -> Result<(), String> {
+    custom_slang::add_entry(&app, entry)?;
+    // Cached translations were made with the old dictionary
+    state.cache.lock().map_err(|_| "Poisoned lock")?.clear();
+    Ok(())
+}
+
+/// Removes a term from the custom slang dictionary. Returns whether it was there.
+#[tauri::command]
+async fn remove_slang_entry(
+    app: tauri::AppHandle,
+    lang: custom_slang::SlangLanguage,
+    from: String,
+    state: tauri::State<'_, TranslationModelState>,
+) -> Result<bool, String> {
+    let removed = custom_slang::remove_entry(&app, lang, &from)?;
+    if removed {
+        state.cache.lock().map_err(|_| "Poisoned lock")?.clear();
+    }
+    Ok(removed)
+}
+
+/// Selects the per-game glossary used for translations, or clears it with `None`.
+#[tauri::command]
+async fn set_active_glossary(
+    app: tauri::AppHandle,
+    name: Option<String>,
+    state: tauri::State<'_, TranslationModelState>,
+) -> Result<(), String> {
+    let glossary = match &name {
+        Some(name) => Some(glossary::load_glossary(&app, name)?),
+        None => None,
+    };
+
+    if let Some(glossary) = &glossary {
+        tracing::info!("Using glossary {}", glossary.name);
+    }
+
+    *state.glossary.lock().map_err(|_| "Poisoned lock")? = glossary;
+    // Cached translations were produced with the old glossary
+    state.cache.lock().map_err(|_| "Poisoned lock")?.clear();
+
+    settings::update(&app, |settings| settings.active_glossary = name)?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn check_auth_status(state: tauri::State<'_, TwitchBotState>) -> Result<bool, String> {
+    Ok(validated_token(&state).await?.is_some())
+}
+
+#[derive(Clone, Serialize, Debug)]
+struct AuthStatus {
+    valid: bool,
+    // Until Twitch stops accepting the token, so the UI can ask for a new
+    // login before it runs out mid-stream
+    expires_in_secs: Option<u64>,
+    login: Option<String>,
 }
 
+/// Like `check_auth_status`, but also says whose login it is and when it
+/// expires.
 #[tauri::command]
-async fn check_auth_status(state: tauri::State<'_, TwitchBotState>) -> Result<bool, String> {
+async fn get_auth_status(state: tauri::State<'_, TwitchBotState>) -> Result<AuthStatus, String> {
+    Ok(match validated_token(&state).await? {
+        Some(token) => AuthStatus {
+            valid: true,
+            expires_in_secs: Some(token.expires_in().as_secs()),
+            login: Some(token.login.to_string()),
+        },
+        None => AuthStatus {
+            valid: false,
+            expires_in_secs: None,
+            login: None,
+        },
+    })
+}
+
+// The stored token, if Twitch still accepts it and it can read chat
+async fn validated_token(state: &TwitchBotState) -> Result<Option<UserToken>, String> {
     // 1. Lock mutexes to get values safely
     let client_id = state.client_id.lock().map_err(|_| "Poisoned lock")?.clone();
     let client_secret = state
@@ -174,22 +1247,59 @@ async fn check_auth_status(state: tauri::State<'_, TwitchBotState>) -> Result<bo
 
         match token {
             Ok(t) => {
-                if t.validate_token(&client).await.is_ok() {
-                    return Ok(true);
+                // The bot can't do anything without reading chat, so a token
+                // missing that scope needs a fresh login
+                if t.validate_token(&client).await.is_ok()
+                    && t.scopes().contains(&Scope::UserReadChat)
+                {
+                    return Ok(Some(t));
                 }
             }
-            Err(_) => return Ok(false),
+            Err(_) => return Ok(None),
         }
     }
 
-    Ok(false)
+    Ok(None)
+}
+
+/// Parses OAuth scope names like "user:read:chat", rejecting unknown ones.
+fn parse_scopes(names: &[String]) -> Result<Vec<Scope>, String> {
+    let scopes = names
+        .iter()
+        .map(|name| match Scope::parse(name.clone()) {
+            Scope::Other(_) => Err(format!("Unknown OAuth scope '{}'", name)),
+            scope => Ok(scope),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if !scopes.contains(&Scope::UserReadChat) {
+        return Err("The user:read:chat scope is required to read chat".to_string());
+    }
+
+    Ok(scopes)
 }
 
+/// Starts the device flow. `scopes` overrides (and replaces) the configured
+/// scope list; leave out `user:write:chat` for a read-only login.
+/// `client_secret` is only needed for confidential Twitch applications,
+/// which can't refresh their tokens without it.
 #[tauri::command]
 async fn get_token(
+    app: tauri::AppHandle,
     client_id: String,
+    client_secret: Option<String>,
+    scopes: Option<Vec<String>>,
     state: tauri::State<'_, AuthorizationFlow>,
 ) -> Result<String, String> {
+    let scopes = match scopes {
+        Some(scopes) => {
+            let parsed = parse_scopes(&scopes)?;
+            settings::update(&app, |settings| settings.oauth_scopes = scopes)?;
+            parsed
+        }
+        None => parse_scopes(&settings::current(&app).oauth_scopes)?,
+    };
+
     let client: HelixClient<reqwest::Client> = twitch_api::HelixClient::with_client(
         ClientDefault::default_client_with_name(Some(
             "star-system-bot"
@@ -199,16 +1309,17 @@ async fn get_token(
         .map_err(|e: ReqwestClientDefaultError| e.to_string())?,
     );
 
-    let mut builder = twitch_oauth2::tokens::DeviceUserTokenBuilder::new(
-        client_id.clone(),
-        vec![Scope::UserReadChat, Scope::UserWriteChat],
-    );
+    let mut builder = twitch_oauth2::tokens::DeviceUserTokenBuilder::new(client_id.clone(), scopes);
 
     let code = builder.start(&client).await.map_err(|e| e.to_string())?;
     let auth_url = code.verification_uri.to_string();
 
     *state.builder.lock().map_err(|_| "Failed to lock mutex")? = Some(builder);
     *state.client_id.lock().map_err(|_| "Failed to lock mutex")? = Some(client_id);
+    *state
+        .client_secret
+        .lock()
+        .map_err(|_| "Failed to lock mutex")? = client_secret.filter(|secret| !secret.is_empty());
 
     Ok(auth_url)
 }
@@ -245,49 +1356,409 @@ async fn wait_for_token(
         .await
         .map_err(|e| e.to_string())?;
 
-    let access_token = token.access_token.secret().to_string();
+    let app_secret = auth_flow
+        .client_secret
+        .lock()
+        .map_err(|_| "Failed to lock mutex")?
+        .take();
 
     // 4. Update the TwitchBotState (The Fix: Lock, then Assign)
     {
         let mut id_lock = bot_state.client_id.lock().map_err(|_| "Failed lock")?;
         *id_lock = Some(client_id_str.clone());
 
-        let mut secret_lock = bot_state.client_secret.lock().map_err(|_| "Failed lock")?;
-        *secret_lock = Some(access_token.clone());
+        let mut app_secret_lock = bot_state.app_secret.lock().map_err(|_| "Failed lock")?;
+        *app_secret_lock = app_secret.clone();
     }
 
-    // 5. Persist to Disk
+    // 5. Persist to Disk, keeping the secrets out of the JSON store
     let store = app.store(STORE_PATH).map_err(|err| err.to_string())?;
     store.set(CLIENT_ID_KEY, client_id_str);
-    store.set(CLIENT_SECRET_KEY, access_token);
     let _ = store.save(); // Don't forget to save!
 
+    match app_secret {
+        Some(app_secret) => credentials::save(&app, APP_SECRET_KEY, &app_secret)?,
+        None => credentials::delete(&app, APP_SECRET_KEY)?,
+    }
+    save_login(&app, &token)
+}
+
+// Remembers a new access token and its refresh token, in memory and on disk
+fn save_login(app: &tauri::AppHandle, token: &UserToken) -> Result<(), String> {
+    let state = app.state::<TwitchBotState>();
+    let access_token = token.access_token.secret().to_string();
+    let refresh_token = token
+        .refresh_token
+        .as_ref()
+        .map(|refresh_token| refresh_token.secret().to_string());
+
+    *state.client_secret.lock().map_err(|_| "Failed lock")? = Some(access_token.clone());
+    *state.refresh_token.lock().map_err(|_| "Failed lock")? = refresh_token.clone();
+
+    credentials::save(app, CLIENT_SECRET_KEY, &access_token)?;
+    match refresh_token {
+        Some(refresh_token) => credentials::save(app, REFRESH_TOKEN_KEY, &refresh_token),
+        None => credentials::delete(app, REFRESH_TOKEN_KEY),
+    }
+}
+
+/// Refreshes a bot's copy of the login and saves the new token. Every
+/// joined channel holds its own copy and Twitch only accepts a refresh token
+/// once, so a copy that another channel already refreshed is replaced with
+/// the saved token instead.
+async fn refresh_login(
+    app: &tauri::AppHandle,
+    client: &HelixClient<'static, reqwest::Client>,
+    token: &mut UserToken,
+) -> Result<(), String> {
+    let state = app.state::<TwitchBotState>().inner();
+    refresh_saved_login(state, client, token, |token| save_login(app, token)).await
+}
+
+// `refresh_login` with saving the new token left to `save`, so tests can
+// keep it out of the OS credential store
+async fn refresh_saved_login(
+    state: &TwitchBotState,
+    client: &HelixClient<'static, reqwest::Client>,
+    token: &mut UserToken,
+    save: impl FnOnce(&UserToken) -> Result<(), String>,
+) -> Result<(), String> {
+    let current = token.access_token.secret().to_string();
+
+    refresh_once(
+        &state.refreshing,
+        &state.client_secret,
+        &current,
+        |saved| async move {
+            let Some(saved) = saved else {
+                token
+                    .refresh_token(client)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                return save(token);
+            };
+
+            let refresh_token = state
+                .refresh_token
+                .lock()
+                .map_err(|_| "Failed lock")?
+                .clone();
+            let app_secret = state.app_secret.lock().map_err(|_| "Failed lock")?.clone();
+            *token = UserToken::from_existing(
+                client,
+                AccessToken::new(saved),
+                refresh_token.map(RefreshToken::new),
+                app_secret.map(ClientSecret::new),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(())
+        },
+    )
+    .await
+}
+
+// Runs `refresh` with the other bots' refreshes held off. It's given the
+// saved access token if that's no longer `current`, i.e. a bot that was
+// waiting its turn finds the login already refreshed, or `None` when the
+// login is still `current` and needs refreshing.
+async fn refresh_once<F, Fut>(
+    refreshing: &tokio::sync::Mutex<()>,
+    saved: &Mutex<Option<String>>,
+    current: &str,
+    refresh: F,
+) -> Result<(), String>
+where
+    F: FnOnce(Option<String>) -> Fut,
+    Fut: std::future::Future<Output = Result<(), String>>,
+{
+    let _refreshing = refreshing.lock().await;
+    // Read under the lock, so it includes any refresh that just finished
+    let saved = saved.lock().map_err(|_| "Failed lock")?.clone();
+
+    refresh(saved.filter(|saved| saved != current)).await
+}
+
+/// Forgets the Twitch account: leaves every channel, clears the stored
+/// credentials and, unless `revoke` is false, revokes the token with Twitch.
+#[tauri::command]
+async fn logout(
+    app: tauri::AppHandle,
+    revoke: Option<bool>,
+    bot_state: tauri::State<'_, TwitchBotState>,
+    channel_state: tauri::State<'_, JoinedChannelState>,
+) -> Result<(), String> {
+    let handles: Vec<_> = channel_state
+        .join_handles
+        .lock()
+        .map_err(|_| "Failed to lock mutex")?
+        .drain()
+        .map(|(_, handle)| handle)
+        .collect();
+    for handle in handles {
+        handle.abort();
+    }
+
+    let client_id = bot_state
+        .client_id
+        .lock()
+        .map_err(|_| "Failed lock")?
+        .take();
+    let access_token = bot_state
+        .client_secret
+        .lock()
+        .map_err(|_| "Failed lock")?
+        .take();
+    bot_state
+        .refresh_token
+        .lock()
+        .map_err(|_| "Failed lock")?
+        .take();
+    bot_state
+        .app_secret
+        .lock()
+        .map_err(|_| "Failed lock")?
+        .take();
+
+    let store = app.store(STORE_PATH).map_err(|err| err.to_string())?;
+    store.delete(CLIENT_ID_KEY);
+    store.delete(JOINED_CHANNELS_KEY);
+    store.save().map_err(|err| err.to_string())?;
+    credentials::delete(&app, CLIENT_SECRET_KEY)?;
+    credentials::delete(&app, REFRESH_TOKEN_KEY)?;
+    credentials::delete(&app, APP_SECRET_KEY)?;
+
+    // The local credentials are gone either way, so a failed revocation is
+    // only worth a warning
+    if revoke.unwrap_or(true) {
+        if let (Some(client_id), Some(access_token)) = (client_id, access_token) {
+            let revoked = AccessToken::new(access_token)
+                .revoke_token(&reqwest::Client::new(), &ClientId::new(client_id))
+                .await;
+            if let Err(e) = revoked {
+                tracing::warn!("Failed to revoke Twitch token: {}", e);
+            }
+        }
+    }
+
+    tracing::info!("Logged out");
     Ok(())
 }
 
+/// Whether the bot is in `broadcaster_login`'s channel, or in any channel
+/// when no login is given.
 #[tauri::command]
-async fn is_in_channel(bot_state: tauri::State<'_, JoinedChannelState>) -> Result<bool, String> {
-    if let Some(_) = *bot_state
-        .join_handle
+async fn is_in_channel(
+    broadcaster_login: Option<String>,
+    bot_state: tauri::State<'_, JoinedChannelState>,
+) -> Result<bool, String> {
+    let mut handles = bot_state
+        .join_handles
         .lock()
-        .map_err(|err| err.to_string())?
-    {
-        return Ok(true);
+        .map_err(|err| err.to_string())?;
+    prune_finished(&mut handles);
+
+    Ok(match broadcaster_login {
+        Some(login) => handles.contains_key(&normalize_login(&login)?),
+        None => !handles.is_empty(),
+    })
+}
+
+#[tauri::command]
+async fn list_channels(
+    bot_state: tauri::State<'_, JoinedChannelState>,
+) -> Result<Vec<String>, String> {
+    let mut handles = bot_state
+        .join_handles
+        .lock()
+        .map_err(|err| err.to_string())?;
+    prune_finished(&mut handles);
+
+    let mut logins: Vec<String> = handles.keys().cloned().collect();
+    logins.sort();
+
+    Ok(logins)
+}
+
+// Holds a channel's slot in `join_handles` while it's being joined, so a
+// second join of the same channel fails instead of starting another bot.
+// Unless the bot's handle has taken its place, the slot is freed on drop.
+struct JoinReservation<'a> {
+    handles: &'a Mutex<HashMap<String, tauri::async_runtime::JoinHandle<()>>>,
+    login: String,
+    filled: bool,
+}
+
+impl<'a> JoinReservation<'a> {
+    fn new(
+        handles: &'a Mutex<HashMap<String, tauri::async_runtime::JoinHandle<()>>>,
+        login: &str,
+    ) -> Result<Self, String> {
+        let mut locked = handles.lock().map_err(|_| "Failed to lock mutex")?;
+        prune_finished(&mut locked);
+        if locked.contains_key(login) {
+            return Err(format!("Already in {}'s channel", login));
+        }
+
+        // Never finishes, so the slot isn't pruned while the bot is set up
+        locked.insert(
+            login.to_string(),
+            tauri::async_runtime::spawn(std::future::pending::<()>()),
+        );
+
+        Ok(Self {
+            handles,
+            login: login.to_string(),
+            filled: false,
+        })
     }
+}
+
+impl Drop for JoinReservation<'_> {
+    fn drop(&mut self) {
+        if self.filled {
+            return;
+        }
+        if let Ok(mut handles) = self.handles.lock() {
+            if let Some(placeholder) = handles.remove(&self.login) {
+                placeholder.abort();
+            }
+        }
+    }
+}
+
+// Bots remove their own handle when they stop; this catches one whose task
+// panicked before it could
+fn prune_finished(handles: &mut HashMap<String, tauri::async_runtime::JoinHandle<()>>) {
+    handles.retain(|_, handle| !handle.inner().is_finished());
+}
+
+#[tauri::command]
+async fn channel_language_stats(
+    bot_state: tauri::State<'_, JoinedChannelState>,
+) -> Result<Vec<bot::LanguageShare>, String> {
+    Ok(bot_state
+        .language_stats
+        .lock()
+        .map_err(|_| "Failed to lock mutex")?
+        .distribution())
+}
+
+/// What became of each joined channel's messages this session, keyed by
+/// channel.
+#[tauri::command]
+async fn get_translation_stats(
+    bot_state: tauri::State<'_, JoinedChannelState>,
+) -> Result<HashMap<String, bot::TranslationStatsSnapshot>, String> {
+    Ok(bot_state
+        .translation_stats
+        .lock()
+        .map_err(|_| "Failed to lock mutex")?
+        .iter()
+        .map(|(channel, stats)| (channel.clone(), stats.snapshot()))
+        .collect())
+}
+
+/// Zeroes the translation stats of one channel, or of all of them.
+#[tauri::command]
+async fn reset_stats(
+    broadcaster_login: Option<String>,
+    bot_state: tauri::State<'_, JoinedChannelState>,
+) -> Result<(), String> {
+    let broadcaster_login = broadcaster_login
+        .as_deref()
+        .map(normalize_login)
+        .transpose()?;
+    let stats = bot_state
+        .translation_stats
+        .lock()
+        .map_err(|_| "Failed to lock mutex")?;
 
-    Ok(false)
+    match broadcaster_login {
+        Some(login) => stats
+            .get(&login)
+            .ok_or_else(|| format!("Bot is not in {}'s channel!", login))?
+            .reset(),
+        None => stats.values().for_each(|stats| stats.reset()),
+    }
+    Ok(())
 }
 
 #[tauri::command]
 async fn join_channel(
     app: tauri::AppHandle,
     broadcaster_login: String,
-    state: tauri::State<'_, TwitchBotState>,
-    bot_state: tauri::State<'_, JoinedChannelState>,
+    force_language: Option<String>,
+) -> Result<(), String> {
+    let force_language = force_language.as_deref().map(parse_language).transpose()?;
+    join(&app, broadcaster_login, force_language).await
+}
+
+#[derive(Clone, Serialize, Debug)]
+struct AutoRejoinFailedPayload {
+    channel: String,
+    error: String,
+}
+
+// Joins the channels the bot was in when the app last closed. A failure,
+// usually an expired token, is reported to the UI rather than stopping
+// startup.
+async fn rejoin_saved_channels(app: tauri::AppHandle, logins: Vec<String>) {
+    for login in logins {
+        if let Err(error) = join(&app, login.clone(), None).await {
+            tracing::warn!("Failed to rejoin {}: {}", login, error);
+            let _ = app.emit(
+                "auto-rejoin-failed",
+                AutoRejoinFailedPayload {
+                    channel: login,
+                    error,
+                },
+            );
+        }
+    }
+}
+
+// Remembers the joined channels so `auto_rejoin` can restore them
+fn save_joined_channels(app: &tauri::AppHandle) -> Result<(), String> {
+    let mut logins: Vec<String> = app
+        .state::<JoinedChannelState>()
+        .join_handles
+        .lock()
+        .map_err(|_| "Failed to lock mutex")?
+        .keys()
+        .cloned()
+        .collect();
+    logins.sort();
+
+    let store = app.store(STORE_PATH).map_err(|err| err.to_string())?;
+    store.set(JOINED_CHANNELS_KEY, logins);
+    store.save().map_err(|err| err.to_string())
+}
+
+async fn join(
+    app: &tauri::AppHandle,
+    broadcaster_login: String,
+    force_language: Option<Language>,
 ) -> Result<(), String> {
+    let broadcaster_login = normalize_login(&broadcaster_login)?;
     tracing::info!("Joining channel {}", &broadcaster_login);
 
+    let state = app.state::<TwitchBotState>();
+    let bot_state = app.state::<JoinedChannelState>();
+
+    let mut reservation = JoinReservation::new(&bot_state.join_handles, &broadcaster_login)?;
+
     // 1. Extract Credentials properly using Locks
+    let refresh_token = state
+        .refresh_token
+        .lock()
+        .map_err(|_| "Lock poisoned")?
+        .clone();
+    let app_secret = state
+        .app_secret
+        .lock()
+        .map_err(|_| "Lock poisoned")?
+        .clone();
     let (_, access_token) = {
         let id_lock = state.client_id.lock().map_err(|_| "Lock poisoned")?;
         let secret_lock = state.client_secret.lock().map_err(|_| "Lock poisoned")?;
@@ -307,67 +1778,470 @@ async fn join_channel(
         .map_err(|e: ReqwestClientDefaultError| e.to_string())?,
     );
 
-    let token: UserToken =
-        UserToken::from_existing(&client, AccessToken::new(access_token), None, None)
+    let token: UserToken = {
+        let (client, access_token) = (&client, &access_token);
+        let (refresh_token, app_secret) = (&refresh_token, &app_secret);
+        // With the refresh token, the bot can renew the login as it expires
+        helix::with_retries("validate the login", || async move {
+            UserToken::from_existing(
+                client,
+                AccessToken::new(access_token.clone()),
+                refresh_token.clone().map(RefreshToken::new),
+                app_secret.clone().map(ClientSecret::new),
+            )
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(helix::validation_error)
+        })
+        .await?
+    };
+
+    if !token.scopes().contains(&Scope::UserReadChat) {
+        return Err(
+            "This login is missing the user:read:chat scope needed to read chat. Please log in again."
+                .to_string(),
+        );
+    }
+
+    let can_reply = token.scopes().contains(&Scope::UserWriteChat);
+    if !can_reply {
+        tracing::warn!("Login lacks user:write:chat, translations won't be posted to chat");
+    }
 
     // We need to know the numeric ID of the channel we want to join
     let broadcaster_username: twitch_api::types::UserName =
         broadcaster_login
             .as_str()
             .try_into()
-            .map_err(|_| "Invalid broadcaster username")?;
+            .map_err(|_| invalid_login_message(&broadcaster_login))?;
 
-    let user = client
-        .get_user_from_login(&broadcaster_username, &token)
-        .await
-        .map_err(|e| e.to_string())?
-        .ok_or("Broadcaster not found")?;
+    let user = {
+        let (client, username, token) = (&client, &broadcaster_username, &token);
+        helix::with_retries("look up the channel", || async move {
+            client
+                .get_user_from_login(username, token)
+                .await
+                .map_err(helix::request_error)
+        })
+        .await?
+    }
+    .ok_or_else(|| format!("No Twitch channel is named '{}'", broadcaster_login))?;
 
     let broadcaster_id = user.id;
 
+    let third_party_emotes = if settings::current(&app).third_party_emotes {
+        let cached = bot_state
+            .emote_cache
+            .lock()
+            .map_err(|_| "Failed to lock mutex")?
+            .get(broadcaster_id.as_str())
+            .cloned();
+
+        match cached {
+            Some(emotes) => emotes,
+            None => {
+                let emotes = Arc::new(emotes::fetch_channel_emotes(broadcaster_id.as_str()).await);
+                bot_state
+                    .emote_cache
+                    .lock()
+                    .map_err(|_| "Failed to lock mutex")?
+                    .insert(broadcaster_id.to_string(), emotes.clone());
+                emotes
+            }
+        }
+    } else {
+        Arc::new(emotes::EmoteSet::new())
+    };
+
+    // Stats describe the current session, so start fresh when joining the
+    // first channel, whose reserved slot is the only one
+    if bot_state
+        .join_handles
+        .lock()
+        .map_err(|_| "Failed to lock mutex")?
+        .len()
+        == 1
+    {
+        bot_state
+            .language_stats
+            .lock()
+            .map_err(|_| "Failed to lock mutex")?
+            .clear();
+    }
+
     let bot = bot::Bot {
         app_handle: app.clone(),
         client,
         token: Arc::new(tokio::sync::Mutex::new(token)),
         broadcaster: broadcaster_id,
+        broadcaster_login: broadcaster_login.clone(),
+        language_history: Arc::new(Mutex::new(bot::LanguageHistory::new())),
+        third_party_emotes,
+        can_reply,
+        reply_history: Arc::new(Mutex::new(bot::ReplyHistory::new())),
+        reply_order: Arc::new(Mutex::new(bot::ReplyOrder::default())),
+        debouncer: Arc::new(Mutex::new(bot::MessageDebouncer::default())),
+        reply_limiter: bot_state.reply_limiter.clone(),
+        force_language: Arc::new(Mutex::new(force_language)),
+        reconnect_attempts: Arc::new(AtomicU32::new(0)),
+        stats: Arc::new(bot::TranslationStats::default()),
     };
-
-    // We must spawn this because bot.start() is an infinite loop
-    *bot_state
-        .join_handle
+    bot_state
+        .forced_languages
         .lock()
-        .map_err(|_| "Failed to lock mutex")? = Some(tauri::async_runtime::spawn(async move {
-        println!("Bot starting background task...");
-        if let Err(e) = bot.start().await {
-            eprintln!("Bot crashed: {}", e);
-        }
-    }));
+        .map_err(|_| "Failed to lock mutex")?
+        .insert(broadcaster_login.clone(), bot.force_language.clone());
+    bot_state
+        .translation_stats
+        .lock()
+        .map_err(|_| "Failed to lock mutex")?
+        .insert(broadcaster_login.clone(), bot.stats.clone());
+
+    {
+        // The lock is held until the handle is stored, so a bot that stops
+        // straight away can't look for its handle before it's there
+        let mut join_handles = bot_state
+            .join_handles
+            .lock()
+            .map_err(|_| "Failed to lock mutex")?;
+
+        // Leaving the channel while it was being joined gave up the slot
+        reservation.filled = true;
+        let Some(placeholder) = join_handles.remove(&broadcaster_login) else {
+            return Err(format!(
+                "Left {}'s channel while joining it",
+                broadcaster_login
+            ));
+        };
+        placeholder.abort();
+
+        // We must spawn this because bot.start() is an infinite loop
+        let task_app = app.clone();
+        let login = broadcaster_login.clone();
+        let handle = tauri::async_runtime::spawn(async move {
+            println!("Bot starting background task...");
+            let result = bot.run().await;
+
+            // However it stopped, the bot is no longer in the channel
+            let channel_state = task_app.state::<JoinedChannelState>();
+            if let Ok(mut join_handles) = channel_state.join_handles.lock() {
+                join_handles.remove(&login);
+            }
+            if let Ok(mut forced_languages) = channel_state.forced_languages.lock() {
+                forced_languages.remove(&login);
+            }
+            if let Err(e) = save_joined_channels(&task_app) {
+                tracing::warn!("Failed to save the joined channels: {}", e);
+            }
+
+            if let Err(e) = result {
+                eprintln!("Bot crashed: {}", e);
+
+                // Only logging in again brings it back
+                if e.downcast_ref::<bot::AuthExpired>().is_some() {
+                    let _ = task_app.emit(
+                        "auth-expired",
+                        bot::AuthExpiredPayload {
+                            channel: login,
+                            error: e.root_cause().to_string(),
+                        },
+                    );
+                }
+            }
+        });
+        join_handles.insert(broadcaster_login.clone(), handle);
+    }
 
     tracing::info!("Joined channel {}", &broadcaster_login);
 
-    Ok(())
+    save_joined_channels(app)
 }
 
+/// Treats every message in `broadcaster_login`'s channel as `language`
+/// instead of detecting it, or goes back to detection when it's `None`.
 #[tauri::command]
-async fn leave_channel(bot_state: tauri::State<'_, JoinedChannelState>) -> Result<(), String> {
-    tracing::info!("Leaving channel");
+async fn set_channel_language(
+    broadcaster_login: String,
+    language: Option<String>,
+    bot_state: tauri::State<'_, JoinedChannelState>,
+) -> Result<(), String> {
+    let broadcaster_login = normalize_login(&broadcaster_login)?;
+    let language = language.as_deref().map(parse_language).transpose()?;
+
+    if !bot_state
+        .join_handles
+        .lock()
+        .map_err(|_| "Failed to lock mutex")?
+        .contains_key(&broadcaster_login)
+    {
+        return Err(format!("Bot is not in {}'s channel!", broadcaster_login));
+    }
+
+    let forced_languages = bot_state
+        .forced_languages
+        .lock()
+        .map_err(|_| "Failed to lock mutex")?;
+    let forced = forced_languages
+        .get(&broadcaster_login)
+        .ok_or_else(|| format!("Bot is not in {}'s channel!", broadcaster_login))?;
+    *forced.lock().map_err(|_| "Failed to lock mutex")? = language;
+
+    Ok(())
+}
+
+fn parse_language(name: &str) -> Result<Language, String> {
+    name.parse()
+        .map_err(|_| format!("Unknown language '{}'", name))
+}
+
+// Channel names are often pasted as "@Streamer " or copied from a display
+// name, so they're cleaned up to the lowercase login Twitch expects
+fn normalize_login(input: &str) -> Result<String, String> {
+    let login = input.trim();
+    let login = login.strip_prefix('@').unwrap_or(login).to_lowercase();
+
+    if login.is_empty() {
+        return Err("Enter the name of the channel to join".to_string());
+    }
+    if login.len() > 25 || !login.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(invalid_login_message(&login));
+    }
+
+    Ok(login)
+}
+
+fn invalid_login_message(login: &str) -> String {
+    format!(
+        "'{}' isn't a Twitch channel name. Names are up to 25 letters, numbers and underscores.",
+        login
+    )
+}
 
-    let maybe_handle = {
+/// Leaves `broadcaster_login`'s channel, or every channel when no login is
+/// given. Other channels keep running.
+#[tauri::command]
+async fn leave_channel(
+    app: tauri::AppHandle,
+    broadcaster_login: Option<String>,
+    bot_state: tauri::State<'_, JoinedChannelState>,
+) -> Result<(), String> {
+    let broadcaster_login = broadcaster_login
+        .as_deref()
+        .map(normalize_login)
+        .transpose()?;
+    let handles: Vec<_> = {
         let mut guard = bot_state
-            .join_handle
+            .join_handles
             .lock()
             .map_err(|_| "Failed to lock mutex")?;
 
-        guard.take()
+        match &broadcaster_login {
+            Some(login) => guard.remove(login).into_iter().collect(),
+            None => guard.drain().map(|(_, handle)| handle).collect(),
+        }
     };
 
-    if let Some(handle) = maybe_handle {
+    if handles.is_empty() {
+        return Err(match broadcaster_login {
+            Some(login) => format!("Bot is not in {}'s channel!", login),
+            None => "Bot is currently not in any channel!".to_string(),
+        });
+    }
+
+    for handle in handles {
         handle.abort();
-        tracing::info!("Left channel");
-        Ok(())
-    } else {
-        Err("Bot is currently not in any channel!".to_string())
+    }
+    {
+        // A channel's stats only cover the session it was joined for
+        let mut stats = bot_state
+            .translation_stats
+            .lock()
+            .map_err(|_| "Failed to lock mutex")?;
+        match &broadcaster_login {
+            Some(login) => {
+                stats.remove(login);
+            }
+            None => stats.clear(),
+        }
+    }
+    {
+        let mut forced_languages = bot_state
+            .forced_languages
+            .lock()
+            .map_err(|_| "Failed to lock mutex")?;
+        match &broadcaster_login {
+            Some(login) => {
+                forced_languages.remove(login);
+            }
+            None => forced_languages.clear(),
+        }
+    }
+    tracing::info!(
+        "Left {}",
+        broadcaster_login.as_deref().unwrap_or("all channels")
+    );
+
+    save_joined_channels(&app)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_channel_is_only_reserved_once() {
+        let handles = Mutex::new(HashMap::new());
+
+        let reservation = JoinReservation::new(&handles, "streamer").unwrap();
+        assert!(JoinReservation::new(&handles, "streamer").is_err());
+        assert!(JoinReservation::new(&handles, "other").is_ok());
+
+        // A failed join gives the slot back
+        drop(reservation);
+        assert!(!handles.lock().unwrap().contains_key("streamer"));
+        assert!(JoinReservation::new(&handles, "streamer").is_ok());
+    }
+
+    #[tokio::test]
+    async fn concurrent_refreshes_spend_the_refresh_token_once() {
+        let refreshing = tokio::sync::Mutex::new(());
+        let saved = Mutex::new(Some("old".to_string()));
+        let refreshes = AtomicUsize::new(0);
+        let (refreshing, saved, refreshes) = (&refreshing, &saved, &refreshes);
+
+        // Five bots whose tokens expire together; the mock refresh yields so
+        // the others pile up behind it
+        let bots = (0..5).map(|_| async move {
+            refresh_once(refreshing, saved, "old", |newer| async move {
+                match newer {
+                    Some(newer) => assert_eq!(newer, "new"),
+                    None => {
+                        refreshes.fetch_add(1, Ordering::SeqCst);
+                        tokio::task::yield_now().await;
+                        *saved.lock().unwrap() = Some("new".to_string());
+                    }
+                }
+                Ok(())
+            })
+            .await
+        });
+        let results = futures::future::join_all(bots).await;
+
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+    }
+
+    // Answers Twitch's token and validate endpoints like a real login
+    // would, counting the refreshes
+    fn mock_oauth_server(refreshes: Arc<AtomicUsize>) -> String {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/oauth2/", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let response = if request_line.starts_with("POST /oauth2/token") {
+                    refreshes.fetch_add(1, Ordering::SeqCst);
+                    r#"{"access_token":"new-access","refresh_token":"new-refresh","expires_in":14400,"scope":["user:read:chat"],"token_type":"bearer"}"#
+                } else {
+                    r#"{"client_id":"client-id","login":"bot","user_id":"1","expires_in":14400,"scopes":["user:read:chat"]}"#
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                );
+            }
+        });
+
+        url
+    }
+
+    #[tokio::test]
+    async fn a_refreshed_login_is_saved_and_shared() {
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        // The OAuth URLs are read once, so this must be set before any
+        // request goes out
+        std::env::set_var("TWITCH_OAUTH2_URL", mock_oauth_server(refreshes.clone()));
+
+        let state = TwitchBotState {
+            client_id: Mutex::new(Some("client-id".to_string())),
+            client_secret: Mutex::new(Some("old-access".to_string())),
+            refresh_token: Mutex::new(Some("old-refresh".to_string())),
+            app_secret: Mutex::new(None),
+            refreshing: tokio::sync::Mutex::new(()),
+        };
+        let client: HelixClient<'static, reqwest::Client> =
+            HelixClient::with_client(reqwest::Client::new());
+        let expired = || {
+            UserToken::from_existing_unchecked(
+                AccessToken::new("old-access".to_string()),
+                RefreshToken::new("old-refresh".to_string()),
+                ClientId::new("client-id".to_string()),
+                None::<ClientSecret>,
+                "bot".into(),
+                "1".into(),
+                Some(vec![Scope::UserReadChat]),
+                None,
+            )
+        };
+        // Stands in for the credential store, doing what `save_login` does
+        let persisted = Mutex::new(None);
+        let save = |token: &UserToken| {
+            let refresh_token = token
+                .refresh_token
+                .as_ref()
+                .map(|refresh_token| refresh_token.secret().to_string());
+            *state.client_secret.lock().unwrap() = Some(token.access_token.secret().to_string());
+            *state.refresh_token.lock().unwrap() = refresh_token.clone();
+            *persisted.lock().unwrap() = refresh_token;
+            Ok(())
+        };
+
+        let mut first = expired();
+        refresh_saved_login(&state, &client, &mut first, save)
+            .await
+            .unwrap();
+
+        assert_eq!(first.access_token.secret(), "new-access");
+        assert_eq!(persisted.lock().unwrap().as_deref(), Some("new-refresh"));
+
+        // Another channel's copy of the old login picks up the saved one
+        // instead of spending the refresh token again
+        let mut second = expired();
+        refresh_saved_login(&state, &client, &mut second, |_| {
+            panic!("the login was already saved")
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(second.access_token.secret(), "new-access");
+        assert_eq!(
+            second.refresh_token.as_ref().map(|token| token.secret()),
+            Some("new-refresh")
+        );
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
     }
 }
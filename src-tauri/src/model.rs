@@ -1,11 +1,23 @@
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::Context;
 use anyhow::Result;
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::hash::{BuildHasher, Hasher};
 use std::num::NonZeroU32;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant};
 
 use lingua::{Language, LanguageDetector, LanguageDetectorBuilder};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::Instrument;
 
 use llama_cpp_2::context::params::LlamaContextParams;
 use llama_cpp_2::context::LlamaContext;
@@ -13,17 +25,268 @@ use llama_cpp_2::llama_backend::LlamaBackend;
 use llama_cpp_2::llama_batch::LlamaBatch;
 use llama_cpp_2::model::params::LlamaModelParams;
 use llama_cpp_2::model::{AddBos, LlamaModel, Special};
+use llama_cpp_2::sampling::LlamaSampler;
+use llama_cpp_2::token::LlamaToken;
+use llama_cpp_2::DecodeError;
 
 use tauri::path::BaseDirectory;
-use tauri::Manager;
+use tauri::{Emitter, Manager};
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
 
+use crate::bot::MAX_CHAT_MESSAGE_CHARS;
+use crate::cache;
+use crate::custom_slang;
+use crate::download;
+use crate::gguf;
+use crate::logging;
+use crate::m2m100::M2m100Engine;
+use crate::protect::ProtectedText;
+use crate::quality;
+use crate::remote::RemoteBackend;
+use crate::settings::{self, Settings};
+use crate::slang_en;
+use crate::slang_es;
 use crate::slang_fr;
 use crate::slang_jp;
+use crate::slang_ko;
+use crate::slang_vi;
 use crate::slang_zh;
+use crate::toxicity;
+use crate::RefiningModelState;
 use crate::TranslationModelState;
 use crate::TranslationResponse;
 
-const QWEN_MODEL_NAME: &str = "Qwen3-1.7B-Q8_0.gguf";
+// llama.cpp caps the offloaded layers at the model's layer count, so this
+// means "all of them"
+const ALL_GPU_LAYERS: u32 = 999;
+// VRAM left over after the weights, for the KV caches and compute buffers
+const VRAM_HEADROOM_BYTES: u64 = 1 << 30;
+// The bundled model, which is also the one offered for download
+const QWEN_MODEL_NAME: &str = download::DEFAULT_MODEL.filename;
+// The Q8_0 quant is about 1.8 GB; anything far smaller is most likely a
+// truncated download.
+const QWEN_MODEL_MIN_BYTES: u64 = 1_500_000_000;
+// Smaller than any model worth translating with, whatever it is
+const MODEL_MIN_BYTES: u64 = 5_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sentiment {
+    Positive,
+    Neutral,
+    Negative,
+}
+
+/// Which way a message is translated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// Viewer chat in any language into English.
+    ToEnglish,
+    /// The streamer's English into the given language.
+    FromEnglish(Language),
+}
+
+// Below this many characters lingua has little to go on, so a known
+// chatter's usual language can override a low-confidence guess.
+const SHORT_MESSAGE_CHARS: usize = 24;
+const UNCERTAIN_CONFIDENCE: f64 = 0.6;
+
+/// Error returned when a translation gave up waiting for a context.
+pub const TRANSLATOR_BUSY: &str = "Translator busy";
+/// Error returned when a translation was dropped to make room for newer ones.
+pub const TRANSLATION_SHED: &str = "Translation shed under load";
+/// Error returned when inference ran past `translation_timeout_ms`.
+pub const TRANSLATION_TIMED_OUT: &str = "Translation timed out";
+
+thread_local! {
+    // When the inference running on this blocking thread has to give up
+    static INFERENCE_DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// What happens to a translation when every context is busy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverloadPolicy {
+    /// Wait as long as it takes.
+    Wait,
+    /// Give up with `TRANSLATOR_BUSY` after `overload_timeout_ms`.
+    Timeout,
+    /// Keep at most `max_queued_translations` waiting, dropping the oldest
+    /// with `TRANSLATION_SHED`.
+    ShedOldest,
+}
+
+const DEFAULT_MAX_NEW_TOKENS: usize = 256;
+
+/// Why generation stopped, reported with `debug`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// The model finished on its own.
+    EndOfText,
+    /// It hit `max_new_tokens`.
+    MaxTokens,
+    /// It ran out of context.
+    ContextFull,
+    /// It got stuck repeating itself.
+    Repetition,
+}
+
+/// How the next token is picked while translating.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplingParams {
+    /// 0.0 always takes the most likely token (greedy, reproducible).
+    pub temperature: f32,
+    pub top_p: f32,
+    pub top_k: usize,
+    /// Fixes the sampling for reproducible output; 0 picks a fresh random
+    /// seed for every translation.
+    pub seed: u64,
+    /// Most tokens a translation may run to. Chat lines are short, so
+    /// anything past this is the model rambling, but reasoning counts too:
+    /// raise it when `thinking` is on.
+    pub max_new_tokens: usize,
+}
+
+impl SamplingParams {
+    /// Plain argmax decoding, for answers that must be stable.
+    pub const GREEDY: SamplingParams = SamplingParams {
+        temperature: 0.0,
+        top_p: 1.0,
+        top_k: 0,
+        seed: 0,
+        max_new_tokens: DEFAULT_MAX_NEW_TOKENS,
+    };
+
+    fn sampler(&self) -> Option<LlamaSampler> {
+        if self.temperature <= 0.0 {
+            return None;
+        }
+
+        Some(LlamaSampler::chain_simple([
+            LlamaSampler::top_k(self.top_k as i32),
+            LlamaSampler::top_p(self.top_p, 1),
+            LlamaSampler::temp(self.temperature),
+            LlamaSampler::dist(fold_seed(self.seed)),
+        ]))
+    }
+}
+
+// llama.cpp takes a 32-bit seed, so the high half is folded into the low one
+// rather than cut off
+fn fold_seed(seed: u64) -> u32 {
+    let seed = if seed == 0 {
+        RandomState::new().build_hasher().finish()
+    } else {
+        seed
+    };
+    (seed ^ (seed >> 32)) as u32
+}
+
+impl Default for SamplingParams {
+    fn default() -> Self {
+        Self {
+            temperature: 0.7,
+            top_p: 0.9,
+            top_k: 40,
+            seed: 0,
+            max_new_tokens: DEFAULT_MAX_NEW_TOKENS,
+        }
+    }
+}
+
+/// Which model translates viewer chat.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Engine {
+    Qwen,
+    /// Faster but more literal; what the slang normalizers were written for.
+    /// Falls back to Qwen when not available.
+    M2m100,
+}
+
+/// Where chat translations are generated. The slang preprocessing is the
+/// same either way.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Backend {
+    /// The bundled or configured GGUF model, run with llama.cpp.
+    Local,
+    /// An OpenAI-style chat completions API. `base_url` includes the version,
+    /// e.g. "https://api.openai.com/v1". `api_key` is kept in the OS
+    /// credential store and comes back empty from `get_settings`.
+    OpenAiCompatible {
+        base_url: String,
+        #[serde(default)]
+        api_key: String,
+        model: String,
+    },
+}
+
+/// How long each stage of a chat translation took, in milliseconds. Stages
+/// that didn't run, e.g. inference for a cached message, are `None`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StageTimings {
+    pub universal_slang_ms: Option<f64>,
+    pub detection_ms: Option<f64>,
+    pub normalization_ms: Option<f64>,
+    pub inference_ms: Option<f64>,
+}
+
+fn elapsed_ms(since: Instant) -> Option<f64> {
+    Some(since.elapsed().as_secs_f64() * 1000.0)
+}
+
+/// How urgently a translation needs a context.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    Normal,
+    /// Broadcaster and moderator messages: served before normal ones and
+    /// never shed or timed out.
+    High,
+}
+
+// After this many decode failures in a row we assume the GPU can't fit the
+// current concurrency and run one fewer translation at a time.
+const DECODE_FAILURES_BEFORE_BACKOFF: usize = 3;
+
+// Fast mode kicks in once at least this many translations have been waiting
+// continuously for `FAST_MODE_AFTER`, and ends when the queue is empty.
+const FAST_MODE_QUEUE_DEPTH: usize = 3;
+const FAST_MODE_AFTER: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Serialize, Debug)]
+pub struct FastModePayload {
+    pub enabled: bool,
+}
+
+// Once this many translations are waiting for a context the UI is told the
+// translator is busy, and again when the queue drains below it.
+const QUEUE_BUSY_DEPTH: usize = 5;
+
+#[derive(Clone, Serialize, Debug)]
+pub struct TranslationStatus {
+    pub in_flight: usize,
+    pub queued: usize,
+    pub available_permits: usize,
+    pub concurrency_limit: usize,
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct TranslationQueuePayload {
+    pub busy: bool,
+    pub queued: usize,
+}
+
+/// Receives each piece of a translation as the model decodes it.
+pub type TokenCallback = Box<dyn FnMut(&str) + Send>;
+
+// Context window every llama context is created with, in tokens.
+const CONTEXT_TOKENS: u32 = 2048;
+// Messages longer than this are cut, leaving the rest of the context for the
+// prompt around them and the answer.
+const MAX_MESSAGE_TOKENS: usize = 1024;
 
 // --- WRAPPER FOR THREAD SAFETY ---
 // We wrap LlamaContext to implement Send + Sync manually.
@@ -34,20 +297,185 @@ unsafe impl Send for ThreadSafeContext {}
 unsafe impl Sync for ThreadSafeContext {}
 // ---------------------------------
 
-pub fn initialize_lingua() -> LanguageDetector {
-    let languages = vec![
-        Language::English,
-        Language::French,
-        Language::Japanese,
-        Language::Chinese,
-    ];
-    LanguageDetectorBuilder::from_languages(&languages)
+/// Something kept in a context pool, wiped before it goes back.
+pub trait PoolItem {
+    fn reset(&mut self);
+}
+
+impl PoolItem for ThreadSafeContext {
+    fn reset(&mut self) {
+        self.0.clear_kv_cache();
+    }
+}
+
+/// A context borrowed from a pool. It goes back, with its KV cache wiped,
+/// when dropped, so an error or panic mid-inference can't leak it.
+pub struct PooledContext<'a, C: PoolItem = ThreadSafeContext> {
+    pool: &'a Mutex<Vec<C>>,
+    ctx: Option<C>,
+}
+
+impl<'a, C: PoolItem> PooledContext<'a, C> {
+    /// Takes a context out of `pool`. Callers hold a semaphore permit, and
+    /// there are never more permits than contexts, so the pool can only be
+    /// empty if that invariant is broken; that fails this translation rather
+    /// than panicking the worker.
+    pub fn take(pool: &'a Mutex<Vec<C>>) -> Result<Self> {
+        let ctx = pool
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Poisoned lock"))?
+            .pop()
+            .context("No free context in the pool despite holding a permit")?;
+
+        Ok(Self {
+            pool,
+            ctx: Some(ctx),
+        })
+    }
+}
+
+impl<C: PoolItem> Deref for PooledContext<'_, C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        self.ctx.as_ref().expect("context already returned")
+    }
+}
+
+impl<C: PoolItem> DerefMut for PooledContext<'_, C> {
+    fn deref_mut(&mut self) -> &mut C {
+        self.ctx.as_mut().expect("context already returned")
+    }
+}
+
+impl<C: PoolItem> Drop for PooledContext<'_, C> {
+    fn drop(&mut self) {
+        if let Some(mut ctx) = self.ctx.take() {
+            ctx.reset();
+            // Even a poisoned pool still holds valid contexts
+            self.pool
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .push(ctx);
+        }
+    }
+}
+
+/// Languages detected unless `enabled_languages` says otherwise.
+pub const DEFAULT_LANGUAGES: &[Language] = &[
+    Language::English,
+    Language::French,
+    Language::Japanese,
+    Language::Chinese,
+    Language::Korean,
+    Language::Spanish,
+    Language::Vietnamese,
+];
+
+/// Builds a detector that only tells `languages` apart, loading just their
+/// models. lingua needs at least two to choose between, so fewer falls back
+/// to `DEFAULT_LANGUAGES`.
+pub fn initialize_lingua(languages: &[Language]) -> LanguageDetector {
+    let languages = if languages.len() < 2 {
+        tracing::warn!("Fewer than two languages enabled, detecting the default ones");
+        DEFAULT_LANGUAGES
+    } else {
+        languages
+    };
+
+    LanguageDetectorBuilder::from_languages(languages)
         .with_preloaded_language_models()
         .build()
 }
 
+// Even a poisoned lock still holds a working detector
+fn read_detector(state: &TranslationModelState) -> RwLockReadGuard<'_, LanguageDetector> {
+    state
+        .detector
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
 pub fn initialize_llama_backend() -> Result<LlamaBackend> {
-    Ok(LlamaBackend::init()?)
+    let backend = LlamaBackend::init()?;
+
+    // A GPU backend being compiled in doesn't mean this machine has a
+    // device (or driver) for it, so say what llama.cpp actually found
+    for device in llama_cpp_2::list_llama_ggml_backend_devices() {
+        tracing::info!(
+            "llama.cpp device {}: {} ({}, {} MiB free)",
+            device.backend,
+            device.name,
+            device.description,
+            device.memory_free / (1024 * 1024)
+        );
+    }
+    match gpu_device() {
+        Some(device) => tracing::info!("Using {} for GPU offload", device),
+        None if backend.supports_gpu_offload() => {
+            tracing::warn!("llama.cpp was built with GPU support but found no GPU; using the CPU")
+        }
+        None => tracing::info!("llama.cpp was built without GPU support; using the CPU"),
+    }
+
+    Ok(backend)
+}
+
+/// The GPU llama.cpp offloads to, as "backend (description)", e.g.
+/// "Vulkan (NVIDIA GeForce RTX 3060)". `None` when only the CPU is
+/// available.
+pub fn gpu_device() -> Option<String> {
+    llama_cpp_2::list_llama_ggml_backend_devices()
+        .into_iter()
+        .find(|device| !device.backend.eq_ignore_ascii_case("CPU"))
+        .map(|device| format!("{} ({})", device.backend, device.description))
+}
+
+// Free memory on the GPU from `gpu_device`
+fn gpu_free_memory() -> Option<u64> {
+    llama_cpp_2::list_llama_ggml_backend_devices()
+        .into_iter()
+        .find(|device| !device.backend.eq_ignore_ascii_case("CPU"))
+        .map(|device| device.memory_free as u64)
+}
+
+// How many of a model's layers fit in `free_bytes` of VRAM, going by the
+// file size. The output layer counts as one more block.
+fn estimate_gpu_layers(free_bytes: u64, model_bytes: u64, block_count: u32) -> u32 {
+    let layers = u64::from(block_count) + 1;
+    let per_layer = model_bytes.div_ceil(layers).max(1);
+    let fitting = free_bytes.saturating_sub(VRAM_HEADROOM_BYTES) / per_layer;
+
+    if fitting >= layers {
+        ALL_GPU_LAYERS
+    } else {
+        fitting as u32
+    }
+}
+
+// The layers to offload when the user hasn't picked a number: as many as
+// fit in free VRAM, or all of them when that can't be measured
+fn auto_gpu_layers(model_path: &Path) -> u32 {
+    let block_count = gguf::read_info(model_path)
+        .ok()
+        .and_then(|info| info.block_count);
+    let model_bytes = std::fs::metadata(model_path).map(|metadata| metadata.len());
+    let (Some(free_bytes), Some(block_count), Ok(model_bytes)) =
+        (gpu_free_memory(), block_count, model_bytes)
+    else {
+        tracing::info!("Couldn't measure free VRAM against the model; offloading every layer");
+        return ALL_GPU_LAYERS;
+    };
+
+    let layers = estimate_gpu_layers(free_bytes, model_bytes, block_count);
+    tracing::info!(
+        "{} MiB of VRAM free for a {} MiB model: offloading {} of {} layers",
+        free_bytes / (1024 * 1024),
+        model_bytes / (1024 * 1024),
+        layers.min(block_count + 1),
+        block_count + 1
+    );
+    layers
 }
 
 // We use unsafe to extend the lifetime to 'static because we know
@@ -56,10 +484,31 @@ pub fn initialize_llama_context(
     backend: &LlamaBackend,
     model: &LlamaModel,
 ) -> Result<ThreadSafeContext> {
+    new_context(backend, model, 1)
+}
+
+/// A context holding `sequences` messages at once for `generate_batch`,
+/// each with a full `CONTEXT_TOKENS` window.
+pub fn initialize_batch_context(
+    backend: &LlamaBackend,
+    model: &LlamaModel,
+    sequences: usize,
+) -> Result<ThreadSafeContext> {
+    new_context(backend, model, sequences as u32)
+}
+
+fn new_context(
+    backend: &LlamaBackend,
+    model: &LlamaModel,
+    sequences: u32,
+) -> Result<ThreadSafeContext> {
+    // llama.cpp splits the window evenly between sequences
+    let n_ctx = CONTEXT_TOKENS * sequences;
     let ctx_params = LlamaContextParams::default()
-        .with_n_ctx(Some(NonZeroU32::new(2048).unwrap()))
-        .with_n_batch(2048)
-        .with_n_ubatch(2048)
+        .with_n_ctx(NonZeroU32::new(n_ctx))
+        .with_n_batch(n_ctx)
+        .with_n_ubatch(CONTEXT_TOKENS)
+        .with_n_seq_max(sequences)
         .with_n_threads(4)
         .with_n_threads_batch(4);
 
@@ -82,8 +531,8 @@ pub fn initialize_llama_context(
 pub fn initialize_llm_from_app_handle(
     app_handle: &tauri::AppHandle,
     backend: &LlamaBackend,
-) -> Result<LlamaModel> {
-    println!("DEBUG: Initializing LLM using FLATPAK logic");
+) -> Result<(LlamaModel, ModelInfo)> {
+    tracing::debug!("Initializing LLM using FLATPAK logic");
 
     // 1. Get the path of the actual running binary inside Flatpak (/app/bin/start-bot)
     let exe_path = env::current_exe().context("Failed to get current exe path")?;
@@ -92,19 +541,17 @@ pub fn initialize_llm_from_app_handle(
     let exe_dir = exe_path.parent().context("Failed to get exe parent dir")?;
 
     // 3. Manually construct the path to the model (/app/bin/model/Qwen...)
-    let model_path = exe_dir.join("model").join(QWEN_MODEL_NAME);
+    let model_path = exe_dir.join("model").join(model_filename(app_handle));
 
-    println!("DEBUG: Looking for model at: {:?}", model_path);
+    tracing::debug!("Looking for model at: {:?}", model_path);
 
     if !model_path.exists() {
         return Err(anyhow::anyhow!("Model file not found at: {:?}", model_path));
     }
 
-    let params = LlamaModelParams::default().with_n_gpu_layers(999);
-    let model = LlamaModel::load_from_file(backend, &model_path, &params)
-        .context("Failed to load Qwen model from file")?;
-
-    Ok(model)
+    let gpu_layers = settings::current(app_handle).gpu_layers;
+    load_model_from_file(backend, &model_path, gpu_layers)
+        .context("Failed to load Qwen model from file")
 }
 
 // ---------------------------------------------------------------------------
@@ -114,69 +561,168 @@ pub fn initialize_llm_from_app_handle(
 pub fn initialize_llm_from_app_handle(
     app_handle: &tauri::AppHandle,
     backend: &LlamaBackend,
-) -> Result<LlamaModel> {
-    println!("DEBUG: Initializing LLM using STANDARD TAURI logic");
+) -> Result<(LlamaModel, ModelInfo)> {
+    tracing::debug!("Initializing LLM using STANDARD TAURI logic");
 
     let model_path = app_handle
         .path()
-        .resolve(
-            format!("model/{}", QWEN_MODEL_NAME),
-            BaseDirectory::Resource,
-        )
-        .context("Failed to resolve path to Qwen model")?;
+        .resolve("model", BaseDirectory::Resource)
+        .context("Failed to resolve path to the model folder")?
+        .join(model_filename(app_handle));
 
-    let params = LlamaModelParams::default().with_n_gpu_layers(999);
-    let model = LlamaModel::load_from_file(backend, &model_path, &params)
-        .context("Failed to load Qwen model from file")?;
+    tracing::debug!("Looking for model at: {:?}", model_path);
 
-    Ok(model)
+    if !model_path.exists() {
+        return Err(anyhow::anyhow!("Model file not found at: {:?}", model_path));
+    }
+
+    let gpu_layers = settings::current(app_handle).gpu_layers;
+    load_model_from_file(backend, &model_path, gpu_layers)
+        .context("Failed to load Qwen model from file")
 }
 
-pub fn localize_with_qwen(
-    model: &LlamaModel,
-    wrapped_ctx: &mut ThreadSafeContext, // Accept the wrapper
-    source_lang: &str,
-    raw_text: &str,
-) -> Result<String> {
-    let ctx = &mut wrapped_ctx.0; // Access internal context
+/// The model file to load: the `model_filename` setting, or the bundled Qwen
+/// model when it's unset.
+pub fn model_filename(app_handle: &tauri::AppHandle) -> String {
+    settings::current(app_handle)
+        .model_filename
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| QWEN_MODEL_NAME.to_string())
+}
+
+/// Where a loaded model came from and how it was loaded.
+#[derive(Clone, Serialize, Debug)]
+pub struct ModelInfo {
+    pub path: PathBuf,
+    /// Layers offloaded to the GPU; 0 when it runs on the CPU. Offloading
+    /// "all" layers is requested as `ALL_GPU_LAYERS`.
+    pub gpu_layers: u32,
+    /// What the model runs on: the GPU from `gpu_device`, or "CPU".
+    pub backend: String,
+}
+
+/// Loads any GGUF model with `gpu_layers` offloaded to the GPU, or as many
+/// layers as fit in free VRAM when that's `None` and llama.cpp found a GPU to
+/// use. If offloading fails, e.g. for lack of VRAM or a broken driver, the
+/// model is loaded on the CPU.
+pub fn load_model_from_file(
+    backend: &LlamaBackend,
+    model_path: &Path,
+    gpu_layers: Option<u32>,
+) -> Result<(LlamaModel, ModelInfo)> {
+    warn_if_truncated(model_path);
 
+    let device = gpu_device().filter(|_| backend.supports_gpu_offload());
+    let gpu_layers = match (gpu_layers, &device) {
+        (Some(gpu_layers), _) => gpu_layers,
+        (None, Some(_)) => auto_gpu_layers(model_path),
+        (None, None) => 0,
+    };
+    tracing::info!("Loading {:?} with {} GPU layers", model_path, gpu_layers);
+
+    let params = LlamaModelParams::default().with_n_gpu_layers(gpu_layers);
+    let (model, gpu_layers) = match LlamaModel::load_from_file(backend, model_path, &params) {
+        Ok(model) => (model, gpu_layers),
+        Err(e) if gpu_layers > 0 => {
+            tracing::warn!(
+                "Failed to load {:?} with {} GPU layers ({}); retrying on the CPU",
+                model_path,
+                gpu_layers,
+                e
+            );
+            let params = LlamaModelParams::default().with_n_gpu_layers(0);
+            let model = LlamaModel::load_from_file(backend, model_path, &params)
+                .with_context(|| format!("Failed to load model from {:?}", model_path))?;
+            (model, 0)
+        }
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to load model from {:?}", model_path))
+        }
+    };
+
+    Ok((
+        model,
+        ModelInfo {
+            path: model_path.to_path_buf(),
+            gpu_layers,
+            backend: match device {
+                Some(device) if gpu_layers > 0 => device,
+                _ => "CPU".to_string(),
+            },
+        },
+    ))
+}
+
+// A truncated GGUF often still loads and then produces garbage, so flag it
+// before anyone has to debug the translations
+fn warn_if_truncated(model_path: &Path) {
+    if let Some(problem) = truncation_problem(model_path) {
+        tracing::warn!(
+            "Model file {:?} {}. It may be a partial download; translations will likely fail.",
+            model_path,
+            problem
+        );
+    }
+}
+
+// What looks wrong with the model file, if anything. Only the bundled
+// quant's size is known; any other model just has to be a whole GGUF header
+// and more than a few MB.
+fn truncation_problem(model_path: &Path) -> Option<String> {
+    let min_bytes = if model_path.file_name() == Some(QWEN_MODEL_NAME.as_ref()) {
+        QWEN_MODEL_MIN_BYTES
+    } else {
+        MODEL_MIN_BYTES
+    };
+
+    match std::fs::metadata(model_path) {
+        Ok(metadata) if metadata.len() < min_bytes => {
+            return Some(format!(
+                "is only {} bytes (expected at least {})",
+                metadata.len(),
+                min_bytes
+            ))
+        }
+        Ok(_) => {}
+        Err(e) => return Some(format!("couldn't be checked ({})", e)),
+    }
+
+    gguf::read_info(model_path)
+        .err()
+        .map(|e| format!("has no valid GGUF header ({})", e))
+}
+
+/// Decodes a completion for `prompt`, starting from an empty KV cache.
+/// `on_piece` sees the text as it's decoded, never splitting a character.
+fn generate(
+    model: &LlamaModel,
+    ctx: &mut LlamaContext<'static>,
+    prompt: &str,
+    max_new_tokens: usize,
+    sampling: &SamplingParams,
+    mut on_piece: impl FnMut(&str),
+) -> Result<(String, StopReason)> {
     ctx.clear_kv_cache();
 
-    let n_ctx = NonZeroU32::new(2048).unwrap();
+    let mut sampler = sampling.sampler();
 
-    let prompt = format!(
-        //         r#"<|im_start|>system
-        // Localize {language} gaming chat to natural, informal English.
-        // Adapt slang/idioms to Western gaming terms (e.g., 'lol', 'choke', 'clutch').
-        // Maintain the user's tone. If the text only includes link, ignore it and
-        // reply with '<ignore>'. If the text is unclear to translate, reply with
-        // '<ignore>'. If the translation is too harsh, tone it down.
-        // Otherwise, output translation only.<|im_end|>
-        // <|im_start|>user
-        // {raw_input}
-        // <|im_end|>
-        // <|im_start|>assistant"#,
-        r#"<|im_start|>system
-If the text is in English, reply with '<@>' exactly.
-Localize gaming chat to natural, informal English.
-Adapt slang/idioms to Western gaming terms (e.g., 'lol', 'choke', 'clutch').
-Maintain the user's tone. If the text only includes link, ignore it and
-reply with '<@>' exactly. If the text is unclear to translate, reply with
-'<@>' exactly. If the translation is too harsh, tone it down. 
-Otherwise, output translation or '<@>' exactly only.<|im_end|>
-<|im_start|>user
-{raw_input}
-<|im_end|>
-<|im_start|>assistant"#,
-        // language = source_lang,
-        raw_input = raw_text
-    );
+    let n_ctx = NonZeroU32::new(CONTEXT_TOKENS).unwrap();
 
     let prompt_tokens = model
-        .str_to_token(&prompt, AddBos::Always)
+        .str_to_token(prompt, AddBos::Always)
         .context("Failed to tokenize prompt")?;
 
-    let mut batch = LlamaBatch::new(2048, 1);
+    // Messages go through `fit_message` first, so this only trips if a
+    // prompt template itself grows too large
+    if prompt_tokens.len() >= n_ctx.get() as usize {
+        anyhow::bail!(
+            "Prompt is {} tokens, more than the {} token context",
+            prompt_tokens.len(),
+            n_ctx
+        );
+    }
+
+    let mut batch = LlamaBatch::new(CONTEXT_TOKENS as usize, 1);
 
     let last_index = prompt_tokens.len() as i32 - 1;
     for (i, token) in prompt_tokens.iter().enumerate() {
@@ -184,163 +730,3050 @@ Otherwise, output translation or '<@>' exactly only.<|im_end|>
         batch.add(*token, i as i32, &[0], is_last)?;
     }
 
-    ctx.decode(&mut batch).context("Failed to decode prompt")?;
+    decode(ctx, &mut batch).context("Failed to decode prompt")?;
 
     let mut response_bytes = Vec::<u8>::with_capacity(4096);
-    let max_new_tokens = 2048;
+    let mut tokens = Vec::new();
+    let mut emitted = 0;
     let mut n_curr = batch.n_tokens();
+    let mut stop_reason = StopReason::MaxTokens;
 
     for _ in 0..max_new_tokens {
         if n_curr as u32 >= n_ctx.get() {
+            stop_reason = StopReason::ContextFull;
             break;
         }
 
-        let last_token_idx = batch.n_tokens() - 1;
-        let candidates = ctx.candidates_ith(last_token_idx);
-
-        let next_token = candidates
-            .max_by(|a, b| a.logit().partial_cmp(&b.logit()).unwrap())
-            .map(|data| data.id())
-            .unwrap_or(model.token_eos());
+        // Stop a runaway generation so its context goes back to the pool
+        check_deadline()?;
 
+        let next_token = next_token(model, ctx, sampler.as_mut(), batch.n_tokens() - 1);
         if next_token == model.token_eos() {
+            stop_reason = StopReason::EndOfText;
             break;
         }
 
         let piece = model.token_to_bytes(next_token, Special::Tokenize)?;
         response_bytes.extend(piece);
+        emit_complete(&response_bytes, &mut emitted, &mut on_piece);
+
+        tokens.push(next_token);
+        if is_repeating(&tokens) {
+            stop_reason = StopReason::Repetition;
+            break;
+        }
 
         batch.clear();
         batch.add(next_token, n_curr, &[0], true)?;
 
-        ctx.decode(&mut batch)?;
+        decode(ctx, &mut batch)?;
         n_curr += 1;
     }
 
-    let full_response = String::from_utf8_lossy(&response_bytes).to_string();
+    Ok((
+        String::from_utf8_lossy(&response_bytes).to_string(),
+        stop_reason,
+    ))
+}
 
-    let clean_output = if let Some(_) = full_response.find("<@>") {
-        String::new()
-    } else if let Some(end_tag_pos) = full_response.find("</think>") {
-        let start_of_text = end_tag_pos + 8;
-        if start_of_text < full_response.len() {
-            full_response[start_of_text..].to_string()
-        } else {
-            String::new()
-        }
-    } else {
-        if let Some(_) = full_response.find("<think>") {
-            return Ok(String::from("<error: I thought too hard>"));
+/// `generate` for several prompts at once, each decoded in its own sequence
+/// of a context from `initialize_batch_context`. `on_piece` also gets the
+/// index of the prompt the piece belongs to.
+fn generate_batch(
+    model: &LlamaModel,
+    ctx: &mut LlamaContext<'static>,
+    prompts: &[String],
+    max_new_tokens: usize,
+    sampling: &SamplingParams,
+    mut on_piece: impl FnMut(usize, &str),
+) -> Result<Vec<(String, StopReason)>> {
+    struct Sequence {
+        sampler: Option<LlamaSampler>,
+        // Position of the next token, and where its logits are in the batch
+        position: i32,
+        logits: i32,
+        bytes: Vec<u8>,
+        tokens: Vec<LlamaToken>,
+        emitted: usize,
+        // Set once the sequence is done
+        stopped: Option<StopReason>,
+    }
+
+    ctx.clear_kv_cache();
+
+    let mut prompt_tokens = Vec::with_capacity(prompts.len());
+    for prompt in prompts {
+        let tokens = model
+            .str_to_token(prompt, AddBos::Always)
+            .context("Failed to tokenize prompt")?;
+        if tokens.len() >= CONTEXT_TOKENS as usize {
+            anyhow::bail!(
+                "Prompt is {} tokens, more than the {} token context",
+                tokens.len(),
+                CONTEXT_TOKENS
+            );
         }
-        String::new()
-    };
+        prompt_tokens.push(tokens);
+    }
 
-    Ok(clean_output.trim().to_string())
-}
+    let total_tokens = prompt_tokens.iter().map(Vec::len).sum::<usize>();
+    let mut batch = LlamaBatch::new(total_tokens.max(prompts.len()), 1);
 
-pub async fn perform_translation(
-    text: String,
-    state: &TranslationModelState,
-) -> Result<TranslationResponse, String> {
-    // FAST PATH: Check for slang/abbreviations immediately
-    if is_universal_slang(&text) {
-        return Ok(TranslationResponse {
-            language: "English".into(),
-            translation: text,
+    let mut sequences = Vec::with_capacity(prompts.len());
+    for (seq, tokens) in prompt_tokens.iter().enumerate() {
+        let last_index = tokens.len() - 1;
+        for (i, token) in tokens.iter().enumerate() {
+            batch.add(*token, i as i32, &[seq as i32], i == last_index)?;
+        }
+        sequences.push(Sequence {
+            sampler: sampling.sampler(),
+            position: tokens.len() as i32,
+            logits: batch.n_tokens() - 1,
+            bytes: Vec::new(),
+            tokens: Vec::new(),
+            emitted: 0,
+            stopped: None,
         });
     }
 
-    // Check if it's English!
-    let detected_lang = state
-        .detector
-        .detect_language_of(&text)
-        .ok_or_else(|| "Unknown Language".to_string())?;
-
-    //  If it is, then we skip!
-    let processed_text = match detected_lang {
-        Language::Chinese => slang_zh::normalize_mandarin_slang(&text),
-        Language::Japanese => slang_jp::normalize_japanese_slang(&text),
-        Language::French => slang_fr::normalize_french_slang(&text),
-        Language::English => {
-            return Ok(TranslationResponse {
-                language: "English".into(),
-                translation: text,
-            })
-        }
-        _ => text.clone(),
-    };
+    decode(ctx, &mut batch).context("Failed to decode prompts")?;
 
-    let language_label = detected_lang.to_string();
+    for _ in 0..max_new_tokens {
+        check_deadline()?;
 
-    // We clone the Arcs here so they can be moved into the spawn_blocking closure
-    let llm_state = state.llm_state.clone();
-    let semaphore = state.semaphore.clone();
+        // Every unfinished sequence samples from the last decode, and its
+        // next token goes into one shared batch
+        batch.clear();
+        for (seq, sequence) in sequences.iter_mut().enumerate() {
+            if sequence.stopped.is_some() {
+                continue;
+            }
+            if sequence.position as u32 >= CONTEXT_TOKENS {
+                sequence.stopped = Some(StopReason::ContextFull);
+                continue;
+            }
 
-    // Acquire semaphore (Async wait)
-    let _permit = semaphore
-        .acquire_owned()
-        .await
-        .map_err(|e| format!("Semaphore Error: {}", e))?;
+            let token = next_token(model, ctx, sequence.sampler.as_mut(), sequence.logits);
+            if token == model.token_eos() {
+                sequence.stopped = Some(StopReason::EndOfText);
+                continue;
+            }
 
-    // Run inference (Blocking thread)
-    let translation = tauri::async_runtime::spawn_blocking(move || {
-        let mut ctx = {
-            let mut pool = llm_state
-                .context_pool
-                .lock()
-                .map_err(|_| "Poisoned lock")
-                .unwrap();
-            pool.pop().expect("Semaphore logic failed: Pool was empty!")
-        };
+            sequence
+                .bytes
+                .extend(model.token_to_bytes(token, Special::Tokenize)?);
+            emit_complete(&sequence.bytes, &mut sequence.emitted, |piece| {
+                on_piece(seq, piece)
+            });
 
-        let result =
-            localize_with_qwen(&llm_state.model, &mut ctx, &language_label, &processed_text);
+            sequence.tokens.push(token);
+            if is_repeating(&sequence.tokens) {
+                sequence.stopped = Some(StopReason::Repetition);
+                continue;
+            }
 
-        {
-            let mut pool = llm_state
-                .context_pool
-                .lock()
-                .map_err(|_| "Poisoned lock")
-                .unwrap();
-            pool.push(ctx);
+            sequence.logits = batch.n_tokens();
+            batch.add(token, sequence.position, &[seq as i32], true)?;
+            sequence.position += 1;
         }
 
-        result
-    })
-    .await
-    .map_err(|e| format!("Task Join Error: {}", e))?
-    .map_err(|e| format!("LLM Inference Error: {}", e))?;
+        if batch.n_tokens() == 0 {
+            break;
+        }
+        decode(ctx, &mut batch)?;
+    }
 
-    Ok(TranslationResponse {
-        language: detected_lang.to_string(),
-        translation,
+    Ok(sequences
+        .into_iter()
+        .map(|sequence| {
+            (
+                String::from_utf8_lossy(&sequence.bytes).to_string(),
+                sequence.stopped.unwrap_or(StopReason::MaxTokens),
+            )
+        })
+        .collect())
+}
+
+// A generation is stuck once its last tokens are the same short run over
+// and over, e.g. "lol lol lol ...". Short runs must repeat more often, so
+// "hahaha" isn't cut off.
+const REPETITION_MAX_RUN: usize = 8;
+const REPETITION_MIN_REPEATS: usize = 4;
+const REPETITION_MIN_TOKENS: usize = 16;
+
+fn is_repeating(tokens: &[LlamaToken]) -> bool {
+    (1..=REPETITION_MAX_RUN).any(|run| {
+        let repeats = REPETITION_MIN_REPEATS.max(REPETITION_MIN_TOKENS.div_ceil(run));
+        let Some(start) = tokens.len().checked_sub(run * repeats) else {
+            return false;
+        };
+        let tail = &tokens[start..];
+        tail.chunks(run).all(|chunk| chunk == &tail[..run])
     })
 }
 
-fn is_universal_slang(text: &str) -> bool {
-    let text = text.trim();
-    if text.is_empty() {
-        return false;
+/// Runs a throwaway prompt through `ctx` so llama.cpp allocates its buffers
+/// (and uploads to the GPU) before a real translation has to wait for it.
+pub fn warm_up(model: &LlamaModel, ctx: &mut ThreadSafeContext) -> Result<()> {
+    generate(
+        model,
+        &mut ctx.0,
+        WARMUP_PROMPT,
+        1,
+        &SamplingParams::GREEDY,
+        |_| {},
+    )?;
+    ctx.0.clear_kv_cache();
+    Ok(())
+}
+
+const WARMUP_PROMPT: &str = "<|im_start|>user\nhi<|im_end|>\n<|im_start|>assistant\n";
+
+// Fails once the inference running on this thread is past its deadline
+fn check_deadline() -> Result<()> {
+    let deadline = INFERENCE_DEADLINE.with(Cell::get);
+    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        anyhow::bail!(TRANSLATION_TIMED_OUT);
     }
+    Ok(())
+}
 
-    // We split by whitespace to handle messages like "LUL LUL LUL"
-    text.split_whitespace().all(|token| {
-        // Remove common punctuation to handle "LMAO!" or "WTF?"
-        // This will also remove emojis!
-        // and emoticons :)
-        let clean_token: String = token.chars().filter(|c| c.is_alphanumeric()).collect();
+// Sets this thread's inference deadline while alive. It's cleared even if
+// the inference panics, so the next job on the blocking thread doesn't
+// inherit it.
+struct DeadlineGuard;
 
-        if clean_token.is_empty() {
-            return true;
-        }
+impl DeadlineGuard {
+    fn new(timeout: Option<Duration>) -> Self {
+        INFERENCE_DEADLINE.with(|deadline| deadline.set(timeout.map(|t| Instant::now() + t)));
+        Self
+    }
+}
+
+impl Drop for DeadlineGuard {
+    fn drop(&mut self) {
+        INFERENCE_DEADLINE.with(|deadline| deadline.set(None));
+    }
+}
 
-        // Check against a hardcoded list of universal slang
-        match clean_token.to_uppercase().as_str() {
-            "LMAO" | "LMFAO" | "LOL" | "ROFL" | "LUL" | "KEKW" | "OMEGALUL" | "POG" | "POGGERS"
-            | "POGCHAMP" | "KAPPA" | "MONKAW" | "MONKAS" | "PEPELAUGH" | "SADGE" | "BRUH"
-            | "WTF" | "OMG" | "IDK" | "XD" | "XDD" | "HA" | "HAHA" | "HAHAHA" | "JAJA"
-            | "JAJAJA" | "MDR" | "L" | "FTFY" | "ERM" => true,
-            _ => false,
+// Samples the token after the logits at `idx`, or takes the most likely one
+// when decoding greedily
+fn next_token(
+    model: &LlamaModel,
+    ctx: &LlamaContext<'static>,
+    sampler: Option<&mut LlamaSampler>,
+    idx: i32,
+) -> LlamaToken {
+    match sampler {
+        Some(sampler) => {
+            let token = sampler.sample(ctx, idx);
+            sampler.accept(token);
+            token
         }
-    })
+        None => ctx
+            .candidates_ith(idx)
+            .max_by(|a, b| a.logit().total_cmp(&b.logit()))
+            .map(|data| data.id())
+            .unwrap_or(model.token_eos()),
+    }
+}
+
+// Passes on whatever complete characters follow `emitted`. A token can end
+// halfway through a multi-byte character, which waits for the next one.
+fn emit_complete(bytes: &[u8], emitted: &mut usize, mut on_piece: impl FnMut(&str)) {
+    let pending = &bytes[*emitted..];
+    let complete = match std::str::from_utf8(pending) {
+        Ok(text) => text,
+        Err(e) => std::str::from_utf8(&pending[..e.valid_up_to()]).unwrap_or_default(),
+    };
+    if !complete.is_empty() {
+        on_piece(complete);
+        *emitted += complete.len();
+    }
+}
+
+// What `fit_message` needs from a model, so the cutting can be tested
+// without loading one
+trait Tokenizer {
+    fn message_tokens(&self, text: &str) -> Result<Vec<LlamaToken>>;
+    fn token_bytes(&self, token: LlamaToken) -> Result<Vec<u8>>;
+}
+
+impl Tokenizer for LlamaModel {
+    fn message_tokens(&self, text: &str) -> Result<Vec<LlamaToken>> {
+        self.str_to_token(text, AddBos::Never)
+            .context("Failed to tokenize message")
+    }
+
+    fn token_bytes(&self, token: LlamaToken) -> Result<Vec<u8>> {
+        Ok(self.token_to_bytes(token, Special::Tokenize)?)
+    }
+}
+
+/// Cuts `text` down to `MAX_MESSAGE_TOKENS` so a pasted wall of text can't
+/// overflow the context. Returns the text and whether anything was cut.
+fn fit_message(model: &impl Tokenizer, text: &str) -> Result<(String, bool)> {
+    let tokens = model.message_tokens(text)?;
+    if tokens.len() <= MAX_MESSAGE_TOKENS {
+        return Ok((text.to_string(), false));
+    }
+
+    let mut bytes = Vec::new();
+    for token in &tokens[..MAX_MESSAGE_TOKENS] {
+        bytes.extend(model.token_bytes(*token)?);
+    }
+
+    // The cut can land inside a multi-byte character
+    let text = String::from_utf8_lossy(&bytes);
+    Ok((text.trim_end_matches('\u{FFFD}').to_string(), true))
+}
+
+// A failed decode (usually the GPU running out of memory) leaves the KV cache
+// half-written, so it's wiped before the context goes back to the pool.
+fn decode(ctx: &mut LlamaContext<'static>, batch: &mut LlamaBatch) -> Result<()> {
+    match ctx.decode(batch) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            ctx.clear_kv_cache();
+            Err(e.into())
+        }
+    }
+}
+
+pub fn localize_with_qwen(
+    model: &LlamaModel,
+    wrapped_ctx: &mut ThreadSafeContext, // Accept the wrapper
+    source_lang: &str,
+    instructions: &str,
+    raw_text: &str,
+    think: bool,
+    sampling: &SamplingParams,
+    mut on_token: impl FnMut(&str),
+) -> Result<(String, StopReason)> {
+    let ctx = &mut wrapped_ctx.0; // Access internal context
+
+    let prompt = localize_prompt(instructions, raw_text, think);
+
+    // Only the answer is passed on, not the reasoning before it
+    let mut answer = AnswerStream::new(think);
+    let started = Instant::now();
+    let (full_response, stop_reason) = generate(
+        model,
+        ctx,
+        &prompt,
+        sampling.max_new_tokens,
+        sampling,
+        |piece| answer.forward(piece, &mut on_token),
+    )?;
+
+    // Reasoning is most of what a translation costs, so this shows what
+    // turning it on or off buys
+    if let Ok(tokens) = model.str_to_token(&full_response, AddBos::Never) {
+        tracing::info!(
+            "Generated {} tokens in {:?} (thinking {}, stopped: {:?})",
+            tokens.len(),
+            started.elapsed(),
+            if think { "on" } else { "off" },
+            stop_reason
+        );
+    }
+
+    Ok((clean_answer(&full_response, think), stop_reason))
+}
+
+/// `localize_with_qwen` for several messages at once, decoded together in
+/// a context from `initialize_batch_context`. Returns each message's
+/// translation, whether it had to be cut to fit and why generation stopped.
+fn localize_batch_with_qwen(
+    model: &LlamaModel,
+    wrapped_ctx: &mut ThreadSafeContext,
+    items: Vec<BatchItem>,
+    sampling: &SamplingParams,
+) -> Result<Vec<(String, bool, StopReason)>> {
+    let mut prompts = Vec::with_capacity(items.len());
+    let mut truncated = Vec::with_capacity(items.len());
+    let mut answers = Vec::with_capacity(items.len());
+    let mut thinks = Vec::with_capacity(items.len());
+    let mut callbacks = Vec::with_capacity(items.len());
+    for item in items {
+        let (text, cut) = fit_message(model, &item.text)?;
+        prompts.push(localize_prompt(&item.instructions, &text, item.think));
+        truncated.push(cut);
+        answers.push(AnswerStream::new(item.think));
+        thinks.push(item.think);
+        callbacks.push(item.on_token);
+    }
+
+    let started = Instant::now();
+    let responses = generate_batch(
+        model,
+        &mut wrapped_ctx.0,
+        &prompts,
+        sampling.max_new_tokens,
+        sampling,
+        |seq, piece| answers[seq].forward(piece, &mut callbacks[seq]),
+    )?;
+    tracing::info!(
+        "Translated a batch of {} in {:?}",
+        responses.len(),
+        started.elapsed()
+    );
+
+    Ok(responses
+        .iter()
+        .zip(thinks)
+        .zip(truncated)
+        .map(|(((response, stop_reason), think), truncated)| {
+            (clean_answer(response, think), truncated, *stop_reason)
+        })
+        .collect())
+}
+
+/// Tells the model to decline rather than translate. A custom system
+/// prompt without it gets links and gibberish "translated".
+pub const DECLINE_SENTINEL: &str = "<@>";
+
+/// Why a custom system prompt would translate badly, if it would.
+pub fn system_prompt_warning(template: &str) -> Option<String> {
+    (!template.contains(DECLINE_SENTINEL)).then(|| {
+        format!(
+            "The system prompt never asks for '{}', so the model can't skip links or unclear text",
+            DECLINE_SENTINEL
+        )
+    })
+}
+
+// The system prompt for chat translation, shared by the local model and
+// remote backends. `custom` is the user's `system_prompt`, with `{lang}`
+// standing for the target language.
+fn localize_instructions(
+    target_lang: &str,
+    custom: Option<&str>,
+    preserve_profanity: bool,
+) -> String {
+    if let Some(template) = custom {
+        return template.replace("{lang}", target_lang);
+    }
+
+    // The examples only make sense for English
+    let idioms = if target_lang == "English" {
+        "Adapt slang/idioms to Western gaming terms (e.g., 'lol', 'choke', 'clutch').".to_string()
+    } else {
+        format!(
+            "Adapt slang/idioms to gaming terms {} speakers use.",
+            target_lang
+        )
+    };
+
+    let profanity = if preserve_profanity {
+        "Translate profanity and insults faithfully, without softening them."
+    } else {
+        "If the translation is too harsh, tone it down."
+    };
+
+    format!(
+        //         r#"Localize {language} gaming chat to natural, informal English.
+        // Adapt slang/idioms to Western gaming terms (e.g., 'lol', 'choke', 'clutch').
+        // Maintain the user's tone. If the text only includes link, ignore it and
+        // reply with '<ignore>'. If the text is unclear to translate, reply with
+        // '<ignore>'. If the translation is too harsh, tone it down.
+        // Otherwise, output translation only."#,
+        r#"If the text is in {target}, reply with '<@>' exactly.
+Localize gaming chat to natural, informal {target}.
+{idioms}
+Maintain the user's tone. If the text only includes link, ignore it and
+reply with '<@>' exactly. If the text is unclear to translate, reply with
+'<@>' exactly. {profanity}
+Keep placeholders like [#0] exactly as they are.
+Otherwise, output translation or '<@>' exactly only."#,
+        // language = source_lang,
+        target = target_lang,
+        idioms = idioms,
+        profanity = profanity,
+    )
+}
+
+fn localize_prompt(instructions: &str, raw_text: &str, think: bool) -> String {
+    let prompt = format!(
+        r#"<|im_start|>system
+{system}{no_think}<|im_end|>
+<|im_start|>user
+{raw_input}
+<|im_end|>
+<|im_start|>assistant"#,
+        system = instructions,
+        no_think = if think { "" } else { " /no_think" },
+        raw_input = raw_text
+    );
+
+    // Without thinking, an empty think block is pre-filled as well so Qwen
+    // answers straight away
+    if think {
+        prompt
+    } else {
+        format!("{}\n<think>\n\n</think>\n\n", prompt)
+    }
+}
+
+// The API that takes the local model's place, if one is configured
+fn remote_backend(settings: &Settings) -> Option<RemoteBackend<'_>> {
+    match &settings.backend {
+        Backend::Local => None,
+        Backend::OpenAiCompatible {
+            base_url,
+            api_key,
+            model,
+        } => Some(RemoteBackend {
+            base_url,
+            api_key,
+            model,
+        }),
+    }
+}
+
+/// Translates `raw_text` with a remote backend instead of the local model,
+/// using the same instructions. Reasoning models' `<think>` blocks are
+/// dropped like Qwen's.
+async fn localize_remote(
+    backend: &RemoteBackend<'_>,
+    instructions: &str,
+    raw_text: &str,
+    sampling: &SamplingParams,
+    timeout: Duration,
+) -> Result<String, String> {
+    let started = Instant::now();
+    let response = backend
+        .complete(
+            instructions,
+            raw_text,
+            sampling,
+            sampling.max_new_tokens,
+            timeout,
+        )
+        .await?;
+    tracing::info!("Remote translation took {:?}", started.elapsed());
+
+    Ok(clean_answer(&response, true))
+}
+
+// Passes on the pieces of an answer, skipping the reasoning before it.
+// "</think>" usually arrives split over several tokens, so the tail of the
+// reasoning is held on to until the tag is complete.
+struct AnswerStream {
+    answering: bool,
+    reasoning_tail: String,
+}
+
+impl AnswerStream {
+    fn new(think: bool) -> Self {
+        Self {
+            answering: !think,
+            reasoning_tail: String::new(),
+        }
+    }
+
+    fn forward(&mut self, piece: &str, mut on_token: impl FnMut(&str)) {
+        const END_TAG: &str = "</think>";
+
+        if self.answering {
+            on_token(piece);
+            return;
+        }
+
+        self.reasoning_tail.push_str(piece);
+        if let Some(end) = self.reasoning_tail.find(END_TAG) {
+            self.answering = true;
+            let answer = &self.reasoning_tail[end + END_TAG.len()..];
+            if !answer.is_empty() {
+                on_token(answer);
+            }
+            self.reasoning_tail = String::new();
+        } else {
+            // Only the start of a tag can still become one
+            let mut cut = self.reasoning_tail.len().saturating_sub(END_TAG.len() - 1);
+            while !self.reasoning_tail.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.reasoning_tail.drain(..cut);
+        }
+    }
+}
+
+// The translation in a raw response, or nothing if the model declined. A
+// '<@>' tacked onto a real translation is dropped rather than taken as the
+// model declining.
+fn clean_answer(full_response: &str, think: bool) -> String {
+    let answer = if think {
+        strip_think(full_response)
+    } else {
+        full_response
+    };
+
+    let without_sentinel = answer.replace(DECLINE_SENTINEL, " ");
+    // Quotes around the sentinel still mean declining, but other punctuation
+    // is a real answer: "???" translates "？？？"
+    if without_sentinel
+        .chars()
+        .all(|c| c.is_whitespace() || QUOTE_MARKS.contains(&c))
+    {
+        return String::new();
+    }
+
+    sanitize_answer(&without_sentinel)
+}
+
+// Quotes the model sometimes wraps its answer in
+const QUOTE_MARKS: &[char] = &['"', '\'', '`', '“', '”', '‘', '’', '「', '」', '『', '』'];
+
+// Chat template tokens the model sometimes runs on into, like <|im_end|>,
+// with the role name after an <|im_start|>
+static TEMPLATE_TOKEN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<\|[a-z_]+\|>(?:system|user|assistant)?").unwrap());
+
+/// Makes an answer fit in one chat message: leftover template tokens are
+/// dropped, line breaks become spaces, and anything past Twitch's limit is
+/// cut at a word boundary.
+fn sanitize_answer(answer: &str) -> String {
+    let without_tokens = TEMPLATE_TOKEN.replace_all(answer, " ");
+    let single_line = without_tokens
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if single_line.chars().count() <= MAX_CHAT_MESSAGE_CHARS {
+        return single_line;
+    }
+
+    // One character is left for the ellipsis
+    let cut: String = single_line
+        .chars()
+        .take(MAX_CHAT_MESSAGE_CHARS - 1)
+        .collect();
+    // Text without spaces (Chinese, Japanese) is cut wherever it ends
+    let cut = match cut.rfind(' ') {
+        Some(space) if space > cut.len() / 2 => &cut[..space],
+        _ => cut.as_str(),
+    };
+    format!("{}…", cut.trim_end())
+}
+
+/// Qwen3's answer without its reasoning: the text after the last
+/// `</think>`, cut short at a `<think>` that was never closed. Reasoning
+/// cut off by the token limit therefore leaves an empty answer.
+fn strip_think(response: &str) -> &str {
+    let answer = match response.rfind("</think>") {
+        Some(end) => &response[end + "</think>".len()..],
+        None => response,
+    };
+
+    match answer.find("<think>") {
+        Some(start) => &answer[..start],
+        None => answer,
+    }
+}
+
+// The system prompt for the streamer's outbound messages, shared by the
+// local model and remote backends
+fn localize_into_instructions(target_lang: &str) -> String {
+    format!(
+        r#"Translate this English gaming stream chat message into natural, informal {language}.
+Maintain the streamer's tone.
+Keep placeholders like [#0] exactly as they are.
+Output the translation only."#,
+        language = target_lang
+    )
+}
+
+/// Inverse of `localize_with_qwen`: renders English chat into `target_lang`.
+pub fn localize_into_with_qwen(
+    model: &LlamaModel,
+    wrapped_ctx: &mut ThreadSafeContext,
+    target_lang: &str,
+    raw_text: &str,
+    sampling: &SamplingParams,
+    on_token: impl FnMut(&str),
+) -> Result<String> {
+    let prompt = format!(
+        r#"<|im_start|>system
+{system}<|im_end|>
+<|im_start|>user
+{raw_input}
+<|im_end|>
+<|im_start|>assistant
+<think>
+
+</think>
+
+"#,
+        system = localize_into_instructions(target_lang),
+        raw_input = raw_text
+    );
+
+    let (response, _) = generate(model, &mut wrapped_ctx.0, &prompt, 512, sampling, on_token)?;
+
+    Ok(response.trim().to_string())
+}
+
+// The system prompt for sentiment, shared by the local model and remote
+// backends
+const SENTIMENT_INSTRUCTIONS: &str = "Classify the mood of this gaming chat message.
+Reply with exactly one word: positive, neutral or negative.";
+// The answer is one word, so a handful of tokens is enough
+const SENTIMENT_MAX_TOKENS: usize = 8;
+
+pub fn classify_sentiment_with_qwen(
+    model: &LlamaModel,
+    wrapped_ctx: &mut ThreadSafeContext,
+    raw_text: &str,
+) -> Result<Sentiment> {
+    let (raw_text, _) = fit_message(model, raw_text)?;
+
+    // The empty think block skips Qwen3's reasoning so a handful of tokens
+    // is enough for the one-word answer.
+    let prompt = format!(
+        r#"<|im_start|>system
+{system}<|im_end|>
+<|im_start|>user
+{raw_input}
+<|im_end|>
+<|im_start|>assistant
+<think>
+
+</think>
+
+"#,
+        system = SENTIMENT_INSTRUCTIONS,
+        raw_input = raw_text
+    );
+
+    let (response, _) = generate(
+        model,
+        &mut wrapped_ctx.0,
+        &prompt,
+        SENTIMENT_MAX_TOKENS,
+        &SamplingParams::GREEDY,
+        |_| {},
+    )?;
+
+    Ok(parse_sentiment(&response))
+}
+
+// Reads the one-word answer to `SENTIMENT_INSTRUCTIONS`
+fn parse_sentiment(response: &str) -> Sentiment {
+    let response = response.to_lowercase();
+    if response.contains("positive") {
+        Sentiment::Positive
+    } else if response.contains("negative") {
+        Sentiment::Negative
+    } else {
+        Sentiment::Neutral
+    }
+}
+
+/// Translates `text`. `forced_language` skips language detection entirely,
+/// while `language_hint` only breaks close calls.
+pub async fn perform_translation(
+    text: String,
+    state: &TranslationModelState,
+    settings: &Settings,
+    language_hint: Option<Language>,
+    forced_language: Option<Language>,
+    direction: Direction,
+    priority: Priority,
+) -> Result<TranslationResponse, String> {
+    stream_translation(
+        text,
+        state,
+        settings,
+        language_hint,
+        forced_language,
+        direction,
+        priority,
+        Box::new(|_| {}),
+    )
+    .await
+}
+
+/// Like `perform_translation`, but hands each decoded piece of a chat
+/// translation to `on_token` as it arrives. Cached and skipped messages
+/// produce no pieces, only the final response.
+pub async fn stream_translation(
+    text: String,
+    state: &TranslationModelState,
+    settings: &Settings,
+    language_hint: Option<Language>,
+    forced_language: Option<Language>,
+    direction: Direction,
+    priority: Priority,
+    on_token: TokenCallback,
+) -> Result<TranslationResponse, String> {
+    // One span per translation; the translation log writes it out with
+    // whatever was recorded once it closes
+    let span = tracing::info_span!(
+        target: logging::TRANSLATION_TARGET,
+        "translation",
+        original = %text,
+        normalized = tracing::field::Empty,
+        output = tracing::field::Empty,
+        language = tracing::field::Empty,
+        error = tracing::field::Empty,
+        latency_ms = tracing::field::Empty,
+    );
+    let started = Instant::now();
+
+    let result = async {
+        let mut response = match direction {
+            Direction::ToEnglish => {
+                translate_message(
+                    text.clone(),
+                    state,
+                    settings,
+                    language_hint,
+                    forced_language,
+                    priority,
+                    on_token,
+                )
+                .await?
+            }
+            Direction::FromEnglish(target) => {
+                translate_from_english(text.clone(), state, settings, target, priority, on_token)
+                    .await?
+            }
+        };
+
+        if settings.score_toxicity {
+            response.toxicity = Some(toxicity::score_toxicity(&text));
+        }
+
+        // Sentiment costs a second (tiny) inference, so it's opt-in
+        if settings.classify_sentiment && response.sentiment.is_none() {
+            let sentiment = match remote_backend(settings) {
+                Some(backend) => backend
+                    .complete(
+                        SENTIMENT_INSTRUCTIONS,
+                        &text,
+                        &SamplingParams::GREEDY,
+                        SENTIMENT_MAX_TOKENS,
+                        Duration::from_millis(settings.translation_timeout_ms),
+                    )
+                    .await
+                    .map(|response| parse_sentiment(strip_think(&response))),
+                None => run_with_context(state, priority, move |model, ctx| {
+                    classify_sentiment_with_qwen(model, ctx, &text)
+                })
+                .await?
+                .map_err(|e| e.to_string()),
+            };
+
+            match sentiment {
+                Ok(sentiment) => response.sentiment = Some(sentiment),
+                Err(e) => tracing::warn!("Sentiment classification failed: {}", e),
+            }
+        }
+
+        Ok::<_, String>(response)
+    }
+    .instrument(span.clone())
+    .await;
+
+    match &result {
+        Ok(response) => {
+            span.record("output", response.translation.as_str());
+            span.record("language", response.language.as_str());
+            if let Some(timings) = &response.debug_timings {
+                tracing::debug!("Stage timings: {:?}", timings);
+            }
+        }
+        Err(e) => {
+            span.record("error", e.as_str());
+        }
+    }
+    span.record("latency_ms", started.elapsed().as_millis() as u64);
+
+    result
+}
+
+async fn translate_message(
+    text: String,
+    state: &TranslationModelState,
+    settings: &Settings,
+    language_hint: Option<Language>,
+    forced_language: Option<Language>,
+    priority: Priority,
+    mut on_token: TokenCallback,
+) -> Result<TranslationResponse, String> {
+    let target = settings.target_language();
+    let mut timings = StageTimings::default();
+
+    // FAST PATH: Check for slang/abbreviations immediately
+    let stage = Instant::now();
+    let universal_slang = is_universal_slang(&text);
+    timings.universal_slang_ms = elapsed_ms(stage);
+    if universal_slang {
+        return Ok(TranslationResponse {
+            language: "English".into(),
+            target_language: target.to_string(),
+            translation: text,
+            sentiment: None,
+            toxicity: None,
+            normalized_only: false,
+            truncated: false,
+            confidence: None,
+            detected: false,
+            already_target: true,
+            normalized_input: None,
+            stop_reason: None,
+            debug_timings: Some(timings),
+        });
+    }
+
+    // Too short to be worth a model call, or to translate reliably
+    if is_too_short(&text, settings) {
+        return Ok(TranslationResponse {
+            language: "Unknown".into(),
+            target_language: target.to_string(),
+            translation: text,
+            sentiment: None,
+            toxicity: None,
+            normalized_only: false,
+            truncated: false,
+            confidence: None,
+            detected: false,
+            already_target: false,
+            normalized_input: None,
+            stop_reason: None,
+            debug_timings: Some(timings),
+        });
+    }
+
+    // Nothing but @mentions and links: the model would only answer '<@>'
+    if is_mentions_and_links(&text) {
+        return Ok(TranslationResponse {
+            language: "Unknown".into(),
+            target_language: target.to_string(),
+            translation: text,
+            sentiment: None,
+            toxicity: None,
+            normalized_only: false,
+            truncated: false,
+            confidence: None,
+            detected: false,
+            already_target: false,
+            normalized_input: None,
+            stop_reason: None,
+            debug_timings: Some(timings),
+        });
+    }
+
+    // Check if it's already in the target language!
+    // A guess below the threshold would only send the text through the wrong
+    // slang dictionary and prompt. A forced language isn't a guess, so it
+    // reports no confidence.
+    let stage = Instant::now();
+    let (detected_lang, confidence) =
+        detect_source_language(state, settings, &text, language_hint, forced_language)?;
+    timings.detection_ms = elapsed_ms(stage);
+
+    //  If it is, then we skip! English jargon can still be explained
+    if detected_lang == target {
+        let translation = if target == Language::English && settings.explain_english_jargon {
+            slang_en::explain_english_jargon(&text)
+        } else {
+            text.clone()
+        };
+        return Ok(TranslationResponse {
+            language: detected_lang.to_string(),
+            target_language: target.to_string(),
+            normalized_only: translation != text,
+            translation,
+            sentiment: None,
+            toxicity: None,
+            truncated: false,
+            confidence,
+            detected: confidence.is_some(),
+            already_target: true,
+            normalized_input: None,
+            stop_reason: None,
+            debug_timings: Some(timings),
+        });
+    }
+
+    let stage = Instant::now();
+    let mut protected = normalize_message(detected_lang, text.clone(), settings.preserve_profanity);
+    timings.normalization_ms = elapsed_ms(stage);
+    tracing::Span::current().record("normalized", protected.text.as_str());
+
+    // For some languages the normalized text alone is good enough
+    if settings.language_settings(detected_lang).skip_llm {
+        return Ok(TranslationResponse {
+            language: detected_lang.to_string(),
+            target_language: target.to_string(),
+            translation: protected.restore(&protected.text),
+            sentiment: None,
+            toxicity: None,
+            normalized_only: true,
+            truncated: false,
+            confidence,
+            detected: confidence.is_some(),
+            already_target: false,
+            normalized_input: Some(protected.text.clone()),
+            stop_reason: None,
+            debug_timings: Some(timings),
+        });
+    }
+
+    let language_label = detected_lang.to_string();
+    let instructions = localize_instructions(
+        &target.to_string(),
+        settings.system_prompt.as_deref(),
+        settings.preserve_profanity,
+    );
+
+    // Curated glossary terms bypass the model and come back verbatim
+    if let Some(glossary) = state.glossary.lock().map_err(|_| "Poisoned lock")?.as_ref() {
+        glossary.protect(&mut protected);
+    }
+    let processed_text = protected.text.clone();
+    let model_input = processed_text.clone();
+
+    // Near-identical messages (copypasta, spam chains) reuse an earlier result,
+    // as long as it was translated the same way. Hashtags and glossary terms
+    // are placeholders by now, and a hit fills in this message's own.
+    let cache_key = cache::CacheKey {
+        text: cache::cache_key(&model_input, settings.cache_key_mode),
+        language: detected_lang,
+        target,
+        system_prompt: settings.system_prompt.clone(),
+        preserve_profanity: settings.preserve_profanity,
+    };
+    let cached = state
+        .cache
+        .lock()
+        .map_err(|_| "Poisoned lock")?
+        .get(&cache_key);
+    if let Some(mut cached) = cached {
+        if protected.is_protected() {
+            cached.translation = protected.restore(&cached.translation);
+        }
+        cached.confidence = confidence;
+        cached.detected = confidence.is_some();
+        cached.normalized_input = Some(model_input);
+        cached.stop_reason = None;
+        cached.debug_timings = Some(timings);
+        return Ok(cached);
+    }
+
+    let stage = Instant::now();
+    // Held until the translation is done, whichever engine runs it
+    let _language_permit = acquire_language_permit(state, settings, detected_lang).await?;
+    // While the queue stays backed up, the faster M2M100 engine takes over
+    // if it's available; otherwise Qwen skips its reasoning
+    let fast_mode = settings.fast_mode_under_load && update_fast_mode(state);
+    let m2m100_translation = if uses_m2m100(settings.engine, fast_mode) {
+        translate_with_m2m100(
+            state,
+            processed_text.clone(),
+            detected_lang,
+            target,
+            priority,
+        )
+        .await?
+    } else {
+        None
+    };
+
+    let (translation, truncated, stop_reason) = match (m2m100_translation, remote_backend(settings))
+    {
+        (Some(translation), _) => {
+            // M2M100 doesn't stream, so the translation is one piece
+            on_token(&translation);
+            (translation, false, None)
+        }
+        (None, Some(backend)) => {
+            let translation = localize_remote(
+                &backend,
+                &instructions,
+                &processed_text,
+                &settings.sampling,
+                Duration::from_millis(settings.translation_timeout_ms),
+            )
+            .await?;
+            // The whole reply arrives at once, so it streams as one piece
+            on_token(&translation);
+            (translation, false, None)
+        }
+        (None, None) => {
+            let think = settings.thinking && !fast_mode;
+            let sampling = settings.sampling;
+
+            // Broadcaster and mod messages don't wait for a batch to fill
+            if settings.batch_window_ms > 0 && priority == Priority::Normal {
+                let item = BatchItem {
+                    instructions,
+                    text: processed_text,
+                    think,
+                    on_token,
+                };
+                let (translation, truncated, stop_reason) = translate_batched(state, item).await?;
+                (translation, truncated, Some(stop_reason))
+            } else {
+                let translation = run_with_context(state, priority, move |model, ctx| {
+                    let (text, truncated) = fit_message(model, &processed_text)?;
+                    localize_with_qwen(
+                        model,
+                        ctx,
+                        &language_label,
+                        &instructions,
+                        &text,
+                        think,
+                        &sampling,
+                        on_token,
+                    )
+                    .map(|(translation, stop_reason)| (translation, truncated, Some(stop_reason)))
+                })
+                .await?;
+                track_decode_failures(state, &translation);
+                translation.map_err(|e| format!("LLM Inference Error: {}", e))?
+            }
+        }
+    };
+
+    timings.inference_ms = elapsed_ms(stage);
+
+    // Garbage isn't cached, so the next copy of the message gets another try
+    if let Some(reason) = settings
+        .quality_gate
+        .check(&model_input, &translation, target)
+    {
+        tracing::info!("Rejected translation ({}): {}", reason, translation);
+        return Ok(translated_response(
+            detected_lang,
+            target,
+            confidence,
+            model_input,
+            (String::new(), truncated, stop_reason),
+            timings,
+        ));
+    }
+
+    let mut response = translated_response(
+        detected_lang,
+        target,
+        confidence,
+        model_input,
+        (translation, truncated, stop_reason),
+        timings,
+    );
+
+    // Cached with its placeholders, which a hit fills in from its own message
+    state
+        .cache
+        .lock()
+        .map_err(|_| "Poisoned lock")?
+        .insert(cache_key, response.clone());
+
+    if protected.is_protected() {
+        response.translation = protected.restore(&response.translation);
+    }
+    Ok(response)
+}
+
+// The response for a chat message that went to the model: its translation,
+// whether the message was cut to fit and why generation stopped
+fn translated_response(
+    language: Language,
+    target: Language,
+    confidence: Option<f64>,
+    model_input: String,
+    (translation, truncated, stop_reason): (String, bool, Option<StopReason>),
+    timings: StageTimings,
+) -> TranslationResponse {
+    TranslationResponse {
+        language: language.to_string(),
+        target_language: target.to_string(),
+        translation,
+        sentiment: None,
+        toxicity: None,
+        normalized_only: false,
+        truncated,
+        confidence,
+        detected: confidence.is_some(),
+        already_target: false,
+        normalized_input: Some(model_input),
+        stop_reason,
+        debug_timings: Some(timings),
+    }
+}
+
+// Outbound messages skip the cache and slang dictionaries, which only know
+// how to read non-English chat.
+async fn translate_from_english(
+    text: String,
+    state: &TranslationModelState,
+    settings: &Settings,
+    target: Language,
+    priority: Priority,
+    mut on_token: TokenCallback,
+) -> Result<TranslationResponse, String> {
+    let detected = detect_language(&read_detector(state), &text, None);
+    let detected_lang = detected.map(|(language, _)| language);
+    let confidence = detected.map_or(0.0, |(_, confidence)| confidence);
+
+    // Only plain English goes out; anything else is left as written
+    if is_universal_slang(&text) || detected_lang != Some(Language::English) {
+        return Ok(TranslationResponse {
+            language: detected_lang
+                .map(|language| language.to_string())
+                .unwrap_or_else(|| "Unknown".into()),
+            target_language: target.to_string(),
+            translation: text,
+            sentiment: None,
+            toxicity: None,
+            normalized_only: false,
+            truncated: false,
+            confidence: detected.map(|(_, confidence)| confidence),
+            detected: detected.is_some(),
+            already_target: false,
+            normalized_input: None,
+            stop_reason: None,
+            debug_timings: None,
+        });
+    }
+
+    let mut protected = ProtectedText::new(text);
+    protected.protect_hashtags();
+    if let Some(glossary) = state.glossary.lock().map_err(|_| "Poisoned lock")?.as_ref() {
+        glossary.protect(&mut protected);
+    }
+    let processed_text = protected.text.clone();
+    let target_label = target.to_string();
+
+    // Same engine choice as for chat
+    let fast_mode = settings.fast_mode_under_load && update_fast_mode(state);
+    let m2m100_translation = if uses_m2m100(settings.engine, fast_mode) {
+        translate_with_m2m100(
+            state,
+            processed_text.clone(),
+            Language::English,
+            target,
+            priority,
+        )
+        .await?
+    } else {
+        None
+    };
+
+    let (translation, truncated) = match (m2m100_translation, remote_backend(settings)) {
+        (Some(translation), _) => {
+            on_token(&translation);
+            (translation, false)
+        }
+        (None, Some(backend)) => {
+            let response = backend
+                .complete(
+                    &localize_into_instructions(&target_label),
+                    &processed_text,
+                    &settings.sampling,
+                    settings.sampling.max_new_tokens,
+                    Duration::from_millis(settings.translation_timeout_ms),
+                )
+                .await?;
+            let translation = strip_think(&response).trim().to_string();
+            // The whole reply arrives at once, so it streams as one piece
+            on_token(&translation);
+            (translation, false)
+        }
+        (None, None) => {
+            let sampling = settings.sampling;
+            let translation = run_with_context(state, priority, move |model, ctx| {
+                let (text, truncated) = fit_message(model, &processed_text)?;
+                localize_into_with_qwen(model, ctx, &target_label, &text, &sampling, on_token)
+                    .map(|translation| (translation, truncated))
+            })
+            .await?;
+            track_decode_failures(state, &translation);
+            translation.map_err(|e| format!("LLM Inference Error: {}", e))?
+        }
+    };
+
+    let translation = if protected.is_protected() {
+        protected.restore(&translation)
+    } else {
+        translation
+    };
+
+    Ok(TranslationResponse {
+        language: Language::English.to_string(),
+        target_language: target.to_string(),
+        translation,
+        sentiment: None,
+        toxicity: None,
+        normalized_only: false,
+        truncated,
+        confidence: Some(confidence),
+        detected: true,
+        already_target: false,
+        normalized_input: None,
+        stop_reason: None,
+        debug_timings: None,
+    })
+}
+
+/// Resizes the semaphore to allow `limit` translations at once, clamped to
+/// the context pool size. Returns the limit that was applied.
+pub fn set_concurrency_limit(state: &TranslationModelState, limit: usize) -> Result<usize, String> {
+    let limit = limit.clamp(1, state.pool_size.load(Ordering::SeqCst));
+    let mut current = state
+        .concurrency_limit
+        .lock()
+        .map_err(|_| "Poisoned lock")?;
+
+    // Without a model there's no semaphore yet; it's created with `limit`
+    let llm_state = state.llm_state.lock().map_err(|_| "Poisoned lock")?.clone();
+    if let Some(llm_state) = llm_state {
+        let semaphore = llm_state.semaphore.clone();
+        if limit > *current {
+            semaphore.add_permits(limit - *current);
+        } else if limit < *current {
+            // Permits in use can't be revoked, so retire them as they come back
+            let excess = (*current - limit) as u32;
+            tauri::async_runtime::spawn(async move {
+                if let Ok(permits) = semaphore.acquire_many_owned(excess).await {
+                    permits.forget();
+                }
+            });
+        }
+    }
+
+    *current = limit;
+    Ok(limit)
+}
+
+pub fn translation_status(state: &TranslationModelState) -> Result<TranslationStatus, String> {
+    Ok(TranslationStatus {
+        in_flight: state.in_flight.load(Ordering::SeqCst),
+        queued: state.queued.load(Ordering::SeqCst),
+        available_permits: state
+            .llm_state
+            .lock()
+            .map_err(|_| "Poisoned lock")?
+            .as_ref()
+            .map_or(0, |llm_state| llm_state.semaphore.available_permits()),
+        concurrency_limit: *state
+            .concurrency_limit
+            .lock()
+            .map_err(|_| "Poisoned lock")?,
+    })
+}
+
+// Counts consecutive decode failures and lowers the concurrency limit when
+// they keep happening.
+fn track_decode_failures<T>(state: &TranslationModelState, result: &Result<T>) {
+    let failed = matches!(result, Err(e) if e.downcast_ref::<DecodeError>().is_some());
+    if !failed {
+        state.decode_failures.store(0, Ordering::SeqCst);
+        return;
+    }
+
+    let failures = state.decode_failures.fetch_add(1, Ordering::SeqCst) + 1;
+    if failures < DECODE_FAILURES_BEFORE_BACKOFF {
+        return;
+    }
+    state.decode_failures.store(0, Ordering::SeqCst);
+
+    let current = match state.concurrency_limit.lock() {
+        Ok(limit) => *limit,
+        Err(_) => return,
+    };
+    if current <= 1 {
+        return;
+    }
+
+    match set_concurrency_limit(state, current - 1) {
+        Ok(limit) => tracing::warn!(
+            "Repeated decode failures, running {} translations at a time",
+            limit
+        ),
+        Err(e) => tracing::error!("Failed to lower concurrency: {}", e),
+    }
+}
+
+// Tracks how long the queue has been backed up and flips fast mode on or off.
+// Returns whether fast mode is on.
+fn update_fast_mode(state: &TranslationModelState) -> bool {
+    let queued = state.queued.load(Ordering::SeqCst);
+    let Ok(mut overloaded_since) = state.overloaded_since.lock() else {
+        return state.fast_mode.load(Ordering::SeqCst);
+    };
+
+    let enabled = next_fast_mode(
+        queued,
+        &mut overloaded_since,
+        state.fast_mode.load(Ordering::SeqCst),
+        Instant::now(),
+    );
+
+    if state.fast_mode.swap(enabled, Ordering::SeqCst) != enabled {
+        if enabled {
+            tracing::warn!("Translator backed up, switching to fast mode");
+        } else {
+            tracing::info!("Backlog cleared, leaving fast mode");
+        }
+        let _ = state
+            .app_handle
+            .emit("fast-mode", &FastModePayload { enabled });
+    }
+
+    enabled
+}
+
+// Whether fast mode should be on with `queued` translations waiting at `now`,
+// given whether it's on already.
+fn next_fast_mode(
+    queued: usize,
+    overloaded_since: &mut Option<Instant>,
+    enabled: bool,
+    now: Instant,
+) -> bool {
+    if queued >= FAST_MODE_QUEUE_DEPTH {
+        let since = *overloaded_since.get_or_insert(now);
+        enabled || now.duration_since(since) >= FAST_MODE_AFTER
+    } else {
+        *overloaded_since = None;
+        queued > 0 && enabled
+    }
+}
+
+// M2M100 runs when it's the chosen engine, and takes over from Qwen in fast
+// mode
+fn uses_m2m100(engine: Engine, fast_mode: bool) -> bool {
+    engine == Engine::M2m100 || fast_mode
+}
+
+// Counts a translation as waiting for a context while alive, emitting
+// `translation-queue` when the queue crosses `QUEUE_BUSY_DEPTH`.
+struct QueuedGuard<'a>(&'a TranslationModelState);
+
+impl<'a> QueuedGuard<'a> {
+    fn new(state: &'a TranslationModelState) -> Self {
+        let depth = state.queued.fetch_add(1, Ordering::SeqCst) + 1;
+        if depth == QUEUE_BUSY_DEPTH {
+            emit_queue_status(state, true, depth);
+        }
+        Self(state)
+    }
+}
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        let depth = self.0.queued.fetch_sub(1, Ordering::SeqCst) - 1;
+        if depth + 1 == QUEUE_BUSY_DEPTH {
+            emit_queue_status(self.0, false, depth);
+        }
+    }
+}
+
+fn emit_queue_status(state: &TranslationModelState, busy: bool, queued: usize) {
+    let _ = state.app_handle.emit(
+        "translation-queue",
+        &TranslationQueuePayload { busy, queued },
+    );
+}
+
+// Counts a translation as running while alive.
+struct InFlightGuard<'a>(&'a AtomicUsize);
+
+impl<'a> InFlightGuard<'a> {
+    fn new(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Borrows a context from the pool for the duration of `job`, which runs on
+/// a blocking thread once a semaphore permit is available. Gives up with
+/// `TRANSLATION_TIMED_OUT` after `translation_timeout_ms`.
+async fn run_with_context<T, F>(
+    state: &TranslationModelState,
+    priority: Priority,
+    job: F,
+) -> Result<T, String>
+where
+    F: FnOnce(&LlamaModel, &mut ThreadSafeContext) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    run_blocking(state, priority, move |llm_state| {
+        let mut ctx = PooledContext::take(&llm_state.context_pool).map_err(|e| e.to_string())?;
+        Ok(job(&llm_state.model, &mut *ctx))
+    })
+    .await
+}
+
+/// Translations waiting to be decoded together in one batch.
+#[derive(Default)]
+pub struct BatchQueue {
+    jobs: Vec<BatchJob>,
+    // Whether a task is already collecting and running batches
+    flushing: bool,
+}
+
+// One message's part of a batch
+struct BatchItem {
+    instructions: String,
+    text: String,
+    think: bool,
+    on_token: TokenCallback,
+}
+
+struct BatchJob {
+    item: BatchItem,
+    reply: oneshot::Sender<Result<(String, bool, StopReason), String>>,
+}
+
+// Queues a translation for the next batch and waits for its translation,
+// whether it was cut to fit and why generation stopped. The first message of
+// a batch starts the task that collects and runs it.
+async fn translate_batched(
+    state: &TranslationModelState,
+    item: BatchItem,
+) -> Result<(String, bool, StopReason), String> {
+    let max_batch_size = settings::current(&state.app_handle).max_batch_size.max(1);
+    let (reply, result) = oneshot::channel();
+
+    let start_flushing = {
+        let mut queue = state.batch_queue.lock().map_err(|_| "Poisoned lock")?;
+        queue.jobs.push(BatchJob { item, reply });
+        // Only a collector already waiting is woken; a stored wakeup would
+        // cut the next batch's window short
+        if queue.jobs.len() >= max_batch_size {
+            state.batch_full.notify_waiters();
+        }
+        !std::mem::replace(&mut queue.flushing, true)
+    };
+
+    if start_flushing {
+        let app_handle = state.app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            flush_batches(&app_handle.state::<TranslationModelState>()).await;
+        });
+    }
+
+    result.await.map_err(|_| "Batch was dropped".to_string())?
+}
+
+// Runs batches until the queue is empty, giving each one `batch_window_ms`
+// to fill up unless it's already full.
+async fn flush_batches(state: &TranslationModelState) {
+    loop {
+        let settings = settings::current(&state.app_handle);
+        let max_batch_size = settings.max_batch_size.max(1);
+        // Taken before the queue is read, so a batch filling up in between
+        // still ends the wait
+        let batch_full = state.batch_full.notified();
+
+        let waiting = {
+            let mut queue = state
+                .batch_queue
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if queue.jobs.is_empty() {
+                queue.flushing = false;
+                return;
+            }
+            queue.jobs.len()
+        };
+
+        if waiting < max_batch_size {
+            let window = Duration::from_millis(settings.batch_window_ms);
+            let _ = tokio::time::timeout(window, batch_full).await;
+        }
+
+        let jobs: Vec<BatchJob> = {
+            let mut queue = state
+                .batch_queue
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let count = queue.jobs.len().min(max_batch_size);
+            queue.jobs.drain(..count).collect()
+        };
+
+        let (items, replies): (Vec<_>, Vec<_>) =
+            jobs.into_iter().map(|job| (job.item, job.reply)).unzip();
+        let sampling = settings.sampling;
+
+        let translations = run_with_batch_context(state, items.len(), move |model, ctx| {
+            localize_batch_with_qwen(model, ctx, items, &sampling)
+        })
+        .await;
+        if let Ok(translations) = &translations {
+            track_decode_failures(state, translations);
+        }
+
+        match translations {
+            Ok(Ok(translations)) => {
+                for (reply, translation) in replies.into_iter().zip(translations) {
+                    let _ = reply.send(Ok(translation));
+                }
+            }
+            Ok(Err(e)) => {
+                let error = format!("LLM Inference Error: {}", e);
+                for reply in replies {
+                    let _ = reply.send(Err(error.clone()));
+                }
+            }
+            Err(e) => {
+                for reply in replies {
+                    let _ = reply.send(Err(e.clone()));
+                }
+            }
+        }
+    }
+}
+
+// Like `run_with_context`, but with the batch context, sized for at least
+// `sequences` messages. A batch counts as one translation against the
+// concurrency limit.
+async fn run_with_batch_context<T, F>(
+    state: &TranslationModelState,
+    sequences: usize,
+    job: F,
+) -> Result<T, String>
+where
+    F: FnOnce(&LlamaModel, &mut ThreadSafeContext) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    run_blocking(state, Priority::Normal, move |llm_state| {
+        let mut batch_context = llm_state.batch_context.lock().unwrap_or_else(|poisoned| {
+            // A batch panicked partway through decoding, so its context is
+            // rebuilt rather than trusted
+            llm_state.batch_context.clear_poison();
+            let mut batch_context = poisoned.into_inner();
+            *batch_context = None;
+            batch_context
+        });
+
+        // Built on first use, since most setups never batch
+        if !batch_context
+            .as_ref()
+            .is_some_and(|(_, capacity)| *capacity >= sequences)
+        {
+            *batch_context = None;
+            let ctx = initialize_batch_context(&llm_state.backend, &llm_state.model, sequences)
+                .map_err(|e| format!("{:#}", e))?;
+            *batch_context = Some((ctx, sequences));
+        }
+
+        let (ctx, _) = batch_context
+            .as_mut()
+            .expect("batch context was just built");
+        Ok(job(&llm_state.model, ctx))
+    })
+    .await
+}
+
+// Runs `job` on a blocking thread once a permit is free, under the
+// configured translation timeout.
+async fn run_blocking<T, F>(
+    state: &TranslationModelState,
+    priority: Priority,
+    job: F,
+) -> Result<T, String>
+where
+    F: FnOnce(&RefiningModelState) -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    // We clone the Arcs here so they can be moved into the spawn_blocking closure
+    let llm_state = state
+        .llm_state
+        .lock()
+        .map_err(|_| "Poisoned lock")?
+        .clone()
+        .ok_or("No translation model loaded")?;
+    let semaphore = llm_state.semaphore.clone();
+
+    // Acquire semaphore (Async wait)
+    let queued = QueuedGuard::new(state);
+    let permit = acquire_permit(state, semaphore, priority).await?;
+    drop(queued);
+    let _in_flight = InFlightGuard::new(&state.in_flight);
+
+    let timeout_ms = settings::current(&state.app_handle).translation_timeout_ms;
+    let timeout = (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms));
+
+    run_with_permit(permit, timeout, move || job(&llm_state)).await
+}
+
+// Runs `job` on a blocking thread, holding `permit` until it's done.
+async fn run_with_permit<T, F>(
+    permit: OwnedSemaphorePermit,
+    timeout: Option<Duration>,
+    job: F,
+) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    // Run inference (Blocking thread)
+    let inference = tauri::async_runtime::spawn_blocking(move || {
+        // The permit is only given back once the context is back in the pool,
+        // even if the caller has stopped waiting
+        let _permit = permit;
+        let _deadline = DeadlineGuard::new(timeout);
+
+        job()
+    });
+
+    // The deadline stops generation between tokens; this covers a single
+    // decode that hangs
+    let joined = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, inference)
+            .await
+            .map_err(|_| TRANSLATION_TIMED_OUT.to_string())?,
+        None => inference.await,
+    };
+
+    // A panic has already put the context back while unwinding
+    match joined {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => Err("Inference panicked".to_string()),
+        Err(e) => Err(format!("Task Join Error: {}", e)),
+    }
+}
+
+// Waits for a slot under `language`'s `max_concurrent` cap, if it has one.
+// It's taken before the global permit, so messages held back by their cap
+// don't sit on contexts other languages could use.
+async fn acquire_language_permit(
+    state: &TranslationModelState,
+    settings: &Settings,
+    language: Language,
+) -> Result<Option<OwnedSemaphorePermit>, String> {
+    let Some(cap) = settings.language_settings(language).max_concurrent else {
+        return Ok(None);
+    };
+    let cap = cap.max(1);
+
+    let semaphore = {
+        let mut semaphores = state
+            .language_semaphores
+            .lock()
+            .map_err(|_| "Poisoned lock")?;
+        let entry = semaphores
+            .entry(language)
+            .or_insert_with(|| (cap, Arc::new(Semaphore::new(cap))));
+        // A changed cap starts over; translations holding the old
+        // semaphore's permits just finish
+        if entry.0 != cap {
+            *entry = (cap, Arc::new(Semaphore::new(cap)));
+        }
+        entry.1.clone()
+    };
+
+    semaphore
+        .acquire_owned()
+        .await
+        .map(Some)
+        .map_err(|e| format!("Semaphore Error: {}", e))
+}
+
+// High-priority translations wait in line like any other, but normal ones
+// that get a permit while one is waiting hand it straight back.
+async fn acquire_permit(
+    state: &TranslationModelState,
+    semaphore: Arc<Semaphore>,
+    priority: Priority,
+) -> Result<OwnedSemaphorePermit, String> {
+    if priority == Priority::High {
+        let _waiting = HighPriorityGuard::new(state);
+        return semaphore
+            .acquire_owned()
+            .await
+            .map_err(|e| format!("Semaphore Error: {}", e));
+    }
+
+    loop {
+        let high_priority_served = state.high_priority_served.notified();
+        let permit = acquire_with_policy(&state.shed_queue, semaphore.clone(), || {
+            settings::current(&state.app_handle)
+        })
+        .await?;
+        if state.high_priority_waiting.load(Ordering::SeqCst) == 0 {
+            return Ok(permit);
+        }
+        drop(permit);
+        high_priority_served.await;
+    }
+}
+
+// Counts a high-priority translation as waiting while alive, waking the
+// normal ones that stepped aside once none are left.
+struct HighPriorityGuard<'a>(&'a TranslationModelState);
+
+impl<'a> HighPriorityGuard<'a> {
+    fn new(state: &'a TranslationModelState) -> Self {
+        state.high_priority_waiting.fetch_add(1, Ordering::SeqCst);
+        Self(state)
+    }
+}
+
+impl Drop for HighPriorityGuard<'_> {
+    fn drop(&mut self) {
+        if self.0.high_priority_waiting.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.high_priority_served.notify_waiters();
+        }
+    }
+}
+
+/// Translations waiting under `ShedOldest`, oldest first, each with a
+/// handle that cancels its wait.
+#[derive(Default)]
+pub struct ShedQueue {
+    waiting: Mutex<std::collections::VecDeque<(usize, oneshot::Sender<()>)>>,
+    next_ticket: AtomicUsize,
+}
+
+// Waits for a permit according to the configured `OverloadPolicy`. The
+// settings are only read once the semaphore turns out to be full.
+async fn acquire_with_policy(
+    shed_queue: &ShedQueue,
+    semaphore: Arc<Semaphore>,
+    settings: impl FnOnce() -> Settings,
+) -> Result<OwnedSemaphorePermit, String> {
+    if let Ok(permit) = semaphore.clone().try_acquire_owned() {
+        return Ok(permit);
+    }
+
+    let settings = settings();
+    let acquire = semaphore.acquire_owned();
+
+    match settings.overload_policy {
+        OverloadPolicy::Wait => acquire.await.map_err(|e| format!("Semaphore Error: {}", e)),
+        OverloadPolicy::Timeout => {
+            tokio::time::timeout(Duration::from_millis(settings.overload_timeout_ms), acquire)
+                .await
+                .map_err(|_| TRANSLATOR_BUSY.to_string())?
+                .map_err(|e| format!("Semaphore Error: {}", e))
+        }
+        OverloadPolicy::ShedOldest => {
+            let ticket = shed_queue.next_ticket.fetch_add(1, Ordering::SeqCst);
+            let (shed_tx, shed_rx) = oneshot::channel();
+            {
+                let mut waiting = shed_queue.waiting.lock().map_err(|_| "Poisoned lock")?;
+                waiting.push_back((ticket, shed_tx));
+                while settings.max_queued_translations > 0
+                    && waiting.len() > settings.max_queued_translations
+                {
+                    if let Some((_, shed)) = waiting.pop_front() {
+                        let _ = shed.send(());
+                    }
+                }
+            }
+
+            let result = tokio::select! {
+                permit = acquire => permit.map_err(|e| format!("Semaphore Error: {}", e)),
+                _ = shed_rx => Err(TRANSLATION_SHED.to_string()),
+            };
+
+            if let Ok(mut waiting) = shed_queue.waiting.lock() {
+                waiting.retain(|(waiting_ticket, _)| *waiting_ticket != ticket);
+            }
+
+            result
+        }
+    }
+}
+
+// Returns `None` whenever Qwen should handle the message instead: M2M100
+// isn't built in, its files are missing, or it doesn't know the languages.
+// It runs one translation at a time, so messages wait for it under the same
+// overload policy and timeout as for Qwen.
+async fn translate_with_m2m100(
+    state: &TranslationModelState,
+    text: String,
+    source: Language,
+    target: Language,
+    priority: Priority,
+) -> Result<Option<String>, String> {
+    // Loaded once, however many messages ask for it at the same time
+    let engine = state
+        .m2m100
+        .get_or_init(|| async {
+            let app_handle = state.app_handle.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                M2m100Engine::load(&app_handle)
+                    .map_err(|e| tracing::warn!("M2M100 unavailable, using Qwen: {:#}", e))
+                    .ok()
+                    .map(Arc::new)
+            })
+            .await
+            .ok()
+            .flatten()
+        })
+        .await
+        .clone();
+    let Some(engine) = engine else {
+        return Ok(None);
+    };
+
+    let queued = QueuedGuard::new(state);
+    let permit = acquire_permit(state, state.m2m100_semaphore.clone(), priority).await?;
+    drop(queued);
+    let _in_flight = InFlightGuard::new(&state.in_flight);
+
+    let timeout_ms = settings::current(&state.app_handle).translation_timeout_ms;
+    let timeout = (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms));
+
+    run_with_permit(permit, timeout, move || {
+        Ok(engine
+            .translate(&text, source, target)
+            .map_err(|e| tracing::warn!("M2M100 translation failed, using Qwen: {:#}", e))
+            .ok())
+    })
+    .await
+}
+
+/// Error for a message whose language scored below
+/// `min_detection_confidence`.
+pub const LOW_CONFIDENCE_ERROR: &str = "Unknown Language";
+
+// The language a chat message is translated from, with lingua's confidence
+// unless it was forced
+fn detect_source_language(
+    state: &TranslationModelState,
+    settings: &Settings,
+    text: &str,
+    language_hint: Option<Language>,
+    forced_language: Option<Language>,
+) -> Result<(Language, Option<f64>), String> {
+    match forced_language {
+        Some(language) => Ok((language, None)),
+        None => detect_language(&read_detector(state), text, language_hint)
+            .filter(|(_, confidence)| *confidence >= settings.min_detection_confidence)
+            .map(|(language, confidence)| (language, Some(confidence)))
+            .ok_or_else(|| LOW_CONFIDENCE_ERROR.to_string()),
+    }
+}
+
+// Hashtags are set aside before the slang dictionaries can mangle them
+fn normalize_message(language: Language, text: String, preserve_profanity: bool) -> ProtectedText {
+    let mut protected = ProtectedText::new(text);
+    protected.protect_hashtags();
+    if preserve_profanity {
+        // Swears stay as written so the model can translate them faithfully
+        protected.map_unprotected(|segment| {
+            toxicity::map_around_vulgar_terms(segment, |rest| normalize_slang(language, rest))
+        });
+    } else {
+        protected.map_unprotected(|segment| normalize_slang(language, segment));
+    }
+    protected
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct SlangPreview {
+    pub detected_language: String,
+    pub normalized_text: String,
+}
+
+/// What the model would be given for `text`: the language it's detected
+/// as (or `forced_language`) and the slang-normalized text, without
+/// running the model.
+pub fn preview_normalization(
+    state: &TranslationModelState,
+    settings: &Settings,
+    text: &str,
+    forced_language: Option<Language>,
+) -> Result<SlangPreview, String> {
+    let (language, _) = detect_source_language(state, settings, text, None, forced_language)?;
+    let protected = normalize_message(language, text.to_string(), settings.preserve_profanity);
+
+    Ok(SlangPreview {
+        detected_language: language.to_string(),
+        normalized_text: protected.restore(&protected.text),
+    })
+}
+
+/// What language detection makes of a message, for the frontend.
+#[derive(Clone, Serialize, Debug)]
+pub struct DetectionResult {
+    /// `None` when lingua can't place the text at all, e.g. only emoji.
+    pub language: Option<String>,
+    /// lingua's confidence (0-1) in `language`.
+    pub confidence: Option<f64>,
+    /// Whether the confidence reaches `min_detection_confidence`, i.e.
+    /// whether the bot would translate from `language`.
+    pub meets_threshold: bool,
+}
+
+/// Detects the language of `text` like chat messages are, without a
+/// chatter's history to break close calls and without translating it.
+pub fn detect_message_language(
+    state: &TranslationModelState,
+    settings: &Settings,
+    text: &str,
+) -> DetectionResult {
+    match detect_language(&read_detector(state), text, None) {
+        Some((language, confidence)) => DetectionResult {
+            language: Some(language.to_string()),
+            confidence: Some(confidence),
+            meets_threshold: confidence >= settings.min_detection_confidence,
+        },
+        None => DetectionResult {
+            language: None,
+            confidence: None,
+            meets_threshold: false,
+        },
+    }
+}
+
+// Chat-like samples for `self_test`, one per built-in language. Slang-only
+// messages should pass through untouched, so they have no language.
+const SELF_TEST_SAMPLES: &[(Option<Language>, &str)] = &[
+    (Some(Language::English), "that was a really clean play"),
+    (Some(Language::French), "mdr t'es trop fort, bien joué"),
+    (Some(Language::Japanese), "草、今のプレイやばすぎる"),
+    (Some(Language::Chinese), "哈哈哈这波操作太秀了"),
+    (Some(Language::Korean), "ㅋㅋㅋ 방금 플레이 진짜 대박이다"),
+    (
+        Some(Language::Spanish),
+        "jajaja qué buena jugada, eres un crack",
+    ),
+    (
+        Some(Language::Vietnamese),
+        "trời ơi pha này đỉnh quá anh em",
+    ),
+    (None, "LUL gg"),
+];
+
+/// How one `self_test` sample came out.
+#[derive(Clone, Serialize, Debug)]
+pub struct SelfTestResult {
+    pub sample: String,
+    /// The language the sample is written in; `None` for slang only.
+    pub expected_language: Option<String>,
+    pub detected_language: Option<String>,
+    pub translation: Option<String>,
+    pub passed: bool,
+    /// What went wrong, if the sample failed.
+    pub problem: Option<String>,
+}
+
+/// Translates a fixed sample per enabled language, plus a slang-only one,
+/// with the current settings, checking detection, normalization and the
+/// configured backend end to end. Samples run one at a time so they don't crowd out
+/// live chat.
+pub async fn self_test(state: &TranslationModelState, settings: &Settings) -> Vec<SelfTestResult> {
+    let target = settings.target_language();
+    let enabled = settings.detection_languages();
+    let mut results = Vec::new();
+
+    for &(expected, sample) in SELF_TEST_SAMPLES {
+        // A language that's switched off can't be detected
+        if expected.is_some_and(|language| !enabled.contains(&language)) {
+            continue;
+        }
+
+        let result = perform_translation(
+            sample.to_string(),
+            state,
+            settings,
+            None,
+            None,
+            Direction::ToEnglish,
+            Priority::High,
+        )
+        .await;
+
+        let problem = match &result {
+            Err(e) => Some(e.clone()),
+            Ok(response) => match expected {
+                None if response.translation != sample => {
+                    Some("slang was translated instead of left alone".to_string())
+                }
+                None => None,
+                Some(language) if response.language != language.to_string() => {
+                    Some(format!("detected as {}", response.language))
+                }
+                Some(language) if language == target && response.translation != sample => {
+                    Some("text already in the target language was translated".to_string())
+                }
+                Some(language)
+                    if language != target
+                        && (response.translation.is_empty() || response.translation == sample) =>
+                {
+                    Some("the model returned no translation".to_string())
+                }
+                Some(_) => None,
+            },
+        };
+
+        let response = result.ok();
+        results.push(SelfTestResult {
+            sample: sample.to_string(),
+            expected_language: expected.map(|language| language.to_string()),
+            detected_language: response.as_ref().map(|r| r.language.clone()),
+            translation: response.map(|r| r.translation),
+            passed: problem.is_none(),
+            problem,
+        });
+    }
+
+    results
+}
+
+fn normalize_slang(language: Language, text: &str) -> String {
+    match language {
+        Language::Chinese => slang_zh::normalize_mandarin_slang(text),
+        Language::Japanese => slang_jp::normalize_japanese_slang(text),
+        Language::French => slang_fr::normalize_french_slang(text),
+        Language::Korean => slang_ko::normalize_korean_slang(text),
+        Language::Spanish => slang_es::normalize_spanish_slang(text),
+        Language::Vietnamese => slang_vi::normalize_vietnamese_slang(text),
+        _ => custom_slang::normalize_universal_slang(text),
+    }
+}
+
+/// Detects the language of `text` and how confident lingua is about it (0-1),
+/// letting `hint` (the language this chatter usually writes in) win when a
+/// short message gets a low-confidence guess.
+fn detect_language(
+    detector: &LanguageDetector,
+    text: &str,
+    hint: Option<Language>,
+) -> Option<(Language, f64)> {
+    let confidences = detector.compute_language_confidence_values(text);
+    // Text lingua can't place at all scores zero everywhere
+    let detected = confidences
+        .first()
+        .copied()
+        .filter(|(_, confidence)| *confidence > 0.0);
+
+    let hint = match hint {
+        Some(hint) if text.chars().count() <= SHORT_MESSAGE_CHARS => hint,
+        _ => return detected,
+    };
+
+    let confidence = detected.map_or(0.0, |(_, confidence)| confidence);
+
+    if confidence < UNCERTAIN_CONFIDENCE {
+        let hint_confidence = confidences
+            .iter()
+            .find(|(language, _)| *language == hint)
+            .map_or(0.0, |(_, confidence)| *confidence);
+        Some((hint, hint_confidence))
+    } else {
+        detected
+    }
+}
+
+// Chat slang and emotes that read the same in every language, uppercase.
+static UNIVERSAL_SLANG: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+    HashSet::from([
+        "LMAO",
+        "LMFAO",
+        "LOL",
+        "ROFL",
+        "LUL",
+        "KEKW",
+        "OMEGALUL",
+        "POG",
+        "POGGERS",
+        "POGCHAMP",
+        "KAPPA",
+        "MONKAW",
+        "MONKAS",
+        "PEPELAUGH",
+        "SADGE",
+        "BRUH",
+        "WTF",
+        "OMG",
+        "IDK",
+        "XD",
+        "HA",
+        "HAHA",
+        "HAHAHA",
+        "JAJA",
+        "JAJAJA",
+        "MDR",
+        "L",
+        "FTFY",
+        "ERM",
+        "GG",
+        "GGWP",
+        "GGS",
+    ])
+});
+
+// Terms added at runtime through the universal custom slang, uppercase.
+static CUSTOM_UNIVERSAL_SLANG: Lazy<RwLock<HashSet<String>>> =
+    Lazy::new(|| RwLock::new(HashSet::new()));
+
+/// Replaces the runtime additions to the universal slang set.
+pub fn set_custom_universal_slang(terms: impl IntoIterator<Item = String>) {
+    let terms = terms.into_iter().map(|term| term.to_uppercase()).collect();
+    *CUSTOM_UNIVERSAL_SLANG
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = terms;
+}
+
+/// Whether every word of `text` is language-neutral chat slang ("GG!",
+/// "LUL LUL", "LOOOL"), emoji or emoticons. Runs on every message, so it
+/// must accept any input.
+fn is_universal_slang(text: &str) -> bool {
+    let text = text.trim();
+    if text.is_empty() {
+        return false;
+    }
+
+    let custom = CUSTOM_UNIVERSAL_SLANG
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let is_known = |token: &str| UNIVERSAL_SLANG.contains(token) || custom.contains(token);
+
+    // We split by whitespace to handle messages like "LUL LUL LUL"
+    text.split_whitespace().all(|token| {
+        // Remove punctuation to handle "LMAO!" or "WTF?". Emoji and
+        // emoticons like :) have nothing left and count as universal.
+        let clean_token: String = token
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_uppercase();
+
+        if clean_token.is_empty() {
+            return true;
+        }
+
+        // Drawn-out spellings collapse to the plain one: "LOOOL" and "XDDDD"
+        // to single letters, "POGGGERS" and "GGGG" to double ones
+        is_known(&clean_token)
+            || is_known(&collapse_repeats(&clean_token, 1))
+            || is_known(&collapse_repeats(&clean_token, 2))
+    })
+}
+
+static MENTION: Lazy<Regex> = Lazy::new(|| Regex::new(r"@\w+").unwrap());
+static URL: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\b(?:https?://|www\.)\S+").unwrap());
+
+// Messages need at least this many words left once mentions and links are
+// gone to be worth translating.
+const MIN_CONTENT_WORDS: usize = 1;
+
+/// Whether `text` has no words to translate once @mentions and URLs are
+/// taken out ("@streamer https://clips.twitch.tv/..."). Like
+/// `is_universal_slang`, this runs before anything expensive.
+fn is_mentions_and_links(text: &str) -> bool {
+    // Symbols and numbers alone can still mean something ("666")
+    if !MENTION.is_match(text) && !URL.is_match(text) {
+        return false;
+    }
+
+    let without_mentions = MENTION.replace_all(text, " ");
+    let rest = URL.replace_all(&without_mentions, " ");
+
+    rest.split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphabetic))
+        .count()
+        < MIN_CONTENT_WORDS
+}
+
+/// Whether `text` has fewer characters than `min_chars_to_translate`, or
+/// `min_cjk_chars` if any of them are CJK. Whitespace doesn't count.
+pub fn is_too_short(text: &str, settings: &Settings) -> bool {
+    let chars = text.chars().filter(|c| !c.is_whitespace());
+    let (count, cjk) = chars.fold((0, false), |(count, cjk), c| {
+        (count + 1, cjk || quality::is_cjk(c))
+    });
+
+    let min_chars = if cjk {
+        settings.min_cjk_chars
+    } else {
+        settings.min_chars_to_translate
+    };
+    count < min_chars
+}
+
+// Shortens runs of the same character to at most `max_run`: "LOOOL" -> "LOL"
+// with 1, "POGGGERS" -> "POGGERS" with 2.
+fn collapse_repeats(token: &str, max_run: usize) -> String {
+    let mut collapsed = String::with_capacity(token.len());
+    let mut run = 0;
+    let mut previous = None;
+    for c in token.chars() {
+        run = if previous == Some(c) { run + 1 } else { 1 };
+        previous = Some(c);
+        if run <= max_run {
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    // Tests that run the real model are ignored by default; run them with
+    // TEST_MODEL_PATH pointing at a GGUF file and `cargo test -- --ignored`
+    static TEST_BACKEND: Lazy<Arc<LlamaBackend>> =
+        Lazy::new(|| Arc::new(LlamaBackend::init().expect("Failed to initialize llama.cpp")));
+    static TEST_MODEL: Lazy<(Arc<LlamaModel>, ModelInfo)> = Lazy::new(|| {
+        let path = env::var("TEST_MODEL_PATH").expect("TEST_MODEL_PATH is not set");
+        let (model, info) = load_model_from_file(&TEST_BACKEND, Path::new(&path), Some(0))
+            .expect("Failed to load the test model");
+        (Arc::new(model), info)
+    });
+
+    // Translation state around the test model
+    fn test_state(pool_size: usize) -> TranslationModelState {
+        let (model, model_info) = TEST_MODEL.clone();
+        let llm_state = RefiningModelState::new(
+            TEST_BACKEND.clone(),
+            model,
+            model_info,
+            pool_size,
+            pool_size,
+        )
+        .expect("Failed to build the context pool");
+
+        let mut state = state_without_model();
+        state.backend = Some(TEST_BACKEND.clone());
+        *state.llm_state.get_mut().unwrap() = Some(Arc::new(llm_state));
+        *state.pool_size.get_mut() = pool_size;
+        *state.concurrency_limit.get_mut().unwrap() = pool_size;
+        state
+    }
+
+    // Translation state with no model loaded, on an app that's built but
+    // never run so settings and events have a real `AppHandle` behind them
+    fn state_without_model() -> TranslationModelState {
+        let mut context = tauri::generate_context!();
+        context.config_mut().app.windows.clear();
+        let builder = tauri::Builder::default();
+        #[cfg(any(windows, target_os = "linux"))]
+        let builder = builder.any_thread();
+        let app = builder
+            .build(context)
+            .expect("Failed to build the test app");
+
+        let settings = Settings::default();
+        let detection_languages = settings.detection_languages();
+        app.manage(settings::SettingsState {
+            settings: Mutex::new(settings),
+        });
+
+        TranslationModelState {
+            detector: RwLock::new(initialize_lingua(&detection_languages)),
+            backend: None,
+            llm_state: Mutex::new(None),
+            load_error: Mutex::new(None),
+            pool_size: AtomicUsize::new(1),
+            concurrency_limit: Mutex::new(1),
+            queued: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            shed_queue: ShedQueue::default(),
+            high_priority_waiting: AtomicUsize::new(0),
+            high_priority_served: tokio::sync::Notify::new(),
+            overloaded_since: Mutex::new(None),
+            fast_mode: AtomicBool::new(false),
+            decode_failures: AtomicUsize::new(0),
+            app_handle: app.handle().clone(),
+            m2m100: tokio::sync::OnceCell::new(),
+            m2m100_semaphore: Arc::new(Semaphore::new(1)),
+            cache: Mutex::new(cache::TranslationCache::new(16)),
+            glossary: Mutex::new(None),
+            batch_queue: Mutex::new(BatchQueue::default()),
+            batch_full: tokio::sync::Notify::new(),
+            language_semaphores: Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a GGUF model at TEST_MODEL_PATH"]
+    fn fit_message_cuts_a_5000_token_message() {
+        let text = "hello ".repeat(5000);
+
+        let (fitted, truncated) = fit_message(&*TEST_MODEL.0, &text).unwrap();
+
+        assert!(truncated);
+        assert!(!fitted.is_empty());
+        assert!(fitted.len() < text.len());
+    }
+
+    // One token per byte, so a cut can land inside a character
+    struct ByteTokenizer;
+
+    impl Tokenizer for ByteTokenizer {
+        fn message_tokens(&self, text: &str) -> Result<Vec<LlamaToken>> {
+            Ok(text.bytes().map(|b| LlamaToken::new(b.into())).collect())
+        }
+
+        fn token_bytes(&self, token: LlamaToken) -> Result<Vec<u8>> {
+            Ok(vec![token.0 as u8])
+        }
+    }
+
+    #[test]
+    fn a_5000_token_message_is_cut_to_fit() {
+        let text = "hello ".repeat(1000);
+
+        let (fitted, truncated) = fit_message(&ByteTokenizer, &text).unwrap();
+
+        assert!(truncated);
+        assert_eq!(fitted, text[..MAX_MESSAGE_TOKENS]);
+    }
+
+    #[test]
+    fn a_short_message_is_not_cut() {
+        let (fitted, truncated) = fit_message(&ByteTokenizer, "こんにちは").unwrap();
+
+        assert!(!truncated);
+        assert_eq!(fitted, "こんにちは");
+    }
+
+    #[test]
+    fn a_cut_inside_a_character_drops_the_partial_character() {
+        // 1024 isn't a multiple of 3, so the last kanji is cut in half
+        let text = "日".repeat(2000);
+
+        let (fitted, truncated) = fit_message(&ByteTokenizer, &text).unwrap();
+
+        assert!(truncated);
+        assert_eq!(fitted, "日".repeat(MAX_MESSAGE_TOKENS / 3));
+    }
+
+    #[test]
+    fn a_cut_message_is_flagged_on_the_response() {
+        let (fitted, truncated) = fit_message(&ByteTokenizer, &"hello ".repeat(1000)).unwrap();
+
+        let response = translated_response(
+            Language::Spanish,
+            Language::English,
+            Some(0.9),
+            fitted,
+            ("hello".to_string(), truncated, Some(StopReason::EndOfText)),
+            StageTimings::default(),
+        );
+
+        assert!(response.truncated);
+        assert_eq!(response.translation, "hello");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs a GGUF model at TEST_MODEL_PATH"]
+    async fn fifty_concurrent_translations_give_everything_back() {
+        let state = Arc::new(test_state(4));
+        let llm_state = state.llm_state.lock().unwrap().clone().unwrap();
+        let permits = llm_state.semaphore.available_permits();
+
+        // Far more jobs than contexts, some of them jumping the queue
+        let jobs = (0..50).map(|i| {
+            let state = state.clone();
+            async move {
+                let priority = if i % 10 == 0 {
+                    Priority::High
+                } else {
+                    Priority::Normal
+                };
+                run_with_context(&state, priority, |_, _| {
+                    std::thread::sleep(Duration::from_millis(10))
+                })
+                .await
+            }
+        });
+        let results = futures::future::join_all(jobs).await;
+
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(llm_state.context_pool.lock().unwrap().len(), 4);
+        assert_eq!(llm_state.semaphore.available_permits(), permits);
+        assert_eq!(state.in_flight.load(Ordering::SeqCst), 0);
+        assert_eq!(state.queued.load(Ordering::SeqCst), 0);
+    }
+
+    fn overload_settings(policy: OverloadPolicy, max_queued: usize) -> Settings {
+        Settings {
+            overload_policy: policy,
+            overload_timeout_ms: 20,
+            max_queued_translations: max_queued,
+            ..Settings::default()
+        }
+    }
+
+    // Starts `count` translations waiting on a full `semaphore`, each giving
+    // its permit straight back once it gets one
+    fn spawn_waiters(
+        count: usize,
+        queue: &Arc<ShedQueue>,
+        semaphore: &Arc<Semaphore>,
+        settings: &Settings,
+    ) -> Vec<tokio::task::JoinHandle<Result<(), String>>> {
+        (0..count)
+            .map(|_| {
+                let queue = queue.clone();
+                let semaphore = semaphore.clone();
+                let settings = settings.clone();
+                tokio::spawn(async move {
+                    acquire_with_policy(&queue, semaphore, || settings)
+                        .await
+                        .map(drop)
+                })
+            })
+            .collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn waiting_translations_all_get_a_turn() {
+        let queue = Arc::new(ShedQueue::default());
+        let semaphore = Arc::new(Semaphore::new(1));
+        let busy = semaphore.clone().acquire_owned().await.unwrap();
+
+        let waiters = spawn_waiters(
+            3,
+            &queue,
+            &semaphore,
+            &overload_settings(OverloadPolicy::Wait, 10),
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(busy);
+
+        for waiter in waiters {
+            assert_eq!(waiter.await.unwrap(), Ok(()));
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn a_translation_gives_up_after_the_overload_timeout() {
+        let queue = ShedQueue::default();
+        let semaphore = Arc::new(Semaphore::new(1));
+        let _busy = semaphore.clone().acquire_owned().await.unwrap();
+
+        let settings = overload_settings(OverloadPolicy::Timeout, 10);
+        let result = acquire_with_policy(&queue, semaphore.clone(), || settings).await;
+
+        assert_eq!(result.map(drop), Err(TRANSLATOR_BUSY.to_string()));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn a_free_permit_is_taken_whatever_the_policy() {
+        let queue = ShedQueue::default();
+        let semaphore = Arc::new(Semaphore::new(1));
+
+        let result = acquire_with_policy(&queue, semaphore, || {
+            panic!("settings are only needed when every permit is taken")
+        })
+        .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn the_oldest_waiting_translations_are_shed() {
+        let queue = Arc::new(ShedQueue::default());
+        let semaphore = Arc::new(Semaphore::new(1));
+        let busy = semaphore.clone().acquire_owned().await.unwrap();
+        let settings = overload_settings(OverloadPolicy::ShedOldest, 2);
+
+        let mut waiters = Vec::new();
+        for _ in 0..4 {
+            waiters.extend(spawn_waiters(1, &queue, &semaphore, &settings));
+            // Queued one after the other, so "oldest" is well defined
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        drop(busy);
+
+        let mut results = Vec::new();
+        for waiter in waiters {
+            results.push(waiter.await.unwrap());
+        }
+        let shed = Err(TRANSLATION_SHED.to_string());
+        assert_eq!(results, [shed.clone(), shed, Ok(()), Ok(())]);
+        assert!(queue.waiting.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn a_queue_limit_of_zero_sheds_nothing() {
+        let queue = Arc::new(ShedQueue::default());
+        let semaphore = Arc::new(Semaphore::new(1));
+        let busy = semaphore.clone().acquire_owned().await.unwrap();
+
+        let waiters = spawn_waiters(
+            3,
+            &queue,
+            &semaphore,
+            &overload_settings(OverloadPolicy::ShedOldest, 0),
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(busy);
+
+        for waiter in waiters {
+            assert_eq!(waiter.await.unwrap(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn think_blocks_are_stripped_from_answers() {
+        assert_eq!(strip_think("<think>hmm</think>gg"), "gg");
+        assert_eq!(strip_think("<think>a</think><think>b</think>gg"), "gg");
+        // The opening tag is part of the prompt, so only the end comes back
+        assert_eq!(strip_think("hmm</think>gg"), "gg");
+        assert_eq!(strip_think("gg"), "gg");
+    }
+
+    #[test]
+    fn unclosed_think_blocks_leave_no_reasoning_behind() {
+        assert_eq!(strip_think("<think>still thinking when the limit hit"), "");
+        assert_eq!(strip_think("gg<think>second thoughts"), "gg");
+    }
+
+    #[test]
+    fn a_stray_sentinel_after_a_translation_is_dropped() {
+        assert_eq!(clean_answer("good game <@>", false), "good game");
+        assert_eq!(clean_answer("<think>hmm</think>nice <@>", true), "nice");
+    }
+
+    #[test]
+    fn only_a_bare_sentinel_means_declining() {
+        assert_eq!(clean_answer("<@>", false), "");
+        assert_eq!(clean_answer(" \"<@>\"\n", false), "");
+        assert_eq!(clean_answer("???", false), "???");
+        assert_eq!(clean_answer("!!", false), "!!");
+    }
+
+    #[test]
+    fn trailing_template_tokens_are_dropped() {
+        assert_eq!(sanitize_answer("gg wp<|im_end|>"), "gg wp");
+        assert_eq!(
+            sanitize_answer("gg wp <|im_end|>\n<|im_start|>user"),
+            "gg wp"
+        );
+        assert_eq!(sanitize_answer("gg<|endoftext|>"), "gg");
+    }
+
+    #[test]
+    fn long_answers_fit_in_one_chat_message() {
+        let answer = "word ".repeat(200);
+
+        let sanitized = sanitize_answer(&answer);
+
+        // Cut at the last whole word
+        assert!(sanitized.chars().count() <= MAX_CHAT_MESSAGE_CHARS);
+        assert!(sanitized.ends_with(" word…"));
+    }
+
+    #[test]
+    fn passed_deadline_stops_inference() {
+        let _deadline = DeadlineGuard::new(Some(Duration::ZERO));
+
+        assert!(check_deadline().is_err());
+    }
+
+    #[test]
+    fn deadline_is_cleared_after_a_panic() {
+        let _ = std::panic::catch_unwind(|| {
+            let _deadline = DeadlineGuard::new(Some(Duration::ZERO));
+            panic!("inference panicked");
+        });
+
+        assert!(check_deadline().is_ok());
+    }
+
+    // Stands in for a llama context, remembering whether it was wiped
+    #[derive(Default)]
+    struct FakeContext {
+        dirty: bool,
+    }
+
+    impl PoolItem for FakeContext {
+        fn reset(&mut self) {
+            self.dirty = false;
+        }
+    }
+
+    fn fake_pool(size: usize) -> Arc<Mutex<Vec<FakeContext>>> {
+        Arc::new(Mutex::new(
+            (0..size).map(|_| FakeContext::default()).collect(),
+        ))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn a_failed_or_panicking_job_returns_its_context() {
+        let pool = fake_pool(2);
+        let semaphore = Arc::new(Semaphore::new(2));
+
+        let job_pool = pool.clone();
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        let failed = run_with_permit(permit, None, move || {
+            let mut ctx = PooledContext::take(&*job_pool).map_err(|e| e.to_string())?;
+            ctx.dirty = true;
+            Err::<(), _>("forced failure".to_string())
+        })
+        .await;
+
+        let job_pool = pool.clone();
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        let panicked = run_with_permit(permit, None, move || {
+            let mut ctx = PooledContext::take(&*job_pool).map_err(|e| e.to_string())?;
+            ctx.dirty = true;
+            panic!("forced panic");
+        })
+        .await;
+
+        assert_eq!(failed, Err("forced failure".to_string()));
+        assert_eq!(panicked, Err::<(), _>("Inference panicked".to_string()));
+        let pool = pool.lock().unwrap();
+        assert_eq!(pool.len(), 2);
+        assert!(pool.iter().all(|ctx| !ctx.dirty));
+        assert_eq!(semaphore.available_permits(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn fifty_concurrent_jobs_never_outnumber_the_contexts() {
+        let pool = fake_pool(4);
+        let semaphore = Arc::new(Semaphore::new(4));
+        let queue = ShedQueue::default();
+        let settings = Settings::default();
+
+        // Far more jobs than contexts, some of them failing or panicking
+        let jobs = (0..50).map(|i| {
+            let pool = pool.clone();
+            let semaphore = semaphore.clone();
+            let queue = &queue;
+            let settings = settings.clone();
+            async move {
+                let permit = acquire_with_policy(queue, semaphore, || settings).await?;
+                run_with_permit(permit, None, move || {
+                    let mut ctx = PooledContext::take(&*pool).map_err(|e| e.to_string())?;
+                    ctx.dirty = true;
+                    std::thread::sleep(Duration::from_millis(5));
+                    match i % 10 {
+                        3 => Err("forced failure".to_string()),
+                        7 => panic!("forced panic"),
+                        _ => Ok(()),
+                    }
+                })
+                .await
+            }
+        });
+        let results = futures::future::join_all(jobs).await;
+
+        // A missing context would have failed a job with a pool error
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 40);
+        assert!(results.iter().all(|result| match result {
+            Ok(()) => true,
+            Err(e) => e == "forced failure" || e == "Inference panicked",
+        }));
+        let pool = pool.lock().unwrap();
+        assert_eq!(pool.len(), 4);
+        assert!(pool.iter().all(|ctx| !ctx.dirty));
+        assert_eq!(semaphore.available_permits(), 4);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs a GGUF model at TEST_MODEL_PATH"]
+    async fn failed_inference_returns_its_context() {
+        let state = test_state(2);
+        let llm_state = state.llm_state.lock().unwrap().clone().unwrap();
+
+        let failed = run_blocking(&state, Priority::Normal, |llm_state| {
+            let _ctx = PooledContext::take(&llm_state.context_pool).map_err(|e| e.to_string())?;
+            Err::<(), _>("forced failure".to_string())
+        })
+        .await;
+        let panicked = run_with_context(&state, Priority::Normal, |_, _| {
+            panic!("forced panic");
+        })
+        .await;
+
+        assert_eq!(failed, Err("forced failure".to_string()));
+        assert_eq!(panicked, Err::<(), _>("Inference panicked".to_string()));
+        assert_eq!(llm_state.context_pool.lock().unwrap().len(), 2);
+        assert_eq!(llm_state.semaphore.available_permits(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs a GGUF model at TEST_MODEL_PATH"]
+    async fn a_repeated_message_is_served_from_the_cache() {
+        let state = test_state(1);
+        let settings = Settings::default();
+        let translate = || {
+            perform_translation(
+                "je suis vraiment content de ce match".to_string(),
+                &state,
+                &settings,
+                None,
+                None,
+                Direction::ToEnglish,
+                Priority::Normal,
+            )
+        };
+
+        let first = translate().await.unwrap();
+        // Without a model, anything that reaches `run_blocking` fails
+        *state.llm_state.lock().unwrap() = None;
+        let second = translate().await.unwrap();
+
+        assert_eq!(second.translation, first.translation);
+        assert_eq!(second.language, "French");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn copypasta_is_served_from_the_cache_whoever_posts_it() {
+        // Without a model, anything that gets past the cache fails
+        let state = state_without_model();
+        let settings = Settings::default();
+        let message = "je suis vraiment content de ce match, quelle belle victoire";
+        let key = cache::CacheKey {
+            text: cache::cache_key(
+                &normalize_message(Language::French, format!("#gg {}", message), false).text,
+                settings.cache_key_mode,
+            ),
+            language: Language::French,
+            target: Language::English,
+            system_prompt: None,
+            preserve_profanity: false,
+        };
+        let cached = translated_response(
+            Language::French,
+            Language::English,
+            Some(0.9),
+            String::new(),
+            (
+                "[#0] I'm really happy with this match, what a win".to_string(),
+                false,
+                Some(StopReason::EndOfText),
+            ),
+            StageTimings::default(),
+        );
+        state.cache.lock().unwrap().insert(key, cached);
+
+        // A regular whose language is known, then someone new, each with
+        // their own hashtag
+        let regular = perform_translation(
+            format!("#gg {}", message),
+            &state,
+            &settings,
+            Some(Language::French),
+            None,
+            Direction::ToEnglish,
+            Priority::Normal,
+        )
+        .await
+        .unwrap();
+        let newcomer = perform_translation(
+            format!("#ez {}", message),
+            &state,
+            &settings,
+            None,
+            None,
+            Direction::ToEnglish,
+            Priority::Normal,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            regular.translation,
+            "#gg I'm really happy with this match, what a win"
+        );
+        assert_eq!(
+            newcomer.translation,
+            "#ez I'm really happy with this match, what a win"
+        );
+        assert_eq!(newcomer.language, "French");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn universal_slang_needs_no_translation_whatever_the_target() {
+        let state = state_without_model();
+        let settings = Settings {
+            target_language: "French".to_string(),
+            ..Settings::default()
+        };
+
+        let response = perform_translation(
+            "GG".to_string(),
+            &state,
+            &settings,
+            None,
+            None,
+            Direction::ToEnglish,
+            Priority::Normal,
+        )
+        .await
+        .unwrap();
+
+        assert!(response.already_target);
+        assert_eq!(response.translation, "GG");
+    }
+
+    #[test]
+    fn sentiment_is_read_from_a_one_word_answer() {
+        assert_eq!(parse_sentiment("Positive"), Sentiment::Positive);
+        assert_eq!(parse_sentiment("negative."), Sentiment::Negative);
+        assert_eq!(parse_sentiment("neutral"), Sentiment::Neutral);
+        assert_eq!(parse_sentiment("no idea"), Sentiment::Neutral);
+    }
+
+    fn stream(think: bool, pieces: &[&str]) -> String {
+        let mut answer = AnswerStream::new(think);
+        let mut streamed = String::new();
+        for piece in pieces {
+            answer.forward(piece, |text| streamed.push_str(text));
+        }
+        streamed
+    }
+
+    #[test]
+    fn reasoning_is_not_streamed() {
+        assert_eq!(stream(true, &["hmm", "</think>", "\n\nhello"]), "\n\nhello");
+        assert_eq!(stream(false, &["hello", " there"]), "hello there");
+    }
+
+    #[test]
+    fn a_split_think_tag_still_ends_the_reasoning() {
+        assert_eq!(stream(true, &["hmm</", "think", ">hel", "lo"]), "hello");
+        assert_eq!(
+            stream(true, &["好的", "<", "/", "thi", "nk>", "hello"]),
+            "hello"
+        );
+        assert_eq!(stream(true, &["still thinking"]), "");
+    }
+
+    #[test]
+    fn m2m100_takes_over_once_the_queue_stays_backed_up() {
+        let start = Instant::now();
+        let mut overloaded_since = None;
+
+        // Backed up, but not for long enough yet
+        let fast_mode = next_fast_mode(FAST_MODE_QUEUE_DEPTH, &mut overloaded_since, false, start);
+        assert!(!fast_mode);
+        assert!(!uses_m2m100(Engine::Qwen, fast_mode));
+
+        let later = start + FAST_MODE_AFTER;
+        let fast_mode = next_fast_mode(FAST_MODE_QUEUE_DEPTH, &mut overloaded_since, false, later);
+        assert!(fast_mode);
+        assert!(uses_m2m100(Engine::Qwen, fast_mode));
+
+        // Still draining, so it stays on until the queue is empty
+        let fast_mode = next_fast_mode(1, &mut overloaded_since, fast_mode, later);
+        assert!(fast_mode);
+        let fast_mode = next_fast_mode(0, &mut overloaded_since, fast_mode, later);
+        assert!(!fast_mode);
+        assert!(!uses_m2m100(Engine::Qwen, fast_mode));
+        assert!(uses_m2m100(Engine::M2m100, fast_mode));
+    }
+
+    #[test]
+    fn a_short_backlog_does_not_start_fast_mode() {
+        let start = Instant::now();
+        let mut overloaded_since = None;
+
+        next_fast_mode(FAST_MODE_QUEUE_DEPTH, &mut overloaded_since, false, start);
+        // The queue dipped, so the clock starts over
+        next_fast_mode(1, &mut overloaded_since, false, start);
+        let fast_mode = next_fast_mode(
+            FAST_MODE_QUEUE_DEPTH,
+            &mut overloaded_since,
+            false,
+            start + FAST_MODE_AFTER,
+        );
+
+        assert!(!fast_mode);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn a_job_past_the_timeout_gives_up_but_keeps_its_permit() {
+        let semaphore = Arc::new(Semaphore::new(1));
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+
+        let result = run_with_permit(permit, Some(Duration::from_millis(10)), || {
+            std::thread::sleep(Duration::from_millis(100));
+            Ok(())
+        })
+        .await;
+
+        assert_eq!(result, Err(TRANSLATION_TIMED_OUT.to_string()));
+        // The job is still running, so the permit isn't free yet
+        assert_eq!(semaphore.available_permits(), 0);
+        let _ = semaphore.acquire().await.unwrap();
+    }
+
+    fn temp_model(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}.gguf", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn any_model_is_checked_for_truncation() {
+        let html = temp_model("error-page", &[b'x'; MODEL_MIN_BYTES as usize]);
+        let tiny = temp_model("tiny", b"GGUF");
+
+        let html_problem = truncation_problem(&html);
+        let tiny_problem = truncation_problem(&tiny);
+        std::fs::remove_file(html).unwrap();
+        std::fs::remove_file(tiny).unwrap();
+
+        assert!(html_problem.unwrap().contains("no valid GGUF header"));
+        assert!(tiny_problem.unwrap().contains("is only 4 bytes"));
+    }
+
+    const MIB: u64 = 1024 * 1024;
+
+    #[test]
+    fn a_model_that_fits_is_offloaded_whole() {
+        // 28 blocks plus the output layer, 64 MiB each
+        let model_bytes = 29 * 64 * MIB;
+
+        assert_eq!(
+            estimate_gpu_layers(8 * 1024 * MIB, model_bytes, 28),
+            ALL_GPU_LAYERS
+        );
+    }
+
+    #[test]
+    fn only_the_layers_that_fit_are_offloaded() {
+        let model_bytes = 29 * 64 * MIB;
+
+        // Ten layers' worth after the headroom
+        let free_bytes = VRAM_HEADROOM_BYTES + 10 * 64 * MIB;
+        assert_eq!(estimate_gpu_layers(free_bytes, model_bytes, 28), 10);
+        // Not even the headroom
+        assert_eq!(estimate_gpu_layers(512 * MIB, model_bytes, 28), 0);
+    }
+
+    #[test]
+    fn seeds_keep_their_high_bits() {
+        assert_eq!(fold_seed(7), 7);
+        assert_ne!(fold_seed(1 << 32), fold_seed(1 << 33));
+        assert_ne!(fold_seed(1 << 32), 0);
+    }
+
+    #[test]
+    fn universal_slang_is_recognized() {
+        assert!(is_universal_slang("GG!"));
+        assert!(is_universal_slang("LUL LUL"));
+        assert!(is_universal_slang("LOOOL"));
+        assert!(is_universal_slang("POGGGERS"));
+        assert!(is_universal_slang("GGGG"));
+        assert!(!is_universal_slang("good game"));
+        assert!(!is_universal_slang(""));
+    }
+
+    proptest! {
+        // Runs on every message before detection, so no input may panic it:
+        // emoji, combining marks, right-to-left text, control characters
+        #[test]
+        fn universal_slang_check_never_panics(text in any::<String>()) {
+            is_universal_slang(&text);
+        }
+    }
 }
@@ -0,0 +1,188 @@
+use aho_corasick::AhoCorasick;
+
+/// Text with some spans swapped out for numbered placeholders, so they can
+/// pass through the model untouched and be put back afterwards.
+pub struct ProtectedText {
+    pub text: String,
+    restorations: Vec<String>,
+}
+
+impl ProtectedText {
+    pub fn new(text: String) -> Self {
+        Self {
+            text,
+            restorations: Vec::new(),
+        }
+    }
+
+    pub fn is_protected(&self) -> bool {
+        !self.restorations.is_empty()
+    }
+
+    /// Replaces every match of `ac` with a placeholder that restores to the
+    /// entry of `renderings` for the matched pattern.
+    pub fn protect_matches(&mut self, ac: &AhoCorasick, renderings: &[String]) {
+        let matches: Vec<_> = ac.find_iter(&self.text).collect();
+        if matches.is_empty() {
+            return;
+        }
+
+        let mut protected = String::with_capacity(self.text.len());
+        let mut last = 0;
+        for m in matches {
+            protected.push_str(&self.text[last..m.start()]);
+            let placeholder = self.placeholder(renderings[m.pattern().as_usize()].clone());
+            protected.push_str(&placeholder);
+            last = m.end();
+        }
+        protected.push_str(&self.text[last..]);
+
+        self.text = protected;
+    }
+
+    /// Replaces `#tag` tokens with placeholders so they come back verbatim.
+    pub fn protect_hashtags(&mut self) {
+        let text = std::mem::take(&mut self.text);
+        let mut protected = String::with_capacity(text.len());
+        let mut last = 0;
+
+        for (start, _) in text.match_indices('#') {
+            if start < last {
+                continue;
+            }
+
+            // "C#", "##" and our own "[#0]" placeholders aren't hashtags
+            let before = text[..start].chars().next_back();
+            if before.is_some_and(|c| c.is_alphanumeric() || c == '[' || c == '#') {
+                continue;
+            }
+
+            let len = hashtag_len(&text[start + 1..]);
+            if len == 0 {
+                continue;
+            }
+            let end = start + 1 + len;
+
+            protected.push_str(&text[last..start]);
+            let placeholder = self.placeholder(text[start..end].to_string());
+            protected.push_str(&placeholder);
+            last = end;
+        }
+        protected.push_str(&text[last..]);
+
+        self.text = protected;
+    }
+
+    /// Applies `f` to the text between placeholders, leaving them intact.
+    pub fn map_unprotected(&mut self, f: impl Fn(&str) -> String) {
+        let mut mapped = String::with_capacity(self.text.len());
+        let mut rest = self.text.as_str();
+
+        while let Some((start, end)) = find_placeholder(rest) {
+            mapped.push_str(&f(&rest[..start]));
+            mapped.push_str(&rest[start..end]);
+            rest = &rest[end..];
+        }
+        mapped.push_str(&f(rest));
+
+        self.text = mapped;
+    }
+
+    /// Puts the protected spans back into `translated`.
+    pub fn restore(&self, translated: &str) -> String {
+        let mut restored = translated.to_string();
+        for (i, restoration) in self.restorations.iter().enumerate() {
+            restored = restored.replace(&placeholder_for(i), restoration);
+        }
+        restored
+    }
+
+    fn placeholder(&mut self, restoration: String) -> String {
+        let placeholder = placeholder_for(self.restorations.len());
+        self.restorations.push(restoration);
+        placeholder
+    }
+}
+
+// A tag is a run of word characters. One that starts in ASCII ends at the
+// first non-ASCII character, so "#clipit太强了" only tags "#clipit".
+fn hashtag_len(body: &str) -> usize {
+    let ascii = body.chars().next().is_some_and(|c| c.is_ascii());
+
+    body.chars()
+        .take_while(|c| (c.is_alphanumeric() || *c == '_') && c.is_ascii() == ascii)
+        .map(char::len_utf8)
+        .sum()
+}
+
+// Finds the first "[#<digits>]" in `text`, as a byte range.
+fn find_placeholder(text: &str) -> Option<(usize, usize)> {
+    let mut offset = 0;
+    while let Some(pos) = text[offset..].find("[#") {
+        let start = offset + pos;
+        let digits = text[start + 2..]
+            .bytes()
+            .take_while(u8::is_ascii_digit)
+            .count();
+        let end = start + 2 + digits;
+        if digits > 0 && text[end..].starts_with(']') {
+            return Some((start, end + 1));
+        }
+        offset = start + 2;
+    }
+    None
+}
+
+// Digit-bearing but bracketed, so neither the slang dictionaries (which run
+// first) nor the model are likely to touch it.
+fn placeholder_for(index: usize) -> String {
+    format!("[#{}]", index)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::slang_zh;
+
+    #[test]
+    fn hashtags_skip_slang_normalization() {
+        let mut protected = ProtectedText::new("xswl #xswl".to_string());
+        protected.protect_hashtags();
+        protected.map_unprotected(slang_zh::normalize_mandarin_slang);
+
+        assert_eq!(protected.text, "笑死我了 [#0]");
+        assert_eq!(protected.restore("lmao [#0]"), "lmao #xswl");
+    }
+
+    #[test]
+    fn only_real_hashtags_are_protected() {
+        let mut protected = ProtectedText::new("C# and ## and #clipit太强了".to_string());
+        protected.protect_hashtags();
+
+        assert_eq!(protected.text, "C# and ## and [#0]太强了");
+        assert_eq!(
+            protected.restore(&protected.text),
+            "C# and ## and #clipit太强了"
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn protecting_never_panics(text in any::<String>()) {
+            let mut protected = ProtectedText::new(text);
+            protected.protect_hashtags();
+            protected.map_unprotected(slang_zh::normalize_mandarin_slang);
+            protected.restore(&protected.text);
+        }
+
+        // Text that doesn't already look like a placeholder comes back as it was
+        #[test]
+        fn restoring_undoes_protecting(text in any::<String>().prop_filter("no placeholders", |t| !t.contains("[#"))) {
+            let mut protected = ProtectedText::new(text.clone());
+            protected.protect_hashtags();
+            prop_assert_eq!(protected.restore(&protected.text), text);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use lingua::Language;
+use serde::{Deserialize, Serialize};
+
+// Repetition is only judged on outputs at least this long; "gg gg gg" is a
+// fine translation of a short message
+const MIN_WORDS_FOR_REPETITION: usize = 6;
+const MAX_NGRAM: usize = 3;
+
+/// Heuristics that catch model output not worth posting: a copy of the
+/// input, a loop of repeated words, or text left in the source script.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QualityGate {
+    pub enabled: bool,
+    /// Reject output that is the (normalized) input again.
+    pub reject_copies: bool,
+    /// Reject output where one repeated phrase of up to three words makes
+    /// up at least this share (0-1) of it. 1.0 disables the check.
+    pub max_repetition_share: f64,
+    /// Reject English output whose CJK characters make up more than this
+    /// share (0-1) of its letters. 0.0 rejects any CJK at all, apart from
+    /// @mentions and hashtags, which are kept as written.
+    pub max_cjk_share: f64,
+}
+
+impl Default for QualityGate {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            reject_copies: true,
+            max_repetition_share: 0.5,
+            max_cjk_share: 0.0,
+        }
+    }
+}
+
+impl QualityGate {
+    /// Why `output` shouldn't be posted as the translation of `input`, if
+    /// it shouldn't.
+    pub fn check(&self, input: &str, output: &str, target: Language) -> Option<&'static str> {
+        if !self.enabled {
+            return None;
+        }
+
+        if self.reject_copies && same_text(input, output) {
+            return Some("output is a copy of the input");
+        }
+        if repetition_share(output) >= self.max_repetition_share {
+            return Some("output is dominated by a repeated phrase");
+        }
+        if target == Language::English && cjk_share(output) > self.max_cjk_share {
+            return Some("output still contains CJK text");
+        }
+
+        None
+    }
+}
+
+fn same_text(a: &str, b: &str) -> bool {
+    let normalize = |text: &str| {
+        text.split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    };
+
+    normalize(a) == normalize(b)
+}
+
+// Share of the words covered by the most repeated n-gram, for n up to
+// MAX_NGRAM. A phrase has to appear at least three times to count.
+fn repetition_share(text: &str) -> f64 {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect();
+    if words.len() < MIN_WORDS_FOR_REPETITION {
+        return 0.0;
+    }
+
+    let mut worst = 0.0_f64;
+    for n in 1..=MAX_NGRAM {
+        let mut counts: HashMap<&[String], usize> = HashMap::new();
+        for ngram in words.windows(n) {
+            *counts.entry(ngram).or_default() += 1;
+        }
+
+        if let Some(&count) = counts.values().max() {
+            if count >= 3 {
+                let covered = (count * n).min(words.len());
+                worst = worst.max(covered as f64 / words.len() as f64);
+            }
+        }
+    }
+
+    worst
+}
+
+fn cjk_share(text: &str) -> f64 {
+    // "@小明 nice play" is a good translation; the name isn't the model's to
+    // translate
+    let chars = || {
+        text.split_whitespace()
+            .filter(|word| !word.starts_with(['@', '#']))
+            .flat_map(str::chars)
+    };
+    let letters = chars().filter(|c| c.is_alphabetic()).count();
+    if letters == 0 {
+        return 0.0;
+    }
+    let cjk = chars().filter(|&c| is_cjk(c)).count();
+
+    cjk as f64 / letters as f64
+}
+
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}' // Hiragana, Katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}' // Hangul syllables
+        | '\u{1100}'..='\u{11FF}' // Hangul jamo
+        | '\u{3130}'..='\u{318F}' // Hangul compatibility jamo
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_are_compared_loosely() {
+        assert!(same_text("GG  well played", "gg well played"));
+        assert!(same_text(" hello\n", "hello"));
+        assert!(!same_text("gg", "good game"));
+    }
+
+    #[test]
+    fn repeated_phrases_are_measured() {
+        assert_eq!(repetition_share("so good so good so good"), 1.0);
+        assert_eq!(repetition_share("the the the cat sat on a mat"), 3.0 / 8.0);
+        assert_eq!(repetition_share("one two three four five six"), 0.0);
+    }
+
+    #[test]
+    fn short_outputs_are_never_repetitive() {
+        // One word short of MIN_WORDS_FOR_REPETITION
+        assert_eq!(repetition_share("gg gg gg gg gg"), 0.0);
+        assert_eq!(repetition_share("gg gg gg gg gg gg"), 1.0);
+    }
+
+    #[test]
+    fn cjk_is_measured_against_all_letters() {
+        assert_eq!(cjk_share("nice play"), 0.0);
+        assert_eq!(cjk_share("好 ab"), 1.0 / 3.0);
+        assert_eq!(cjk_share("你好"), 1.0);
+        assert_eq!(cjk_share("!!!"), 0.0);
+    }
+
+    #[test]
+    fn mentions_and_hashtags_are_not_untranslated_text() {
+        assert_eq!(cjk_share("@小明 nice play"), 0.0);
+        assert_eq!(cjk_share("love it #原神"), 0.0);
+
+        let gate = QualityGate::default();
+        assert_eq!(
+            gate.check("@小明 打得好", "@小明 nice play", Language::English),
+            None
+        );
+        assert!(gate
+            .check("打得好", "打得好 nice", Language::English)
+            .is_some());
+    }
+}
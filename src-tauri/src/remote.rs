@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::model::SamplingParams;
+
+// Shared so every request reuses the same connection pool
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(reqwest::Client::new);
+
+/// An OpenAI-style `/v1/chat/completions` endpoint standing in for the local
+/// model, e.g. a hosted API or llama.cpp's server on another machine.
+pub struct RemoteBackend<'a> {
+    pub base_url: &'a str,
+    pub api_key: &'a str,
+    pub model: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ChatCompletion {
+    choices: Vec<Choice>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: Message,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    content: Option<String>,
+}
+
+impl RemoteBackend<'_> {
+    /// Sends one system + user exchange and returns the reply. `timeout` of
+    /// zero waits as long as the server takes.
+    pub async fn complete(
+        &self,
+        system: &str,
+        user: &str,
+        sampling: &SamplingParams,
+        max_tokens: usize,
+        timeout: Duration,
+    ) -> Result<String, String> {
+        let url = format!("{}/chat/completions", self.base_url.trim_end_matches('/'));
+        let body = json!({
+            "model": self.model,
+            "messages": [
+                { "role": "system", "content": system },
+                { "role": "user", "content": user },
+            ],
+            "temperature": sampling.temperature,
+            "top_p": sampling.top_p,
+            "max_tokens": max_tokens,
+        });
+
+        let mut request = CLIENT
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string());
+        if !self.api_key.is_empty() {
+            request = request.bearer_auth(self.api_key);
+        }
+        if !timeout.is_zero() {
+            request = request.timeout(timeout);
+        }
+
+        let response = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Remote backend request failed: {}", e))?;
+        let text = response.text().await.map_err(|e| e.to_string())?;
+        let completion: ChatCompletion = serde_json::from_str(&text)
+            .map_err(|e| format!("Unexpected response from remote backend: {}", e))?;
+
+        completion
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .ok_or_else(|| "Remote backend returned no completion".to_string())
+    }
+}
@@ -0,0 +1,41 @@
+use pinyin::ToPinyin;
+use wana_kana::ConvertJapanese;
+
+/// Romanizes CJK characters in a display name so English-reading mods can
+/// pronounce it: pinyin for Han characters, romaji for kana. Everything else
+/// is kept as-is.
+pub fn romanize_name(name: &str) -> String {
+    let mut romanized = String::new();
+    let mut kana_run = String::new();
+
+    for c in name.chars() {
+        if is_kana(c) {
+            kana_run.push(c);
+            continue;
+        }
+
+        push_capitalized(&mut romanized, &kana_run.to_romaji());
+        kana_run.clear();
+
+        match c.to_pinyin() {
+            Some(syllable) => push_capitalized(&mut romanized, syllable.plain()),
+            None => romanized.push(c),
+        }
+    }
+    push_capitalized(&mut romanized, &kana_run.to_romaji());
+
+    romanized
+}
+
+fn is_kana(c: char) -> bool {
+    // Hiragana, Katakana and the prolonged sound mark
+    matches!(c, '\u{3041}'..='\u{309F}' | '\u{30A0}'..='\u{30FF}')
+}
+
+fn push_capitalized(out: &mut String, word: &str) {
+    let mut chars = word.chars();
+    if let Some(first) = chars.next() {
+        out.extend(first.to_uppercase());
+        out.push_str(chars.as_str());
+    }
+}
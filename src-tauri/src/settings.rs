@@ -0,0 +1,434 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use lingua::Language;
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+use tauri_plugin_store::StoreExt;
+
+use crate::bot::TranslationMode;
+use crate::cache::CacheKeyMode;
+use crate::credentials;
+use crate::model::{self, Backend, Engine, OverloadPolicy, SamplingParams};
+use crate::quality::QualityGate;
+use crate::STORE_PATH;
+
+// The remote backend's API key is kept in the credential store under this
+// key, never in the settings file
+const REMOTE_API_KEY: &str = "remote_api_key";
+
+/// Chat bots whose command output and timers aren't worth translating.
+pub const DEFAULT_BOT_ACCOUNTS: &[&str] = &[
+    "nightbot",
+    "streamelements",
+    "moobot",
+    "fossabot",
+    "streamlabs",
+    "wizebot",
+    "botisimo",
+    "deepbot",
+    "coebot",
+    "phantombot",
+    "sery_bot",
+    "soundalerts",
+    "kofistreambot",
+];
+
+/// User-tunable behaviour, persisted next to the Twitch credentials in
+/// `configs.json`. Every field lives under its own top-level key, so a
+/// missing key simply falls back to its default.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// How chat messages are turned into translation cache keys.
+    pub cache_key_mode: CacheKeyMode,
+    /// How many recent translations are kept for repeated messages.
+    pub translation_cache_size: usize,
+    /// Bias short, ambiguous messages toward the language a chatter has
+    /// been using this session.
+    pub learn_user_languages: bool,
+    /// How many recent detections are remembered per chatter.
+    pub user_language_history_size: usize,
+    /// Messages whose detected language scores below this (0-1) are treated
+    /// as unknown and left untranslated.
+    pub min_detection_confidence: f64,
+    /// Messages with fewer characters than this, whitespace aside, are left
+    /// untranslated ("?", "w").
+    pub min_chars_to_translate: usize,
+    /// The same for messages containing CJK, where one character can
+    /// already be a word.
+    pub min_cjk_chars: usize,
+    /// Fall back to the channel's most common language for ambiguous
+    /// messages from chatters we know nothing about.
+    pub use_channel_language_prior: bool,
+    /// Tag each message as positive/neutral/negative with an extra (small)
+    /// inference pass.
+    pub classify_sentiment: bool,
+    /// Score messages for toxicity using the vulgar slang dictionaries.
+    pub score_toxicity: bool,
+    /// Score (0-3) at or above which a `toxic-message` event is emitted.
+    pub toxicity_threshold: u8,
+    /// Romanize CJK display names (pinyin/romaji) in chat replies.
+    pub romanize_usernames: bool,
+    /// Name of the per-game glossary applied to translations, if any.
+    pub active_glossary: Option<String>,
+    /// Fetch BTTV/FFZ/7TV emotes on join and keep them out of translations.
+    pub third_party_emotes: bool,
+    /// Logins of chat bots whose messages are never translated.
+    pub bot_accounts: Vec<String>,
+    /// Logins of chatters whose messages are never translated, on top of
+    /// `bot_accounts`.
+    pub ignored_users: Vec<String>,
+    /// When set, only these chatters' messages are translated, e.g. a few
+    /// regulars who don't write English. Independent of `ignored_users`.
+    pub translate_only_users: Option<Vec<String>>,
+    /// Skip "!command" messages.
+    pub ignore_command_prefix: bool,
+    /// Rejoin the channels the bot was in when the app last closed.
+    pub auto_rejoin: bool,
+    /// How many times in a row a crashed bot is restarted before giving up.
+    pub reconnect_max_attempts: u32,
+    /// OAuth scopes requested when logging in.
+    pub oauth_scopes: Vec<String>,
+    /// Delete our translated reply when a moderator deletes the original.
+    /// Needs the bot to be a moderator with `moderator:manage:chat_messages`.
+    pub delete_retracted_replies: bool,
+    /// Wait this long (ms) for more messages from the same chatter and
+    /// translate them together in one reply. 0 disables it.
+    pub debounce_window_ms: u64,
+    /// Translate the broadcaster's English messages for the rest of chat.
+    pub translate_broadcaster_messages: bool,
+    /// Language the broadcaster's messages are translated into, e.g.
+    /// "French". Defaults to the chat's most common language.
+    pub broadcaster_target_language: Option<String>,
+    /// Language chat is translated into, e.g. "French" for a French streamer.
+    pub target_language: String,
+    /// Languages told apart when detecting what chat is written in, by
+    /// name. The target language is always included.
+    pub enabled_languages: Vec<String>,
+    /// GGUF file loaded as the translation model, e.g. a Q4 quant dropped
+    /// into the model folder. An absolute path works too. Defaults to the
+    /// bundled Qwen model.
+    pub model_filename: Option<String>,
+    /// How many model layers to offload to the GPU. Unset offloads as many
+    /// as fit in free VRAM when a GPU is available; 0 forces CPU mode, e.g.
+    /// for a GPU whose driver crashes llama.cpp.
+    pub gpu_layers: Option<u32>,
+    /// Number of llama contexts kept loaded. Each one costs memory, so more
+    /// only helps on machines with the cores and RAM to run them together.
+    /// Changed with `set_inference_limits`, which rebuilds the pool.
+    pub context_pool_size: usize,
+    /// How many translations may run at once. Never more than the number of
+    /// llama contexts; larger values are clamped.
+    pub max_concurrent_translations: usize,
+    /// What to do with translations when every context is busy.
+    pub overload_policy: OverloadPolicy,
+    /// How long `Timeout` waits for a context before giving up.
+    pub overload_timeout_ms: u64,
+    /// How many translations `ShedOldest` lets wait for a context. 0 lets
+    /// any number wait, like `Wait`.
+    pub max_queued_translations: usize,
+    /// Give up on a translation that runs longer than this (ms), freeing its
+    /// context for other messages. 0 disables it.
+    pub translation_timeout_ms: u64,
+    /// Collect translations arriving within this many ms and decode them
+    /// together, for throughput during chat floods. 0 disables batching.
+    pub batch_window_ms: u64,
+    /// Most translations decoded in one batch. Each one needs its own slice
+    /// of the batch context, so this also sets its memory use.
+    pub max_batch_size: usize,
+    /// Let Qwen reason before translating. Slower, and rarely better for
+    /// short chat messages.
+    pub thinking: bool,
+    /// While the translation queue stays backed up, hand chat to the M2M100
+    /// engine, loading it on the first switch, or skip Qwen's reasoning if
+    /// it isn't available, trading some quality for latency.
+    pub fast_mode_under_load: bool,
+    /// Options for individual source languages, keyed by name ("Chinese").
+    pub language_settings: HashMap<String, LanguageSettings>,
+    /// Quote the original message in replies: "user: original → translation".
+    pub include_original: bool,
+    /// Text in front of every reply, e.g. "(traduction)". Empty for none.
+    pub reply_label: String,
+    /// Reply layout with `{user}`, `{translation}`, `{lang}` and `{original}`
+    /// placeholders, e.g. "[{lang}] {user}: {translation}". `{user}` follows
+    /// `romanize_usernames`; `{user_original}` and `{user_romanized}` are the
+    /// display name as written and romanized, whatever that setting says.
+    /// Replaces `reply_label` and `include_original` when set.
+    pub reply_template: Option<String>,
+    /// Post translations as threaded replies to the original message, or
+    /// as plain chat messages when off.
+    pub reply_as_thread: bool,
+    /// strftime-style pattern for the local time shown next to messages.
+    pub timestamp_format: String,
+    /// At most this many replies are posted per `reply_window_secs`, across
+    /// all channels. Twitch allows 20 per 30 seconds for most accounts.
+    pub max_replies_per_window: u32,
+    /// Length of the reply rate-limit window, in seconds. 0 turns the limit
+    /// off.
+    pub reply_window_secs: u64,
+    /// Drop replies over the rate limit instead of queueing them.
+    pub drop_throttled_replies: bool,
+    /// Post translations to chat, or only show them in the app.
+    pub translation_mode: TranslationMode,
+    /// Model used to translate viewer chat.
+    pub engine: Engine,
+    /// Run Qwen locally or send chat to an OpenAI-compatible API instead.
+    pub backend: Backend,
+    /// Replaces the built-in instructions the model translates chat with.
+    /// `{lang}` stands for the target language. Keep asking for '<@>' on
+    /// text that shouldn't be translated, or links and gibberish get
+    /// translated too.
+    pub system_prompt: Option<String>,
+    /// Translate swears and insults as they are instead of softening them,
+    /// both in the built-in prompt and the slang dictionaries. For
+    /// moderation, where what was actually said matters.
+    pub preserve_profanity: bool,
+    /// Follow Twitch jargon in English messages ("clutch", "copium") with a
+    /// short explanation, for viewers still learning English. Without it,
+    /// English is left alone when the target language is English.
+    pub explain_english_jargon: bool,
+    /// How translations are sampled from the model.
+    pub sampling: SamplingParams,
+    /// Keep a JSON-lines log of every translation (original, normalized
+    /// text, output, language, latency) in the app log dir. Applies on
+    /// restart.
+    pub translation_log: bool,
+    /// How many daily translation log files are kept.
+    pub translation_log_retention_days: usize,
+    /// Checks that keep copies, loops and untranslated output out of chat.
+    pub quality_gate: QualityGate,
+}
+
+/// Options for translating one source language.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LanguageSettings {
+    /// Return the slang-normalized text instead of asking the LLM.
+    pub skip_llm: bool,
+    /// At most this many messages in this language are translated at once,
+    /// so a flood of it can't take every context. Unset for no cap beyond
+    /// `max_concurrent_translations`.
+    pub max_concurrent: Option<usize>,
+}
+
+impl Settings {
+    /// The configured target language, or English if it isn't recognised
+    /// (only possible in a hand-edited settings file; `update_settings`
+    /// rejects unknown names).
+    pub fn target_language(&self) -> Language {
+        self.target_language.parse().unwrap_or(Language::English)
+    }
+
+    /// The `enabled_languages` lingua knows, plus the target language.
+    pub fn detection_languages(&self) -> Vec<Language> {
+        let mut languages: Vec<Language> = self
+            .enabled_languages
+            .iter()
+            .filter_map(|name| name.parse().ok())
+            .collect();
+
+        let target = self.target_language();
+        if !languages.contains(&target) {
+            languages.push(target);
+        }
+        languages
+    }
+
+    pub fn language_settings(&self, language: Language) -> LanguageSettings {
+        self.language_settings
+            .get(&language.to_string())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Whether `login` is one of the `bot_accounts` or `ignored_users`.
+    pub fn ignores_chatter(&self, login: &str) -> bool {
+        self.bot_accounts
+            .iter()
+            .chain(&self.ignored_users)
+            .any(|ignored| ignored.eq_ignore_ascii_case(login))
+    }
+
+    /// A copy with the remote API key blanked out, safe to write to disk or
+    /// hand to the frontend.
+    pub fn redacted(&self) -> Settings {
+        let mut settings = self.clone();
+        if let Backend::OpenAiCompatible { api_key, .. } = &mut settings.backend {
+            api_key.clear();
+        }
+        settings
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            cache_key_mode: CacheKeyMode::Exact,
+            translation_cache_size: 256,
+            learn_user_languages: false,
+            user_language_history_size: 10,
+            min_detection_confidence: 0.0,
+            min_chars_to_translate: 2,
+            min_cjk_chars: 1,
+            use_channel_language_prior: false,
+            classify_sentiment: false,
+            score_toxicity: false,
+            toxicity_threshold: 2,
+            romanize_usernames: false,
+            active_glossary: None,
+            third_party_emotes: false,
+            bot_accounts: DEFAULT_BOT_ACCOUNTS
+                .iter()
+                .map(|login| login.to_string())
+                .collect(),
+            ignored_users: Vec::new(),
+            translate_only_users: None,
+            ignore_command_prefix: true,
+            auto_rejoin: true,
+            reconnect_max_attempts: 5,
+            oauth_scopes: vec!["user:read:chat".to_string(), "user:write:chat".to_string()],
+            delete_retracted_replies: false,
+            debounce_window_ms: 0,
+            translate_broadcaster_messages: false,
+            broadcaster_target_language: None,
+            target_language: "English".to_string(),
+            enabled_languages: model::DEFAULT_LANGUAGES
+                .iter()
+                .map(|language| language.to_string())
+                .collect(),
+            model_filename: None,
+            gpu_layers: None,
+            context_pool_size: 5,
+            max_concurrent_translations: 5,
+            overload_policy: OverloadPolicy::Wait,
+            overload_timeout_ms: 5000,
+            max_queued_translations: 10,
+            translation_timeout_ms: 15000,
+            batch_window_ms: 0,
+            max_batch_size: 4,
+            thinking: false,
+            fast_mode_under_load: false,
+            language_settings: HashMap::new(),
+            include_original: false,
+            reply_label: "(translation)".to_string(),
+            reply_template: None,
+            reply_as_thread: true,
+            timestamp_format: "%H:%M:%S".to_string(),
+            max_replies_per_window: 20,
+            reply_window_secs: 30,
+            drop_throttled_replies: false,
+            translation_mode: TranslationMode::Reply,
+            engine: Engine::Qwen,
+            backend: Backend::Local,
+            system_prompt: None,
+            preserve_profanity: false,
+            explain_english_jargon: false,
+            sampling: SamplingParams::default(),
+            translation_log: false,
+            translation_log_retention_days: 14,
+            quality_gate: QualityGate::default(),
+        }
+    }
+}
+
+pub struct SettingsState {
+    pub settings: Mutex<Settings>,
+}
+
+/// Reads the settings out of the store, ignoring unrelated keys such as the
+/// Twitch credentials. The remote API key is filled in from the credential
+/// store.
+pub fn load(app: &tauri::AppHandle) -> Result<Settings, String> {
+    let store = app.store(STORE_PATH).map_err(|err| err.to_string())?;
+    let entries: serde_json::Map<String, serde_json::Value> = store.entries().into_iter().collect();
+
+    let mut settings: Settings = serde_json::from_value(serde_json::Value::Object(entries))
+        .map_err(|err| err.to_string())?;
+    if let Backend::OpenAiCompatible { api_key, .. } = &settings.backend {
+        // An older version wrote the key to the settings file
+        let in_plain_text = !api_key.is_empty();
+        store_api_key(app, &mut settings)?;
+        if in_plain_text {
+            save(app, &settings)?;
+        }
+    }
+
+    Ok(settings)
+}
+
+/// Moves the remote API key in `settings` to the credential store. The
+/// frontend only ever sees the key redacted, so an empty one keeps the key
+/// already saved; switching back to the local backend forgets it.
+pub fn store_api_key(app: &tauri::AppHandle, settings: &mut Settings) -> Result<(), String> {
+    match &mut settings.backend {
+        Backend::OpenAiCompatible { api_key, .. } if api_key.is_empty() => {
+            *api_key = credentials::load(app, REMOTE_API_KEY)?.unwrap_or_default();
+            Ok(())
+        }
+        Backend::OpenAiCompatible { api_key, .. } => {
+            credentials::save(app, REMOTE_API_KEY, api_key)
+        }
+        Backend::Local => credentials::delete(app, REMOTE_API_KEY),
+    }
+}
+
+fn save(app: &tauri::AppHandle, settings: &Settings) -> Result<(), String> {
+    let store = app.store(STORE_PATH).map_err(|err| err.to_string())?;
+
+    if let serde_json::Value::Object(map) =
+        serde_json::to_value(settings.redacted()).map_err(|err| err.to_string())?
+    {
+        for (key, value) in map {
+            store.set(key, value);
+        }
+    }
+
+    store.save().map_err(|err| err.to_string())
+}
+
+/// Snapshot of the live settings. Falls back to the defaults if the lock is
+/// poisoned so a crashed writer can't take translation down with it.
+pub fn current(app: &tauri::AppHandle) -> Settings {
+    app.state::<SettingsState>()
+        .settings
+        .lock()
+        .map(|settings| settings.clone())
+        .unwrap_or_default()
+}
+
+/// Applies `f` to the live settings and persists the result.
+pub fn update(app: &tauri::AppHandle, f: impl FnOnce(&mut Settings)) -> Result<Settings, String> {
+    let state = app.state::<SettingsState>();
+    let mut settings = state.settings.lock().map_err(|_| "Poisoned lock")?;
+
+    f(&mut settings);
+    save(app, &settings)?;
+
+    Ok(settings.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_chat_bots_are_ignored_by_default() {
+        let settings: Settings = serde_json::from_str("{}").unwrap();
+
+        assert!(settings.ignores_chatter("nightbot"));
+        assert!(settings.ignores_chatter("StreamElements"));
+        assert!(!settings.ignores_chatter("viewer123"));
+    }
+
+    #[test]
+    fn a_saved_bot_list_replaces_the_default_one() {
+        let settings: Settings =
+            serde_json::from_str(r#"{ "bot_accounts": ["mybot"], "ignored_users": ["troll"] }"#)
+                .unwrap();
+
+        assert!(settings.ignores_chatter("MyBot"));
+        assert!(settings.ignores_chatter("troll"));
+        assert!(!settings.ignores_chatter("nightbot"));
+    }
+}
@@ -0,0 +1,200 @@
+use aho_corasick::AhoCorasick;
+
+// Helpers shared by the per-language slang normalizers
+
+/// Whether `text[start..end]` isn't glued to letters or digits on either side.
+pub fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    let before = text[..start].chars().next_back();
+    let after = text[end..].chars().next();
+
+    !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+}
+
+/// Like `AhoCorasick::replace_all`, but skips matches inside a longer word.
+/// Latin-script slang is short enough ("re", "tg") to hide in ordinary words.
+/// A capitalized match keeps its capital ("Mdr" becomes "Mort de rire"),
+/// while all-caps acronyms, the usual way to type them, don't shout: "MDR"
+/// becomes "mort de rire".
+pub fn replace_whole_words(ac: &AhoCorasick, replacements: &[String], text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for m in ac.find_iter(text) {
+        if !is_whole_word(text, m.start(), m.end()) {
+            continue;
+        }
+        result.push_str(&text[last..m.start()]);
+        result.push_str(&match_case(
+            &text[m.start()..m.end()],
+            &replacements[m.pattern().as_usize()],
+        ));
+        last = m.end();
+    }
+    result.push_str(&text[last..]);
+
+    result
+}
+
+fn match_case(matched: &str, replacement: &str) -> String {
+    let mut letters = matched.chars().filter(|c| c.is_alphabetic());
+    let first_upper = letters.next().is_some_and(char::is_uppercase);
+    let rest_lower = letters.all(|c| !c.is_uppercase());
+
+    if first_upper && rest_lower {
+        let mut chars = replacement.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        }
+    } else {
+        replacement.to_string()
+    }
+}
+
+/// Checks `normalize` against a golden file of `input<TAB>expected` lines,
+/// reporting every line that no longer matches. Blank lines and `//`
+/// comments are skipped.
+#[cfg(test)]
+pub fn assert_golden(fixture: &str, normalize: fn(&str) -> String) {
+    let mut mismatches = Vec::new();
+    for (i, line) in fixture.lines().enumerate() {
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+        let (input, expected) = line
+            .split_once('\t')
+            .unwrap_or_else(|| panic!("line {} has no tab: {:?}", i + 1, line));
+        let actual = normalize(input);
+        if actual != expected {
+            mismatches.push(format!(
+                "line {}: {:?} became {:?}, expected {:?}",
+                i + 1,
+                input,
+                actual,
+                expected
+            ));
+        }
+    }
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{slang_en, slang_es, slang_fr, slang_jp, slang_ko, slang_vi, slang_zh};
+
+    // Every built-in dictionary, by name for failure messages
+    fn dictionaries() -> Vec<(&'static str, Vec<(&'static str, &'static str)>)> {
+        vec![
+            ("Mandarin", slang_zh::get_mandarin_slang_dict()),
+            ("Japanese", slang_jp::get_japanese_slang_dict()),
+            ("French", slang_fr::get_french_slang_dict()),
+            ("Korean", slang_ko::get_korean_slang_dict()),
+            ("Spanish", slang_es::get_spanish_slang_dict()),
+            ("Vietnamese", slang_vi::get_vietnamese_slang_dict()),
+            ("English", slang_en::get_english_jargon_dict()),
+        ]
+    }
+
+    fn normalizers() -> Vec<(&'static str, fn(&str) -> String)> {
+        vec![
+            ("Mandarin", slang_zh::normalize_mandarin_slang),
+            ("Japanese", slang_jp::normalize_japanese_slang),
+            ("French", slang_fr::normalize_french_slang),
+            ("Korean", slang_ko::normalize_korean_slang),
+            ("Spanish", slang_es::normalize_spanish_slang),
+            ("Vietnamese", slang_vi::normalize_vietnamese_slang),
+        ]
+    }
+
+    // Slang, what it becomes, and some ordinary chat, for building messages
+    // out of
+    fn corpus() -> Vec<String> {
+        let mut words: Vec<String> = dictionaries()
+            .into_iter()
+            .flat_map(|(_, dict)| dict)
+            .flat_map(|(slang, simple)| [slang.to_string(), simple.to_string()])
+            .collect();
+        words.extend(
+            [
+                "hello",
+                "ok",
+                "!",
+                "?",
+                "草莓",
+                "2021",
+                "比分 3:1",
+                "250 元",
+            ]
+            .map(String::from),
+        );
+        words
+    }
+
+    proptest! {
+        // Matches are cut out of the text by byte offset, so any input must
+        // keep them on character boundaries
+        #[test]
+        fn normalizing_never_panics(text in any::<String>()) {
+            for (_, normalize) in normalizers() {
+                normalize(&text);
+            }
+            slang_en::explain_english_jargon(&text);
+        }
+
+        // A replacement that contains slang itself would be rewritten again,
+        // so normalized text must come through a second pass unchanged
+        #[test]
+        fn normalizing_twice_changes_nothing(
+            words in prop::collection::vec(prop::sample::select(corpus()), 0..8),
+            noise in "\\PC{0,24}",
+        ) {
+            let message = words.join(" ");
+            for (name, normalize) in normalizers() {
+                for input in [message.as_str(), noise.as_str()] {
+                    let once = normalize(input);
+                    prop_assert_eq!(
+                        normalize(&once),
+                        once.clone(),
+                        "{} normalizer isn't idempotent on {:?}",
+                        name,
+                        input
+                    );
+                }
+            }
+        }
+    }
+
+    // The automaton would silently keep only one of two values for a key
+    #[test]
+    fn no_dictionary_maps_a_key_twice() {
+        for (name, dict) in dictionaries() {
+            let mut seen = HashSet::new();
+            for (slang, _) in dict {
+                assert!(seen.insert(slang), "{} maps {:?} twice", name, slang);
+            }
+        }
+    }
+
+    #[test]
+    fn whole_word_needs_no_letter_or_digit_around_it() {
+        assert!(is_whole_word("re !", 0, 2));
+        assert!(is_whole_word("ok, re", 4, 6));
+        assert!(!is_whole_word("vendredi", 4, 6));
+        assert!(!is_whole_word("re2", 0, 2));
+    }
+
+    #[test]
+    fn slang_inside_a_word_is_left_alone() {
+        assert_eq!(slang_fr::normalize_french_slang("vendredi"), "vendredi");
+        assert_eq!(slang_fr::normalize_french_slang("caractère"), "caractère");
+        assert_eq!(
+            slang_fr::normalize_french_slang("re tout le monde"),
+            "rebonjour tout le monde"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use once_cell::sync::Lazy;
+
+use crate::slang;
+
+// Unlike the other dictionaries this one doesn't rewrite anything: English
+// chat is already readable, so each term keeps its place and gets a short
+// gloss after it, for viewers who don't know stream jargon.
+static GLOSSARY: Lazy<(AhoCorasick, Vec<&'static str>)> = Lazy::new(|| {
+    let dict = get_english_jargon_dict();
+    let (terms, glosses): (Vec<_>, Vec<_>) = dict.into_iter().unzip();
+
+    // LeftmostLongest is crucial for "poggers" vs "pog"
+    let ac = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostLongest)
+        .ascii_case_insensitive(true)
+        .build(&terms)
+        .expect("Failed to build Automaton");
+
+    (ac, glosses)
+});
+
+/// Follows each Twitch term in `text` with what it means, e.g. "that was
+/// clutch" becomes "that was clutch (won a tough spot under pressure)".
+/// Only whole words count, and a term repeated in the message is explained
+/// once.
+pub fn explain_english_jargon(text: &str) -> String {
+    let (ac, glosses) = &*GLOSSARY;
+    let mut result = String::with_capacity(text.len());
+    let mut explained = HashSet::new();
+    let mut last = 0;
+
+    for m in ac.find_iter(text) {
+        if !slang::is_whole_word(text, m.start(), m.end()) || !explained.insert(m.pattern()) {
+            continue;
+        }
+        result.push_str(&text[last..m.end()]);
+        result.push_str(" (");
+        result.push_str(glosses[m.pattern().as_usize()]);
+        result.push(')');
+        last = m.end();
+    }
+    result.push_str(&text[last..]);
+
+    result
+}
+
+/// The built-in `(term, gloss)` pairs.
+pub fn get_english_jargon_dict() -> Vec<(&'static str, &'static str)> {
+    let mut map = Vec::new();
+
+    // ==========================================
+    // 1. GAMEPLAY JARGON
+    // ==========================================
+    map.push(("clutch", "won a tough spot under pressure"));
+    map.push(("throwing", "losing a game that was already won"));
+    map.push(("threw", "lost a game that was already won"));
+    map.push(("diff", "one side was far better"));
+    map.push(("gap", "one side was far better"));
+    map.push(("cracked", "extremely skilled"));
+    map.push(("washed", "no longer as good as before"));
+    map.push(("tilted", "frustrated and playing worse"));
+    map.push(("griefing", "ruining the game on purpose"));
+    map.push(("smurf", "skilled player on a new account"));
+    map.push(("nerf", "made weaker by an update"));
+    map.push(("buff", "made stronger by an update"));
+    map.push(("meta", "the strategy everyone uses"));
+    map.push(("scuffed", "glitchy or badly made"));
+    map.push(("speedrun", "finishing as fast as possible"));
+
+    // ==========================================
+    // 2. CHAT SLANG
+    // ==========================================
+    map.push(("copium", "denial to feel better"));
+    map.push(("hopium", "unrealistic hope"));
+    map.push(("malding", "getting very angry"));
+    map.push(("based", "bold and admirable"));
+    map.push(("ratio", "got more support than the post it replies to"));
+    map.push(("backseating", "telling the streamer how to play"));
+    map.push(("lurker", "viewer who watches without chatting"));
+    map.push(("raid", "another channel's viewers arriving at once"));
+
+    // ==========================================
+    // 3. EMOTES
+    // ==========================================
+    map.push(("pog", "amazing"));
+    map.push(("poggers", "amazing"));
+    map.push(("kekw", "laughing hard"));
+    map.push(("omegalul", "laughing hard"));
+    map.push(("lul", "laughing"));
+    map.push(("sadge", "sad"));
+    map.push(("monkas", "nervous"));
+    map.push(("pepega", "foolish"));
+    map.push(("5head", "very clever"));
+    map.push(("residentsleeper", "boring"));
+    map.push(("kappa", "just kidding"));
+
+    map
+}
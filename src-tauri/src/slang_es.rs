@@ -0,0 +1,177 @@
+use std::sync::RwLock;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use once_cell::sync::Lazy;
+
+use crate::slang;
+
+// This preprocessor converts texting abbreviations and regional slang into
+// plain Spanish (Simple, literal logic) to prevent M2M100 hallucinations.
+static SEMANTIC_FLATTENER: Lazy<RwLock<(AhoCorasick, Vec<String>)>> =
+    Lazy::new(|| RwLock::new(build_flattener(&[])));
+
+// User entries go first: of two patterns matching the same text,
+// LeftmostLongest picks the earlier one, so they override built-in ones.
+fn build_flattener(custom: &[(String, String)]) -> (AhoCorasick, Vec<String>) {
+    let builtin = get_spanish_slang_dict();
+    let mapping = custom.iter().cloned().chain(
+        builtin
+            .into_iter()
+            .map(|(slang, simple)| (slang.to_string(), simple.to_string())),
+    );
+
+    let mut patterns = Vec::new();
+    let mut replacements = Vec::new();
+
+    for (slang, simple) in mapping {
+        patterns.push(slang);
+        replacements.push(simple);
+    }
+
+    // LeftmostLongest is crucial for "tqm" vs "tq"
+    let ac = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostLongest)
+        .ascii_case_insensitive(true)
+        .build(&patterns)
+        .expect("Failed to build Automaton");
+
+    (ac, replacements)
+}
+
+/// Rebuilds the dictionary with user-defined `(slang, replacement)` pairs
+/// layered over the built-in ones.
+pub fn set_custom_slang(custom: &[(String, String)]) {
+    let flattener = build_flattener(custom);
+    *SEMANTIC_FLATTENER
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = flattener;
+}
+
+/// Preprocesses Spanish text by replacing texting abbreviations and slang
+/// with standard Spanish suitable for translation models like M2M100.
+///
+/// Like French, only whole words are replaced, so the "q" in "aquí" is
+/// left alone.
+pub fn normalize_spanish_slang(text: &str) -> String {
+    let flattener = SEMANTIC_FLATTENER
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let (ac, replacements) = &*flattener;
+    slang::replace_whole_words(ac, replacements, text)
+}
+
+/// Vulgar slang used to flag toxic messages. Only the slang side counts,
+/// since the replacements are deliberately softened.
+pub fn vulgar_terms() -> Vec<&'static str> {
+    get_spanish_vulgar_slang_dict()
+        .into_iter()
+        .map(|(slang, _)| slang)
+        .collect()
+}
+
+/// The built-in `(slang, replacement)` pairs.
+pub fn get_spanish_slang_dict() -> Vec<(&'static str, &'static str)> {
+    let mut map = Vec::new();
+
+    // ==========================================
+    // 1. TEXTING ABBREVIATIONS
+    // ==========================================
+    map.push(("xq", "porque")); // Because / Why
+    map.push(("pq", "porque")); // Because / Why
+    map.push(("xk", "porque")); // Because / Why
+    map.push(("q", "que")); // That / What
+    map.push(("k", "que")); // That / What
+    map.push(("ke", "que")); // That / What
+    map.push(("tb", "también")); // Also
+    map.push(("tmb", "también")); // Also
+    map.push(("tqm", "te quiero mucho")); // Love you lots
+    map.push(("tkm", "te quiero mucho")); // Love you lots
+    map.push(("xfa", "por favor")); // Please
+    map.push(("porfa", "por favor")); // Please
+    map.push(("ntp", "no te preocupes")); // Don't worry
+    map.push(("salu2", "saludos")); // Greetings
+    map.push(("grax", "gracias")); // Thanks
+    map.push(("dnd", "dónde")); // Where
+    map.push(("msj", "mensaje")); // Message
+    map.push(("bn", "bien")); // Good / Well
+    map.push(("finde", "fin de semana")); // Weekend
+    map.push(("aki", "aquí")); // Here
+
+    // ==========================================
+    // 2. LATIN AMERICA SLANG
+    // ==========================================
+    map.push(("wey", "amigo")); // Dude (Mexico, güey)
+    map.push(("güey", "amigo")); // Dude (Mexico)
+    map.push(("chido", "genial")); // Cool (Mexico)
+    map.push(("neta", "verdad")); // For real (Mexico)
+    map.push(("chamba", "trabajo")); // Work / Job
+    map.push(("pana", "amigo")); // Buddy (Venezuela, Caribbean)
+    map.push(("bacán", "genial")); // Cool (Chile, Peru, Colombia)
+    map.push(("chévere", "genial")); // Cool (Venezuela, Colombia)
+    map.push(("cuate", "amigo")); // Buddy (Mexico)
+    map.push(("nmms", "no puede ser")); // No way (Mexico, "no mames")
+    map.push(("no mames", "no puede ser")); // No way (Mexico)
+
+    // ==========================================
+    // 3. SPAIN SLANG
+    // ==========================================
+    map.push(("guay", "genial")); // Cool
+    map.push(("mola", "me gusta")); // It's cool / I like it
+    map.push(("curro", "trabajo")); // Work / Job
+    map.push(("flipar", "sorprenderse")); // To be amazed
+    map.push(("mogollón", "mucho")); // A lot
+    map.push(("tronco", "amigo")); // Mate / Dude
+
+    // ==========================================
+    // 4. GAMING / INTERNET SPECIFIC
+    // ==========================================
+    map.push(("gg", "bien jugado")); // Good Game
+    map.push(("manco", "mal jugador")); // Bad player
+    map.push(("noob", "novato")); // Beginner
+    map.push(("lag", "retraso")); // Lag
+
+    map.extend(get_spanish_vulgar_slang_dict());
+
+    map
+}
+
+fn get_spanish_vulgar_slang_dict() -> Vec<(&'static str, &'static str)> {
+    let mut map = Vec::new();
+
+    // ==========================================
+    // 5. SWEARS & INSULTS
+    // ==========================================
+    map.push(("mierda", "porquería")); // Shit
+    map.push(("joder", "vaya")); // F*** (Spain, generic exclamation)
+    map.push(("coño", "vaya")); // Damn (Spain, lit. c***)
+    map.push(("gilipollas", "idiota")); // Asshole / Idiot (Spain)
+    map.push(("pendejo", "tonto")); // Idiot (Latin America)
+    map.push(("cabrón", "desgraciado")); // Bastard
+    map.push(("culero", "mala persona")); // Asshole (Mexico)
+    map.push(("pinche", "maldito")); // F***ing (Mexico, intensifier)
+    map.push(("chingada", "maldita sea")); // F*** (Mexico)
+
+    // ==========================================
+    // 6. VULGAR ACRONYMS (TEXTING)
+    // ==========================================
+    map.push(("hdp", "desgraciado")); // Son of a b**** (Hijo de puta)
+    map.push(("ctm", "maldita sea")); // F*** (Chile, concha tu madre)
+    map.push(("ptm", "maldita sea")); // F*** (Mexico/Peru, puta madre)
+    map.push(("alv", "al diablo")); // Screw it (Mexico, a la verga)
+    map.push(("npi", "ni idea")); // No f***ing idea (Ni puta idea)
+
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_golden_file() {
+        crate::slang::assert_golden(
+            include_str!("../fixtures/slang/es.tsv"),
+            normalize_spanish_slang,
+        );
+    }
+}
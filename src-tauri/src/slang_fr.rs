@@ -1,10 +1,24 @@
+use std::sync::RwLock;
+
 use aho_corasick::{AhoCorasick, MatchKind};
 use once_cell::sync::Lazy;
 
-// This preprocessor converts idioms/slang into "Baby Chinese"
+use crate::slang;
+
+// This preprocessor converts idioms/slang into plain French
 // (Simple, literal logic) to prevent M2M100 hallucinations.
-static SEMANTIC_FLATTENER: Lazy<(AhoCorasick, Vec<&'static str>)> = Lazy::new(|| {
-    let mapping = get_french_slang_dict();
+static SEMANTIC_FLATTENER: Lazy<RwLock<(AhoCorasick, Vec<String>)>> =
+    Lazy::new(|| RwLock::new(build_flattener(&[])));
+
+// User entries go first: of two patterns matching the same text,
+// LeftmostLongest picks the earlier one, so they override built-in ones.
+fn build_flattener(custom: &[(String, String)]) -> (AhoCorasick, Vec<String>) {
+    let builtin = get_french_slang_dict();
+    let mapping = custom.iter().cloned().chain(
+        builtin
+            .into_iter()
+            .map(|(slang, simple)| (slang.to_string(), simple.to_string())),
+    );
 
     let mut patterns = Vec::new();
     let mut replacements = Vec::new();
@@ -14,23 +28,52 @@ static SEMANTIC_FLATTENER: Lazy<(AhoCorasick, Vec<&'static str>)> = Lazy::new(||
         replacements.push(simple);
     }
 
-    // LeftmostLongest is crucial for "这波" vs "这波操作"
+    // LeftmostLongest is crucial for "xptdr" vs "ptdr"
     let ac = AhoCorasick::builder()
         .match_kind(MatchKind::LeftmostLongest)
+        .ascii_case_insensitive(true)
         .build(&patterns)
         .expect("Failed to build Automaton");
 
     (ac, replacements)
-});
+}
 
-/// Preprocesses Mandarin text by replacing slang with formal text
-/// suitable for translation models like M2M100.
+/// Rebuilds the dictionary with user-defined `(slang, replacement)` pairs
+/// layered over the built-in ones.
+pub fn set_custom_slang(custom: &[(String, String)]) {
+    let flattener = build_flattener(custom);
+    *SEMANTIC_FLATTENER
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = flattener;
+}
+
+/// Preprocesses French text by replacing texting abbreviations and slang
+/// with standard French suitable for translation models like M2M100.
+///
+/// Matches are leftmost-longest and never overlap, so "xptdr" becomes
+/// "explosé de rire" rather than "x" + "pété de rire". Replacement text is
+/// not scanned again, and only whole words are replaced, so the "re" in
+/// "vendredi" is left alone. Matching ignores ASCII case, so "MDR" and
+/// "Mdr" are caught too.
 pub fn normalize_french_slang(text: &str) -> String {
-    let (ac, replacements) = &*SEMANTIC_FLATTENER;
-    ac.replace_all(text, replacements)
+    let flattener = SEMANTIC_FLATTENER
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let (ac, replacements) = &*flattener;
+    slang::replace_whole_words(ac, replacements, text)
+}
+
+/// Vulgar slang used to flag toxic messages. Only the slang side counts,
+/// since the replacements are deliberately softened.
+pub fn vulgar_terms() -> Vec<&'static str> {
+    get_french_vulgar_slang_dict()
+        .into_iter()
+        .map(|(slang, _)| slang)
+        .collect()
 }
 
-fn get_french_slang_dict() -> Vec<(&'static str, &'static str)> {
+/// The built-in `(slang, replacement)` pairs.
+pub fn get_french_slang_dict() -> Vec<(&'static str, &'static str)> {
     let mut map = Vec::new();
 
     // ==========================================
@@ -40,8 +83,6 @@ fn get_french_slang_dict() -> Vec<(&'static str, &'static str)> {
     map.push(("ptdr", "pété de rire")); // LMAO (Farting/Broken with laughter)
     map.push(("xptdr", "explosé de rire")); // ROFL
     map.push(("jpp", "je n'en peux plus")); // I can't even / I'm done
-    map.push(("tg", "tais-toi")); // Shut up (Vulgar: Ta gueule)
-    map.push(("ftg", "ferme ta gueule")); // Shut the f*** up
     map.push(("pk", "pourquoi")); // Why
     map.push(("pq", "pourquoi")); // Why (or toilet paper, context dependent)
     map.push(("stp", "s'il te plaît")); // Please
@@ -58,13 +99,9 @@ fn get_french_slang_dict() -> Vec<(&'static str, &'static str)> {
     map.push(("ki", "qui")); // Who
     map.push(("auj", "aujourd'hui")); // Today
     map.push(("a+", "à plus tard")); // See you later
-    map.push(("osef", "on s'en fiche")); // Who cares / We don't care (Vulgar: On s'en fout)
-    map.push(("balek", "je m'en fiche")); // I don't care (Vulgar: Bat les couilles)
     map.push(("oklm", "au calme")); // Chilling / Relaxed
     map.push(("askip", "à ce qu'il parait")); // Apparently / Rumor has it
     map.push(("bg", "beau gosse")); // Handsome guy / Good job
-    map.push(("blc", "je m'en fiche")); // I don't care (Bat les couilles)
-    map.push(("fdp", "imbécile")); // Son of a b**** (Insult, rarely affectionate)
     map.push(("niques", "parents")); // "Nique ta mere" (Your mom) - deeply offensive usually
 
     // ==========================================
@@ -165,6 +202,14 @@ fn get_french_slang_dict() -> Vec<(&'static str, &'static str)> {
     map.push(("camp", "rester statique")); // Camp
     map.push(("rageux", "mauvais perdant")); // Sore loser / Rager
 
+    map.extend(get_french_vulgar_slang_dict());
+
+    map
+}
+
+fn get_french_vulgar_slang_dict() -> Vec<(&'static str, &'static str)> {
+    let mut map = Vec::new();
+
     // ==========================================
     // 6. FRANCE: VULGAR INSULTS & SWEARS
     // ==========================================
@@ -203,36 +248,36 @@ fn get_french_slang_dict() -> Vec<(&'static str, &'static str)> {
     // ==========================================
     // 8. FRANCE: VULGAR ACRONYMS (TEXTING)
     // ==========================================
-    map.push(("fdp", "fils de pute")); // Son of a b****
-    map.push(("ntm", "nique ta mère")); // F*** your mother
-    map.push(("vtff", "va te faire foutre")); // Go f*** yourself
+    map.push(("fdp", "salaud")); // Son of a b****
+    map.push(("ntm", "va te faire voir")); // F*** your mother
+    map.push(("vtff", "va te faire voir")); // Go f*** yourself
     map.push(("tg", "tais-toi")); // Shut the f*** up (Ta gueule)
-    map.push(("ftg", "ferme ta gueule")); // Shut the f*** up
+    map.push(("ftg", "tais-toi")); // Shut the f*** up
     map.push(("raf", "je m'en fiche")); // I don't give a f*** (Rien à foutre)
-    map.push(("osef", "je m'en fiche")); // Who cares (On s'en fout)
+    map.push(("osef", "on s'en fiche")); // Who cares (On s'en fout)
     map.push(("balek", "je m'en fiche")); // Don't give a sh** (Bat les couilles)
     map.push(("blc", "je m'en fiche")); // Don't give a sh** (Bat les couilles)
-    map.push(("oklm", "tranquille")); // Chilling (Au calme - slang)
     map.push(("klm", "tranquille")); // Chilling
 
     // ==========================================
     // 9. QUEBEC: "LES SACRES" (The Church Swears)
     // ==========================================
-    // We map these to "putain" or "merde" so the translation model knows they are expletives.
+    // We map these to the softened "mince" or "zut" so the translation model knows they are
+    // expletives. Mapping them to "putain" or "merde" would need a second pass to soften.
 
     // The "Big Three" (Strongest)
-    map.push(("tabarnak", "putain")); // F*** (Tabernacle) - The ultimate Quebec swear
-    map.push(("calisse", "putain")); // Damn/F*** (Chalice)
-    map.push(("crisse", "putain")); // Christ/Damn
+    map.push(("tabarnak", "mince")); // F*** (Tabernacle) - The ultimate Quebec swear
+    map.push(("calisse", "mince")); // Damn/F*** (Chalice)
+    map.push(("crisse", "mince")); // Christ/Damn
 
     // Medium Intensity
-    map.push(("osti", "merde")); // Shit/Damn (Host)
-    map.push(("ostie", "merde")); // Shit/Damn
-    map.push(("astie", "merde")); // Shit/Damn (Variation)
+    map.push(("osti", "zut")); // Shit/Damn (Host)
+    map.push(("ostie", "zut")); // Shit/Damn
+    map.push(("astie", "zut")); // Shit/Damn (Variation)
     map.push(("ciboire", "bordel")); // Ciborium (Damn it)
-    map.push(("viarge", "merde")); // Virgin (Damn)
+    map.push(("viarge", "zut")); // Virgin (Damn)
     map.push(("saint-crème", "mon dieu")); // Holy cream (Soft swear)
-    map.push(("marde", "merde")); // Shit (Pronunciation variant)
+    map.push(("marde", "zut")); // Shit (Pronunciation variant)
 
     // "Softened" Versions (Like "Darn" or "Frick")
     map.push(("tabarouette", "zut")); // Darn (Soft Tabarnak)
@@ -258,3 +303,16 @@ fn get_french_slang_dict() -> Vec<(&'static str, &'static str)> {
 
     map
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_golden_file() {
+        crate::slang::assert_golden(
+            include_str!("../fixtures/slang/fr.tsv"),
+            normalize_french_slang,
+        );
+    }
+}
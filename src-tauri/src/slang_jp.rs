@@ -1,10 +1,22 @@
+use std::sync::RwLock;
+
 use aho_corasick::{AhoCorasick, MatchKind};
 use once_cell::sync::Lazy;
 
-// This preprocessor converts idioms/slang into "Baby Chinese"
+// This preprocessor converts idioms/slang into plain Japanese
 // (Simple, literal logic) to prevent M2M100 hallucinations.
-static SEMANTIC_FLATTENER: Lazy<(AhoCorasick, Vec<&'static str>)> = Lazy::new(|| {
-    let mapping = get_japanese_slang_dict();
+static SEMANTIC_FLATTENER: Lazy<RwLock<(AhoCorasick, Vec<String>)>> =
+    Lazy::new(|| RwLock::new(build_flattener(&[])));
+
+// User entries go first: of two patterns matching the same text,
+// LeftmostLongest picks the earlier one, so they override built-in ones.
+fn build_flattener(custom: &[(String, String)]) -> (AhoCorasick, Vec<String>) {
+    let builtin = get_japanese_slang_dict();
+    let mapping = custom.iter().cloned().chain(
+        builtin
+            .into_iter()
+            .map(|(slang, simple)| (slang.to_string(), simple.to_string())),
+    );
 
     let mut patterns = Vec::new();
     let mut replacements = Vec::new();
@@ -14,23 +26,65 @@ static SEMANTIC_FLATTENER: Lazy<(AhoCorasick, Vec<&'static str>)> = Lazy::new(||
         replacements.push(simple);
     }
 
-    // LeftmostLongest is crucial for "这波" vs "这波操作"
+    // LeftmostLongest is crucial for "www" vs "w"
     let ac = AhoCorasick::builder()
         .match_kind(MatchKind::LeftmostLongest)
         .build(&patterns)
         .expect("Failed to build Automaton");
 
     (ac, replacements)
-});
+}
 
-/// Preprocesses Mandarin text by replacing slang with formal text
-/// suitable for translation models like M2M100.
+/// Rebuilds the dictionary with user-defined `(slang, replacement)` pairs
+/// layered over the built-in ones.
+pub fn set_custom_slang(custom: &[(String, String)]) {
+    let flattener = build_flattener(custom);
+    *SEMANTIC_FLATTENER
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = flattener;
+}
+
+/// Preprocesses Japanese text by replacing net slang with standard
+/// Japanese suitable for translation models like M2M100.
+///
+/// Matches are leftmost-longest and never overlap, so "www" becomes
+/// "大爆笑" rather than three "笑". Replacement text is not scanned again.
+/// A short form that's already followed by the rest of its word is left
+/// alone, so "おめでとう" doesn't become "おめでとうでとう".
 pub fn normalize_japanese_slang(text: &str) -> String {
-    let (ac, replacements) = &*SEMANTIC_FLATTENER;
-    ac.replace_all(text, replacements)
+    let flattener = SEMANTIC_FLATTENER
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let (ac, replacements) = &*flattener;
+
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for m in ac.find_iter(text) {
+        let replacement = &replacements[m.pattern().as_usize()];
+        if text[m.start()..].starts_with(replacement.as_str()) {
+            continue;
+        }
+        result.push_str(&text[last..m.start()]);
+        result.push_str(replacement);
+        last = m.end();
+    }
+    result.push_str(&text[last..]);
+
+    result
+}
+
+/// Vulgar slang used to flag toxic messages. Only the slang side counts,
+/// since many replacements are everyday words (お前, 使えない) outside of slang.
+pub fn vulgar_terms() -> Vec<&'static str> {
+    get_japanese_vulgar_slang_dict()
+        .into_iter()
+        .map(|(slang, _)| slang)
+        .collect()
 }
 
-fn get_japanese_slang_dict() -> Vec<(&'static str, &'static str)> {
+/// The built-in `(slang, replacement)` pairs.
+pub fn get_japanese_slang_dict() -> Vec<(&'static str, &'static str)> {
     let mut map = Vec::new();
 
     // ==========================================
@@ -44,7 +98,6 @@ fn get_japanese_slang_dict() -> Vec<(&'static str, &'static str)> {
     map.push(("kwsk", "詳しく")); // Details please (Kuwashiku)
     map.push(("wktk", "ワクワク")); // Excited (Waku waku teka teka)
     map.push(("ggrks", "ググれ")); // Google it yourself (Googure kasu)
-    map.push(("ks", "カス")); // Trash/Scum (Kasu)
     map.push(("gkbr", "ガクガクブルブル")); // Trembling with fear
     map.push(("ng", "駄目")); // No good / Bad
     map.push(("gj", "よくやった")); // Good Job
@@ -141,6 +194,14 @@ fn get_japanese_slang_dict() -> Vec<(&'static str, &'static str)> {
     map.push(("マウント", "優位を誇示")); // Mount (One-upmanship/Flexing)
     map.push(("クソゲー", "悪いゲーム")); // Kusoge (Shitty game)
 
+    map.extend(get_japanese_vulgar_slang_dict());
+
+    map
+}
+
+fn get_japanese_vulgar_slang_dict() -> Vec<(&'static str, &'static str)> {
+    let mut map = Vec::new();
+
     // ==========================================
     // 6. THE "KUSO" FAMILY (Shit/F***)
     // ==========================================
@@ -210,3 +271,16 @@ fn get_japanese_slang_dict() -> Vec<(&'static str, &'static str)> {
 
     map
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_golden_file() {
+        crate::slang::assert_golden(
+            include_str!("../fixtures/slang/jp.tsv"),
+            normalize_japanese_slang,
+        );
+    }
+}
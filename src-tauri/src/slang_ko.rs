@@ -0,0 +1,177 @@
+use std::sync::RwLock;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use once_cell::sync::Lazy;
+
+// This preprocessor converts net slang and jamo abbreviations into plain
+// Korean (Simple, literal logic) to prevent M2M100 hallucinations.
+static SEMANTIC_FLATTENER: Lazy<RwLock<(AhoCorasick, Vec<String>)>> =
+    Lazy::new(|| RwLock::new(build_flattener(&[])));
+
+// User entries go first: of two patterns matching the same text,
+// LeftmostLongest picks the earlier one, so they override built-in ones.
+fn build_flattener(custom: &[(String, String)]) -> (AhoCorasick, Vec<String>) {
+    let builtin = get_korean_slang_dict();
+    let mapping = custom.iter().cloned().chain(
+        builtin
+            .into_iter()
+            .map(|(slang, simple)| (slang.to_string(), simple.to_string())),
+    );
+
+    let mut patterns = Vec::new();
+    let mut replacements = Vec::new();
+
+    for (slang, simple) in mapping {
+        patterns.push(slang);
+        replacements.push(simple);
+    }
+
+    // LeftmostLongest is crucial for "핵노잼" vs "노잼"
+    let ac = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostLongest)
+        .build(&patterns)
+        .expect("Failed to build Automaton");
+
+    (ac, replacements)
+}
+
+/// Rebuilds the dictionary with user-defined `(slang, replacement)` pairs
+/// layered over the built-in ones.
+pub fn set_custom_slang(custom: &[(String, String)]) {
+    let flattener = build_flattener(custom);
+    *SEMANTIC_FLATTENER
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = flattener;
+}
+
+/// Preprocesses Korean text by replacing net slang and initial-consonant
+/// (choseong) abbreviations with standard Korean suitable for translation
+/// models like M2M100.
+///
+/// Matches are leftmost-longest and never overlap, so "핵노잼" becomes
+/// "정말 재미없음" rather than "핵" + "재미없음". Replacement text is not
+/// scanned again.
+pub fn normalize_korean_slang(text: &str) -> String {
+    let flattener = SEMANTIC_FLATTENER
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let (ac, replacements) = &*flattener;
+    ac.replace_all(text, replacements)
+}
+
+/// Vulgar slang used to flag toxic messages. Only the slang side counts,
+/// since the replacements are deliberately softened.
+pub fn vulgar_terms() -> Vec<&'static str> {
+    get_korean_vulgar_slang_dict()
+        .into_iter()
+        .map(|(slang, _)| slang)
+        .collect()
+}
+
+/// The built-in `(slang, replacement)` pairs.
+pub fn get_korean_slang_dict() -> Vec<(&'static str, &'static str)> {
+    let mut map = Vec::new();
+
+    // ==========================================
+    // 1. JAMO ABBREVIATIONS (CHOSEONG)
+    // ==========================================
+    map.push(("ㅋㅋㅋ", "하하하")); // Lololol (Keke)
+    map.push(("ㅋㅋ", "하하")); // Lol
+    map.push(("ㅎㅎ", "헤헤")); // Hehe (Softer laugh)
+    map.push(("ㅠㅠ", "슬퍼")); // Crying eyes
+    map.push(("ㅜㅜ", "슬퍼")); // Crying eyes
+    map.push(("ㄷㄷ", "덜덜")); // Trembling (Shocked/Scared)
+    map.push(("ㄱㄱ", "가자")); // Let's go (Go go)
+    map.push(("ㅇㅈ", "인정")); // Agreed/Facts (Injeong)
+    map.push(("ㄹㅇ", "진짜")); // For real (Real)
+    map.push(("ㅇㅇ", "응")); // Yeah
+    map.push(("ㄴㄴ", "아니")); // No no
+    map.push(("ㅇㅋ", "알겠어")); // OK
+    map.push(("ㄳ", "감사")); // Thanks (Gamsa)
+    map.push(("ㄱㅅ", "감사")); // Thanks
+    map.push(("ㅈㅅ", "죄송")); // Sorry (Joesong)
+    map.push(("ㅊㅋ", "축하")); // Congrats (Chukha)
+    map.push(("ㅎㅇ", "안녕")); // Hi (Hi)
+    map.push(("ㅂㅂ", "잘 가")); // Bye bye
+    map.push(("ㅃㅇ", "잘 가")); // Bye (Ppai)
+    map.push(("ㅅㄱ", "수고했어")); // Good work (Sugo)
+    map.push(("ㄱㅊ", "괜찮아")); // It's fine (Gwaenchana)
+    map.push(("ㅁㄹ", "몰라")); // Dunno (Molla)
+
+    // ==========================================
+    // 2. FUN / BORING (-잼 FAMILY)
+    // ==========================================
+    map.push(("노잼", "재미없음")); // Boring (No fun)
+    map.push(("핵노잼", "정말 재미없음")); // Super boring (Nuke no fun)
+    map.push(("꿀잼", "정말 재미있음")); // Super fun (Honey fun)
+    map.push(("개꿀잼", "정말 재미있음")); // Super fun
+    map.push(("존잼", "정말 재미있음")); // Super fun
+    map.push(("갓겜", "최고의 게임")); // God game
+
+    // ==========================================
+    // 3. REACTIONS & INTERNET CULTURE
+    // ==========================================
+    map.push(("레게노", "레전드")); // Legend (Famous misspelling)
+    map.push(("킹받네", "열받네")); // So annoying (King-angry)
+    map.push(("실화냐", "진짜야")); // Is this real?
+    map.push(("현타", "현실 자각")); // Reality check (Post-hype emptiness)
+    map.push(("멘붕", "멘탈 붕괴")); // Mental breakdown
+    map.push(("뇌절", "도가 지나침")); // Beating a dead joke
+    map.push(("국룰", "당연한 규칙")); // Unwritten rule (National rule)
+    map.push(("개이득", "큰 이득")); // Huge win/profit
+    map.push(("존버", "끝까지 버티기")); // Holding on to the end
+    map.push(("인싸", "인기 있는 사람")); // Insider/Popular person
+    map.push(("아싸", "외톨이")); // Outsider/Loner
+
+    // ==========================================
+    // 4. GAMING SLANG
+    // ==========================================
+    map.push(("고인물", "숙련자")); // Veteran (Stagnant water)
+    map.push(("뉴비", "초보자")); // Newbie
+    map.push(("트롤", "방해꾼")); // Troll
+    map.push(("캐리", "승리를 이끎")); // Carry
+    map.push(("겜", "게임")); // Game (Abbr)
+    map.push(("한타", "팀 싸움")); // Teamfight (Hanta)
+    map.push(("ㅈㅈ", "항복")); // GG, surrender
+
+    map.extend(get_korean_vulgar_slang_dict());
+
+    map
+}
+
+fn get_korean_vulgar_slang_dict() -> Vec<(&'static str, &'static str)> {
+    let mut map = Vec::new();
+
+    // ==========================================
+    // 5. SWEARING
+    // ==========================================
+    // "시발" is left out on purpose: it's also the start of "시발점" (starting point).
+    map.push(("씨발", "젠장")); // F***
+    map.push(("ㅅㅂ", "젠장")); // F*** (Abbr)
+    map.push(("존나", "정말")); // F***ing (Intensifier)
+    map.push(("ㅈㄴ", "정말")); // F***ing (Abbr)
+    map.push(("병신", "바보")); // Idiot (Slur)
+    map.push(("ㅂㅅ", "바보")); // Idiot (Abbr)
+    map.push(("개새끼", "나쁜 놈")); // Son of a b****
+    map.push(("ㄱㅅㄲ", "나쁜 놈")); // Son of a b**** (Abbr)
+    map.push(("닥쳐", "조용히 해")); // Shut up
+    map.push(("ㄷㅊ", "조용히 해")); // Shut up (Abbr)
+    map.push(("꺼져", "가 버려")); // Get lost
+    map.push(("ㄲㅈ", "가 버려")); // Get lost (Abbr)
+    map.push(("ㅁㅊ", "미친")); // Crazy (Abbr)
+
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_golden_file() {
+        crate::slang::assert_golden(
+            include_str!("../fixtures/slang/ko.tsv"),
+            normalize_korean_slang,
+        );
+    }
+}
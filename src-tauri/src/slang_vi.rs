@@ -0,0 +1,163 @@
+use std::sync::RwLock;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use once_cell::sync::Lazy;
+
+use crate::slang;
+
+// This preprocessor converts "teencode" abbreviations and net slang into
+// plain Vietnamese (Simple, literal logic) to prevent M2M100 hallucinations.
+static SEMANTIC_FLATTENER: Lazy<RwLock<(AhoCorasick, Vec<String>)>> =
+    Lazy::new(|| RwLock::new(build_flattener(&[])));
+
+// User entries go first: of two patterns matching the same text,
+// LeftmostLongest picks the earlier one, so they override built-in ones.
+fn build_flattener(custom: &[(String, String)]) -> (AhoCorasick, Vec<String>) {
+    let builtin = get_vietnamese_slang_dict();
+    let mapping = custom.iter().cloned().chain(
+        builtin
+            .into_iter()
+            .map(|(slang, simple)| (slang.to_string(), simple.to_string())),
+    );
+
+    let mut patterns = Vec::new();
+    let mut replacements = Vec::new();
+
+    for (slang, simple) in mapping {
+        patterns.push(slang);
+        replacements.push(simple);
+    }
+
+    // LeftmostLongest is crucial for "vcl" vs "vl"
+    let ac = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostLongest)
+        .ascii_case_insensitive(true)
+        .build(&patterns)
+        .expect("Failed to build Automaton");
+
+    (ac, replacements)
+}
+
+/// Rebuilds the dictionary with user-defined `(slang, replacement)` pairs
+/// layered over the built-in ones.
+pub fn set_custom_slang(custom: &[(String, String)]) {
+    let flattener = build_flattener(custom);
+    *SEMANTIC_FLATTENER
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = flattener;
+}
+
+/// Preprocesses Vietnamese text by replacing teencode and slang with
+/// standard Vietnamese suitable for translation models like M2M100.
+///
+/// Vietnamese separates syllables with spaces and teencode is mostly one
+/// or two letters, so only whole words are replaced.
+pub fn normalize_vietnamese_slang(text: &str) -> String {
+    let flattener = SEMANTIC_FLATTENER
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let (ac, replacements) = &*flattener;
+    slang::replace_whole_words(ac, replacements, text)
+}
+
+/// Vulgar slang used to flag toxic messages. Only the slang side counts,
+/// since the replacements are deliberately softened.
+pub fn vulgar_terms() -> Vec<&'static str> {
+    get_vietnamese_vulgar_slang_dict()
+        .into_iter()
+        .map(|(slang, _)| slang)
+        .collect()
+}
+
+/// The built-in `(slang, replacement)` pairs.
+pub fn get_vietnamese_slang_dict() -> Vec<(&'static str, &'static str)> {
+    let mut map = Vec::new();
+
+    // ==========================================
+    // 1. TEENCODE ABBREVIATIONS
+    // ==========================================
+    map.push(("ko", "không")); // No / Not
+    map.push(("k", "không")); // No / Not
+    map.push(("kh", "không")); // No / Not
+    map.push(("hok", "không")); // No / Not (Cutesy)
+    map.push(("khum", "không")); // No / Not (Cutesy)
+    map.push(("dc", "được")); // OK / Can
+    map.push(("đc", "được")); // OK / Can
+    map.push(("j", "gì")); // What
+    map.push(("r", "rồi")); // Already
+    map.push(("cx", "cũng")); // Also
+    map.push(("vs", "với")); // With
+    map.push(("ns", "nói")); // Say
+    map.push(("bt", "biết")); // Know
+    map.push(("ntn", "như thế nào")); // How
+    map.push(("mn", "mọi người")); // Everyone
+    map.push(("ae", "anh em")); // Bros / Guys
+    map.push(("ng", "người")); // Person
+    map.push(("trc", "trước")); // Before
+    map.push(("lm", "làm")); // Do
+    map.push(("nma", "nhưng mà")); // But
+    map.push(("hnay", "hôm nay")); // Today
+    map.push(("hqua", "hôm qua")); // Yesterday
+    map.push(("mk", "mình")); // I / Me
+    map.push(("mik", "mình")); // I / Me
+    map.push(("iu", "yêu")); // Love
+    map.push(("thik", "thích")); // Like
+    map.push(("ny", "người yêu")); // Lover / Partner
+    map.push(("ck", "chồng")); // Husband
+    map.push(("vk", "vợ")); // Wife
+    map.push(("ib", "nhắn tin riêng")); // DM (Inbox)
+
+    // ==========================================
+    // 2. NET SLANG
+    // ==========================================
+    map.push(("gato", "ghen tị")); // Jealous (Ghen ăn tức ở)
+    map.push(("trẻ trâu", "trẻ con")); // Immature kid (Young buffalo)
+    map.push(("thả thính", "tán tỉnh")); // Flirt (Drop bait)
+    map.push(("gạch đá", "chỉ trích")); // Criticism (Bricks and stones)
+    map.push(("sống ảo", "khoe khoang trên mạng")); // Living for the 'gram
+
+    // ==========================================
+    // 3. GAMING / INTERNET SPECIFIC
+    // ==========================================
+    map.push(("gg", "chơi hay")); // Good Game
+    map.push(("gà", "chơi kém")); // Bad player (Chicken)
+    map.push(("feed", "chết liên tục")); // Feeding (Dying repeatedly)
+    map.push(("noob", "người mới")); // Beginner
+
+    map.extend(get_vietnamese_vulgar_slang_dict());
+
+    map
+}
+
+fn get_vietnamese_vulgar_slang_dict() -> Vec<(&'static str, &'static str)> {
+    let mut map = Vec::new();
+
+    // ==========================================
+    // 4. SWEARS & VULGAR ACRONYMS
+    // ==========================================
+    map.push(("vl", "quá")); // F***ing (Intensifier, vãi lồn)
+    map.push(("vcl", "quá")); // F***ing (Intensifier, vãi cả lồn)
+    map.push(("vkl", "quá")); // F***ing (Intensifier)
+    map.push(("đm", "chết tiệt")); // F*** (Đ* mẹ)
+    map.push(("dm", "chết tiệt")); // F*** (Đ* mẹ)
+    map.push(("đmm", "chết tiệt")); // F*** your mother
+    map.push(("clgt", "cái gì thế")); // WTF
+    map.push(("đéo", "không")); // F***ing not
+    map.push(("ngu", "ngốc")); // Stupid
+    map.push(("óc chó", "ngốc")); // Dumbass (Dog brain)
+
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_golden_file() {
+        crate::slang::assert_golden(
+            include_str!("../fixtures/slang/vi.tsv"),
+            normalize_vietnamese_slang,
+        );
+    }
+}
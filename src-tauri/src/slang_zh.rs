@@ -1,10 +1,24 @@
+use std::sync::RwLock;
+
 use aho_corasick::{AhoCorasick, MatchKind};
 use once_cell::sync::Lazy;
 
+use crate::slang;
+
 // This preprocessor converts idioms/slang into "Baby Chinese"
 // (Simple, literal logic) to prevent M2M100 hallucinations.
-static SEMANTIC_FLATTENER: Lazy<(AhoCorasick, Vec<&'static str>)> = Lazy::new(|| {
-    let mapping = get_mandarin_slang_dict();
+static SEMANTIC_FLATTENER: Lazy<RwLock<(AhoCorasick, Vec<String>)>> =
+    Lazy::new(|| RwLock::new(build_flattener(&[])));
+
+// User entries go first: of two patterns matching the same text,
+// LeftmostLongest picks the earlier one, so they override built-in ones.
+fn build_flattener(custom: &[(String, String)]) -> (AhoCorasick, Vec<String>) {
+    let builtin = get_mandarin_slang_dict();
+    let mapping = custom.iter().cloned().chain(
+        builtin
+            .into_iter()
+            .map(|(slang, simple)| (slang.to_string(), simple.to_string())),
+    );
 
     let mut patterns = Vec::new();
     let mut replacements = Vec::new();
@@ -21,16 +35,118 @@ static SEMANTIC_FLATTENER: Lazy<(AhoCorasick, Vec<&'static str>)> = Lazy::new(||
         .expect("Failed to build Automaton");
 
     (ac, replacements)
-});
+}
+
+/// Rebuilds the dictionary with user-defined `(slang, replacement)` pairs
+/// layered over the built-in ones.
+pub fn set_custom_slang(custom: &[(String, String)]) {
+    let flattener = build_flattener(custom);
+    *SEMANTIC_FLATTENER
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = flattener;
+}
 
 /// Preprocesses Mandarin text by replacing slang with formal text
 /// suitable for translation models like M2M100.
+///
+/// Matches are leftmost-longest and never overlap, so "886" becomes "再见"
+/// rather than "再见6", and "666" is replaced as a whole. Replacement text is
+/// not scanned again. Keys that also occur inside ordinary words and
+/// numbers are only replaced where they stand alone, so "草莓" and "2021"
+/// are left alone, and so are the numbers in "比分 3:1" and "250 元".
+/// Number slang written straight against hanzi, as in "我觉得666", is left
+/// alone too, since a number joined to hanzi is usually a count or a date.
 pub fn normalize_mandarin_slang(text: &str) -> String {
-    let (ac, replacements) = &*SEMANTIC_FLATTENER;
-    ac.replace_all(text, replacements)
+    let flattener = SEMANTIC_FLATTENER
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let (ac, replacements) = &*flattener;
+
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for m in ac.find_iter(text) {
+        if !is_standalone(text, m.start(), m.end()) {
+            continue;
+        }
+        result.push_str(&text[last..m.start()]);
+        result.push_str(&replacements[m.pattern().as_usize()]);
+        last = m.end();
+    }
+    result.push_str(&text[last..]);
+
+    result
+}
+
+// A numeric key ("1", "250") counts only as a number of its own: see
+// `is_standalone_number`. Any other single character ("草") counts only with
+// no letter, digit or hanzi on either side, and a romanized key ("sb") only
+// with no ASCII letter or digit next to it, since hanzi around it are normal
+// ("我觉得sb"). Longer hanzi keys are specific enough to always count.
+fn is_standalone(text: &str, start: usize, end: usize) -> bool {
+    let key = &text[start..end];
+
+    if key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_digit() || c == '+')
+    {
+        is_standalone_number(text, start, end)
+    } else if key.chars().count() == 1 {
+        slang::is_whole_word(text, start, end)
+    } else if key.is_ascii() {
+        let before = text[..start].chars().next_back();
+        let after = text[end..].chars().next();
+        !before.is_some_and(|c| c.is_ascii_alphanumeric())
+            && !after.is_some_and(|c| c.is_ascii_alphanumeric())
+    } else {
+        true
+    }
+}
+
+// Join digits into one number ("3:1", "1.5", "12/5")
+const NUMBER_SEPARATORS: &[char] = &[':', '：', '.', ',', '/', '-', '+', '~', '*', '=', '×'];
+
+// Make the number before them a quantity ("250 元", "88%"), or the one after
+// them a price or rank ("¥250", "第1")
+const NUMBER_UNITS: &[char] = &[
+    '元', '块', '毛', '角', '分', '秒', '岁', '个', '人', '次', '号', '点', '年', '月', '日', '天',
+    '米', '斤', '万', '千', '百', '亿', '%', '％', '楼', '级', '名', '倍', '票', '局', '比',
+];
+const NUMBER_PREFIXES: &[char] = &['¥', '￥', '$', '#', '第'];
+
+// Number slang ("88", "250") is only meant as slang when it's the whole
+// token: bounded by whitespace, punctuation or the ends of the message, not
+// part of a longer number such as a score, time or price, and not followed
+// by a unit.
+fn is_standalone_number(text: &str, start: usize, end: usize) -> bool {
+    let mut before = text[..start].chars().rev();
+    let mut after = text[end..].chars();
+
+    let joined = |neighbor: Option<char>, beyond: Option<char>| match neighbor {
+        Some(c) if c.is_alphanumeric() => true,
+        Some(c) if NUMBER_SEPARATORS.contains(&c) => beyond.is_some_and(|c| c.is_ascii_digit()),
+        _ => false,
+    };
+    if joined(before.next(), before.next()) || joined(after.next(), after.next()) {
+        return false;
+    }
+
+    let unit = text[end..].trim_start().chars().next();
+    let prefix = text[..start].trim_end().chars().next_back();
+    !unit.is_some_and(|c| NUMBER_UNITS.contains(&c))
+        && !prefix.is_some_and(|c| NUMBER_PREFIXES.contains(&c))
 }
 
-fn get_mandarin_slang_dict() -> Vec<(&'static str, &'static str)> {
+/// Vulgar slang used to flag toxic messages. Only the slang side counts,
+/// since many replacements are everyday words (日, 老子) outside of slang.
+pub fn vulgar_terms() -> Vec<&'static str> {
+    get_mandarin_vulgar_slang_dict()
+        .into_iter()
+        .map(|(slang, _)| slang)
+        .collect()
+}
+
+/// The built-in `(slang, replacement)` pairs.
+pub fn get_mandarin_slang_dict() -> Vec<(&'static str, &'static str)> {
     // Ideally, for a large dataset, use a HashMap or a Perfect Hash Function (phf crate).
     // Sticking to Vec as requested for simple iteration.
     let mut map = Vec::new();
@@ -57,11 +173,8 @@ fn get_mandarin_slang_dict() -> Vec<(&'static str, &'static str)> {
     map.push(("gkd", "搞快点")); // Hurry up
     map.push(("srds", "虽然但是")); // Although... but... (Used to transition topics)
     map.push(("yygq", "阴阳怪气")); // Passive aggressive/Sarcastic
-    map.push(("nb", "厉害")); // Awesome (Vulgar origin, now common)
+    map.push(("nb", "厉害")); // Awesome (Vulgar origin, now common; not counted as toxic)
     map.push(("rnb", "真厉害")); // Really awesome
-    map.push(("woc", "哎呀")); // Fuck/Damn (Exclamation) -> Formal: Oh my
-    map.push(("tm", "他妈")); // Damn it
-    map.push(("md", "妈的")); // Damn it
     map.push(("nss", "暖说说")); // Comment on status to boost it
     map.push(("cp", "情侣/搭档")); // Couple/Pairing
     map.push(("be", "悲剧结局")); // Bad Ending
@@ -77,7 +190,6 @@ fn get_mandarin_slang_dict() -> Vec<(&'static str, &'static str)> {
     map.push(("bjd", "不知道")); // Don't know
     map.push(("jms", "姐妹们")); // Sisters/Girls
     map.push(("xdm", "兄弟们")); // Brothers/Guys
-    map.push(("lz", "楼主")); // Original Poster (OP)
     map.push(("ky", "没眼色")); // Kill the mood (Contextually inappropriate)
     map.push(("zq", "周期")); // Period/Cycle (Context dependent) OR Zao Qi (Good morning)
     map.push(("py", "朋友")); // Friend (or sometimes 'py' deal = dirty deal)
@@ -181,6 +293,8 @@ fn get_mandarin_slang_dict() -> Vec<(&'static str, &'static str)> {
     map.push(("肝帝", "极度努力的玩家")); // Hardcore grinder
     map.push(("二次元", "动漫游文化")); // ACG Subculture (2D World)
     map.push(("现充", "现实生活充实者")); // Normie (Real life fulfilled)
+    map.push(("这波操作", "这个操作")); // This play (praise or mockery of a move)
+    map.push(("这波", "这次")); // This time/This round
 
     // ==========================================
     // 6. PHONETIC & MEME SLANG (SOUND-ALIKES)
@@ -209,6 +323,14 @@ fn get_mandarin_slang_dict() -> Vec<(&'static str, &'static str)> {
     map.push(("纯爱战神", "专一的人")); // Loyal lover
     map.push(("服了", "无奈")); // I give up/Unbelievable
 
+    map.extend(get_mandarin_vulgar_slang_dict());
+
+    map
+}
+
+fn get_mandarin_vulgar_slang_dict() -> Vec<(&'static str, &'static str)> {
+    let mut map = Vec::new();
+
     // ==========================================
     // 8. VULGAR SLANG & SWEAR WORDS (The "Ma" & "B" Families)
     // ==========================================
@@ -216,7 +338,6 @@ fn get_mandarin_slang_dict() -> Vec<(&'static str, &'static str)> {
     map.push(("sb", "傻逼")); // Stupid c*** (Idiot/Moron) - Extremely common
     map.push(("dsb", "大傻逼")); // Big idiot
     map.push(("jb", "鸡巴")); // P*nis (Often used as "Trash" or intensifier like "fucking")
-    map.push(("nb", "牛逼")); // Cow p*ssy (Means: Awesome/Badass) - Vulgar but positive
     map.push(("lb", "老逼")); // Old c*** (Insult for older people)
     map.push(("lowb", "低端/没品")); // Low class/Trashy person
     map.push(("fw", "废物")); // Waste/Loser
@@ -248,7 +369,6 @@ fn get_mandarin_slang_dict() -> Vec<(&'static str, &'static str)> {
     map.push(("ri", "日")); // F*** (Sun)
     map.push(("gun", "滚")); // Get lost / F*** off
     map.push(("gwn", "滚")); // Get lost (Typo/variant)
-    map.push(("qnmd", "去你妈的")); // Screw you / F*** off
     map.push(("yp", "约炮")); // Booty call / Hook up
     map.push(("pyjy", "屁眼交易")); // Dirty deal (An*l trade) - Meme for corruption/backdoor deals
 
@@ -258,14 +378,24 @@ fn get_mandarin_slang_dict() -> Vec<(&'static str, &'static str)> {
     map.push(("gzn", "郭楠")); // "Guo Nan" (Despectful term for Chinese men)
     map.push(("xn", "仙女")); // Fairy (Sarcastic term for entitled women)
     map.push(("xxn", "小仙女")); // Little Fairy (Sarcastic term for "woke" or entitled women)
-    map.push(("nmsl", "你妈死了")); // (Repeated for emphasis)
     map.push(("4000+", "死妈")); // 4000+ (Meme implying someone has no mother)
     map.push(("hsbd", "胡说八道")); // Nonsense / Bullsh**
     map.push(("ntr", "被戴绿帽")); // Cuckold (Netorare)
     map.push(("lz", "老子")); // I/Me (Arrogant: "I, your father")
     map.push(("ye", "爷")); // I/Me (Arrogant: "Grandpa")
-    map.push(("xswl", "笑死我了")); // LMAO (Can be mocking)
-    map.push(("yygq", "阴阳怪气")); // Sarcastic/Passive-aggressive
 
     map
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_golden_file() {
+        crate::slang::assert_golden(
+            include_str!("../fixtures/slang/zh.tsv"),
+            normalize_mandarin_slang,
+        );
+    }
+}
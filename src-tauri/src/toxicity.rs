@@ -0,0 +1,128 @@
+use aho_corasick::{AhoCorasick, MatchKind};
+use once_cell::sync::Lazy;
+
+use crate::slang;
+use crate::slang_es;
+use crate::slang_fr;
+use crate::slang_jp;
+use crate::slang_ko;
+use crate::slang_vi;
+use crate::slang_zh;
+
+/// The highest score `score_toxicity` reports.
+pub const MAX_TOXICITY: u8 = 3;
+
+// Every vulgar entry across the slang dictionaries, in any language. Running
+// one automaton over all of them means we don't need language detection
+// before scoring.
+static VULGAR_TERMS: Lazy<(AhoCorasick, Vec<&'static str>)> = Lazy::new(|| {
+    let mut patterns = Vec::new();
+    patterns.extend(slang_zh::vulgar_terms());
+    patterns.extend(slang_jp::vulgar_terms());
+    patterns.extend(slang_fr::vulgar_terms());
+    patterns.extend(slang_ko::vulgar_terms());
+    patterns.extend(slang_es::vulgar_terms());
+    patterns.extend(slang_vi::vulgar_terms());
+
+    let ac = AhoCorasick::builder()
+        .match_kind(MatchKind::LeftmostLongest)
+        .ascii_case_insensitive(true)
+        .build(&patterns)
+        .expect("Failed to build Automaton");
+
+    (ac, patterns)
+});
+
+/// Rates `text` from 0 (clean) to `MAX_TOXICITY` by counting vulgar slang.
+pub fn score_toxicity(text: &str) -> u8 {
+    let (ac, patterns) = &*VULGAR_TERMS;
+
+    let hits = ac
+        .find_iter(text)
+        .filter(|m| is_vulgar_match(text, m, patterns))
+        .count();
+
+    hits.min(MAX_TOXICITY as usize) as u8
+}
+
+/// Applies `f` to the text around vulgar terms, leaving the terms as they
+/// are. Used to normalize slang without softening swears.
+pub fn map_around_vulgar_terms(text: &str, f: impl Fn(&str) -> String) -> String {
+    let (ac, patterns) = &*VULGAR_TERMS;
+
+    let mut mapped = String::with_capacity(text.len());
+    let mut last = 0;
+    for m in ac
+        .find_iter(text)
+        .filter(|m| is_vulgar_match(text, m, patterns))
+    {
+        mapped.push_str(&f(&text[last..m.start()]));
+        mapped.push_str(&text[m.start()..m.end()]);
+        last = m.end();
+    }
+    mapped.push_str(&f(&text[last..]));
+
+    mapped
+}
+
+fn is_vulgar_match(text: &str, m: &aho_corasick::Match, patterns: &[&str]) -> bool {
+    // Short romanized acronyms like "sb" or "con" would otherwise fire
+    // inside ordinary words, and so would a single hanzi inside a longer
+    // word, the same rule the Mandarin normalizer follows
+    let pattern = patterns[m.pattern().as_usize()];
+    if pattern.is_ascii() || pattern.chars().count() == 1 {
+        slang::is_whole_word(text, m.start(), m.end())
+    } else {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vulgar_slang_is_counted() {
+        assert_eq!(score_toxicity("gg"), 0);
+        assert_eq!(score_toxicity("sb"), 1);
+        assert_eq!(score_toxicity("sb nmsl"), 2);
+        assert_eq!(score_toxicity("kuso baka"), 2);
+    }
+
+    #[test]
+    fn scores_stop_at_the_maximum() {
+        assert_eq!(score_toxicity("sb sb sb sb sb"), MAX_TOXICITY);
+    }
+
+    #[test]
+    fn slang_inside_words_is_not_counted() {
+        assert_eq!(score_toxicity("usb cable"), 0);
+    }
+
+    #[test]
+    fn clean_cjk_sentences_score_zero() {
+        for text in [
+            "今日は日本",
+            "今天是我的生日",
+            "我爷爷来了",
+            "老子说过",
+            "靠近一点",
+            "她像仙女一样",
+            "お前はどう思う",
+            "こいつ強い",
+            "あいつは友達",
+            "気持ち悪いくらい上手い",
+            "使えない技",
+        ] {
+            assert_eq!(score_toxicity(text), 0, "{}", text);
+        }
+    }
+
+    #[test]
+    fn clean_chat_stays_under_the_default_threshold() {
+        let threshold = crate::settings::Settings::default().toxicity_threshold;
+
+        assert!(score_toxicity("今日は日本に行く") < threshold);
+        assert!(score_toxicity("sb nmsl") >= threshold);
+    }
+}
@@ -27,6 +27,8 @@ pub struct ChatWebsocketClient {
     pub connect_url: url::Url,
     /// Chats to connect to.
     pub chats: Vec<twitch_api::types::UserId>,
+    /// Called once a session is welcomed and subscribed to every chat.
+    pub on_connected: Box<dyn Fn() + Send + Sync>,
 }
 
 impl ChatWebsocketClient {
@@ -171,12 +173,45 @@ impl ChatWebsocketClient {
                 .await?;
             self.client
                 .create_eventsub_subscription(
-                    eventsub::channel::chat::ChannelChatNotificationV1::new(id.clone(), user_id),
+                    eventsub::channel::chat::ChannelChatNotificationV1::new(
+                        id.clone(),
+                        user_id.clone(),
+                    ),
+                    transport.clone(),
+                    &*token,
+                )
+                .await?;
+            // Moderation clears and deletions, so the app can drop the same
+            // messages viewers lose
+            self.client
+                .create_eventsub_subscription(
+                    eventsub::channel::chat::ChannelChatMessageDeleteV1::new(
+                        id.clone(),
+                        user_id.clone(),
+                    ),
+                    transport.clone(),
+                    &*token,
+                )
+                .await?;
+            self.client
+                .create_eventsub_subscription(
+                    eventsub::channel::chat::ChannelChatClearV1::new(id.clone(), user_id.clone()),
+                    transport.clone(),
+                    &*token,
+                )
+                .await?;
+            self.client
+                .create_eventsub_subscription(
+                    eventsub::channel::chat::ChannelChatClearUserMessagesV1::new(
+                        id.clone(),
+                        user_id,
+                    ),
                     transport.clone(),
                     &*token,
                 )
                 .await?;
         }
+        (self.on_connected)();
         Ok(())
     }
 }